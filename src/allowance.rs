@@ -0,0 +1,124 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{Record, TaxConfig};
+
+/// An allowance paid every month on top of the salary, e.g. for meals or housing.
+#[derive(Clone, Debug, Serialize)]
+pub struct Allowance {
+    /// The name of the allowance, exempt as `[exempt_allowance.NAME]` of the config if declared.
+    pub name: String,
+    /// The monthly amount.
+    pub amount: f64,
+}
+
+impl Allowance {
+    /// Parse `name:amount`.
+    pub(crate) fn parse(s: &str) -> Result<Self> {
+        let (name, amount) = s
+            .split_once(':')
+            .ok_or_else(|| anyhow!("expected name:amount, got `{s}`"))?;
+        Ok(Self {
+            name: name.trim().to_string(),
+            amount: amount
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid allowance `{s}`"))?,
+        })
+    }
+}
+
+impl std::fmt::Display for Allowance {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.name, self.amount)
+    }
+}
+
+/// An allowance exempt from the income tax in part or in whole, e.g. the meal allowance
+/// (误餐补助) within the local cap or the housing allowance of foreigners (外籍个人住房补贴) under
+/// the transition policy.
+#[derive(Clone, Debug, Serialize)]
+pub struct ExemptAllowance {
+    /// The part of the allowance that is exempt.
+    pub ratio: f64,
+    /// The most exempt a month, unlimited if zero.
+    pub cap: f64,
+}
+
+impl Default for ExemptAllowance {
+    fn default() -> Self {
+        Self {
+            ratio: 1.0,
+            cap: 0.0,
+        }
+    }
+}
+
+impl ExemptAllowance {
+    pub(crate) fn parse(name: &str, tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "ratio" => &mut ret.ratio,
+                "cap" => &mut ret.cap,
+                _ => bail!("unknown exempt_allowance.{name} key `{key}`"),
+            };
+            *field = number(value)
+                .ok_or_else(|| anyhow!("exempt_allowance.{name}.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+
+    /// The exempt part of a monthly allowance of `amount`.
+    pub fn exempt(&self, amount: f64) -> f64 {
+        let exempt = 0f64.max(amount * self.ratio);
+        match self.cap > 0.0 {
+            true => exempt.min(self.cap),
+            false => exempt,
+        }
+    }
+}
+
+/// The exemption used by an allowance of the year.
+#[derive(Clone, Debug, Serialize)]
+pub struct AllowanceExemption {
+    pub name: String,
+    /// The allowance paid in the months employed.
+    pub paid: f64,
+    pub exempt: f64,
+    /// The rest, taxed with the salary.
+    pub taxable: f64,
+}
+
+impl std::fmt::Display for AllowanceExemption {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}: {:.2} paid, {:.2} exempt, {:.2} taxable",
+            self.name, self.paid, self.exempt, self.taxable
+        )
+    }
+}
+
+/// The exemption used by every allowance of `record` in the year. The allowances the config does
+/// not declare are taxed in whole.
+pub fn allowance_exemptions(config: &TaxConfig, record: &Record) -> Vec<AllowanceExemption> {
+    let months = config.months_employed();
+    record
+        .allowances
+        .iter()
+        .map(|a| {
+            let exempt = config
+                .exempt_allowance
+                .get(&a.name)
+                .map_or(0.0, |e| e.exempt(a.amount) * months);
+            AllowanceExemption {
+                name: a.name.clone(),
+                paid: a.amount * months,
+                exempt,
+                taxable: a.amount * months - exempt,
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,184 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{optimize, OptimizeOptions, Record, TaxConfig};
+
+/// How the cost of a [`Benefit`] is taxed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Treatment {
+    /// Paid from the salary before tax, deducted like the personal monthly deductions.
+    PreTax,
+    /// Paid from the salary after tax.
+    PostTax,
+    /// A premium of tax-preferred commercial health insurance, deductible within the cap of the
+    /// config.
+    HealthInsurance,
+}
+
+impl std::str::FromStr for Treatment {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "pre_tax" => Self::PreTax,
+            "post_tax" => Self::PostTax,
+            "health_insurance" => Self::HealthInsurance,
+            _ => bail!("unknown treatment `{s}`, expected pre_tax, post_tax or health_insurance"),
+        })
+    }
+}
+
+/// A benefit of a cafeteria plan that may be elected, paid for from the salary.
+#[derive(Clone, Debug, Serialize)]
+pub struct Benefit {
+    pub name: String,
+    /// The annual cost taken from the salary.
+    pub cost: f64,
+    /// What the benefit is worth to you a year, the cost if not given, e.g. less for meal cards
+    /// only some restaurants take.
+    pub value: f64,
+    pub treatment: Treatment,
+}
+
+/// The most benefits [`elect`] chooses among, as it tries every set of them.
+const MAX_BENEFITS: usize = 16;
+
+/// Parse a menu of benefits from TOML, each an entry of `[[benefit]]` such as
+///
+/// ```toml
+/// [[benefit]]
+/// name = "extra housing fund"
+/// cost = 24000
+/// treatment = "pre_tax"
+///
+/// [[benefit]]
+/// name = "meal cards"
+/// cost = 6000
+/// value = 4800
+/// treatment = "post_tax"
+/// ```
+pub fn parse_benefits(text: &str) -> Result<Vec<Benefit>> {
+    let tbl: toml::Table = toml::from_str(text)?;
+    if let Some(key) = tbl.keys().find(|k| *k != "benefit") {
+        bail!("unknown key `{key}`, expected [[benefit]]");
+    }
+    let Some(benefits) = tbl.get("benefit") else {
+        return Ok(Vec::new());
+    };
+    let benefits = benefits
+        .as_array()
+        .ok_or_else(|| anyhow!("benefit is not an array of tables"))?;
+    benefits
+        .iter()
+        .enumerate()
+        .map(|(i, b)| parse_benefit(b, i).with_context(|| format!("benefit {}", i + 1)))
+        .try_collect()
+}
+
+/// Parse the `i`th entry of `[[benefit]]`.
+fn parse_benefit(b: &toml::Value, i: usize) -> Result<Benefit> {
+    let b = b.as_table().ok_or_else(|| anyhow!("not a table"))?;
+    if let Some(key) = b
+        .keys()
+        .find(|k| !["name", "cost", "value", "treatment"].contains(&k.as_str()))
+    {
+        bail!("unknown benefit key `{key}`");
+    }
+    let name = match b.get("name") {
+        Some(toml::Value::String(s)) => s.clone(),
+        Some(_) => bail!("name is not a string"),
+        None => format!("benefit {}", i + 1),
+    };
+    let amount = |key: &str| -> Result<Option<f64>> {
+        b.get(key)
+            .map(|v| number(v).ok_or_else(|| anyhow!("{key} is not a number")))
+            .transpose()
+    };
+    let cost = amount("cost")?.ok_or_else(|| anyhow!("missing key `cost`"))?;
+    anyhow::ensure!(cost >= 0.0, "the cost is negative");
+    let treatment = match b.get("treatment") {
+        Some(toml::Value::String(s)) => s.parse()?,
+        Some(_) => bail!("treatment is not a string"),
+        None => bail!("missing key `treatment`"),
+    };
+    Ok(Benefit {
+        value: amount("value")?.unwrap_or(cost),
+        name,
+        cost,
+        treatment,
+    })
+}
+
+/// A set of benefits elected, with the best movement of the record under it.
+#[derive(Clone, Debug, Serialize)]
+pub struct Election {
+    /// The names of the benefits elected.
+    pub benefits: Vec<String>,
+    pub movement: f64,
+    /// The total tax under the movement.
+    pub tax: f64,
+    /// The annual net income under the movement, less the cost of the benefits elected and plus
+    /// their value.
+    pub net_value: f64,
+}
+
+/// The result of [`elect`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Elections {
+    /// Nothing elected.
+    pub none: Election,
+    /// The set of the most net value, the smallest if several tie.
+    pub best: Election,
+}
+
+/// Choose the set of `benefits` to elect for the most net value of `record`, trying every set
+/// with the best movement of `options` for it.
+pub fn elect(
+    config: &TaxConfig,
+    record: &Record,
+    benefits: &[Benefit],
+    options: &OptimizeOptions,
+) -> Result<Elections> {
+    anyhow::ensure!(
+        benefits.len() <= MAX_BENEFITS,
+        "at most {MAX_BENEFITS} benefits, got {}",
+        benefits.len()
+    );
+    let at = |set: usize| -> Result<Election> {
+        let chosen: Vec<&Benefit> = (0..benefits.len())
+            .filter(|i| set & (1 << i) != 0)
+            .map(|i| &benefits[i])
+            .collect();
+        let mut r = record.clone();
+        for b in &chosen {
+            match b.treatment {
+                Treatment::PreTax => r.monthly_tax_deduction += b.cost / config.months_employed(),
+                Treatment::HealthInsurance => r.health_insurance += b.cost,
+                Treatment::PostTax => {}
+            }
+        }
+        let best = optimize(config, &r, options)?;
+        Ok(Election {
+            benefits: chosen.iter().map(|b| b.name.clone()).collect(),
+            movement: best.movement,
+            tax: best.after.total(),
+            net_value: chosen
+                .iter()
+                .fold(best.after.net, |a, b| a - b.cost + b.value),
+        })
+    };
+    let none = at(0)?;
+    let mut best = none.clone();
+    // By the number of benefits, so that the smallest set wins a tie.
+    let mut sets: Vec<usize> = (1..1 << benefits.len()).collect();
+    sets.sort_by_key(|s| s.count_ones());
+    for set in sets {
+        let election = at(set)?;
+        if election.net_value > best.net_value + 0.005 {
+            best = election;
+        }
+    }
+    Ok(Elections { none, best })
+}
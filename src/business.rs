@@ -0,0 +1,118 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{Record, Table, TaxConfig};
+
+/// Rules of business income (经营所得), e.g. of a sole proprietorship, taxed annually on its own
+/// with a table of five brackets.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct BusinessRules {
+    /// The annual table.
+    pub table: Table,
+    /// The part of the tax waived, e.g. halved for individual businesses.
+    pub relief: f64,
+    /// The taxable income up to which the relief applies. The tax on the income above it is not
+    /// relieved, the tax being shared by the income pro rata.
+    pub relief_cap: f64,
+}
+
+impl TryFrom<&toml::Table> for BusinessRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "table" => {
+                    ret.table = Table::parse("business.table", value)?;
+                    continue;
+                }
+                "relief" => &mut ret.relief,
+                "relief_cap" => &mut ret.relief_cap,
+                _ => bail!("unknown business key `{key}`"),
+            };
+            *field = number(value).ok_or_else(|| anyhow!("business.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+impl BusinessRules {
+    /// The tax for the annual taxable business income, less the relief on the part up to the cap.
+    pub fn tax(&self, income: f64) -> Result<f64> {
+        let tax = self.table.tax(0f64.max(income))?;
+        if income <= 0.0 {
+            return Ok(tax);
+        }
+        Ok(tax - self.relief * tax * (income.min(self.relief_cap) / income))
+    }
+}
+
+/// A split of the profit of a business between the salary of its owner and business income.
+#[derive(Clone, Debug, Serialize)]
+pub struct Draw {
+    pub monthly_salary: f64,
+    /// The taxable business income left.
+    pub business_income: f64,
+    /// The total tax.
+    pub tax: f64,
+    /// The annual net income.
+    pub net: f64,
+}
+
+/// The result of [`draw`].
+#[derive(Clone, Debug, Serialize)]
+pub struct DrawAnalysis {
+    /// The whole profit as business income, no salary paid.
+    pub before: Draw,
+    /// The salary with the most net income, the least if several tie.
+    pub after: Draw,
+}
+
+/// The most steps [`draw`] tries.
+const MAX_STEPS: f64 = 100_000.0;
+
+/// Decide how much of the profit of the business of `record` to pay its owner as salary, which
+/// the business deducts as an expense, and how much to leave as business income, for the most net
+/// income. Monthly salaries in steps of `step` are tried, up to the whole profit; the salary of
+/// the record is replaced.
+pub fn draw(config: &TaxConfig, record: &Record, step: f64) -> Result<DrawAnalysis> {
+    anyhow::ensure!(step > 0.0, "the step must be positive");
+    anyhow::ensure!(
+        config.business.is_some(),
+        "the config has no business income table"
+    );
+    let mut base = record.clone();
+    base.monthly_salary = 0.0;
+    base.salaries = None;
+    let profit = config.business_profit(&base);
+    anyhow::ensure!(profit > 0.0, "the business of the record makes no profit");
+    let months = config.months_employed();
+    let steps = (profit / months / step + 1e-9).floor();
+    anyhow::ensure!(
+        steps <= MAX_STEPS,
+        "the profit has more than {MAX_STEPS} steps of {step}, give a larger step"
+    );
+    let at = |monthly_salary: f64| -> Result<Draw> {
+        let mut r = base.clone();
+        r.monthly_salary = monthly_salary;
+        r.business_expenses += monthly_salary * months;
+        let tax = config.calc(&r)?;
+        Ok(Draw {
+            monthly_salary,
+            business_income: config.business_income(&r),
+            tax: tax.total(),
+            net: tax.net,
+        })
+    };
+    let before = at(0.0)?;
+    let mut after = before.clone();
+    for i in 1..=steps as usize {
+        let draw = at(i as f64 * step)?;
+        if draw.net > after.net + 0.005 {
+            after = draw;
+        }
+    }
+    Ok(DrawAnalysis { before, after })
+}
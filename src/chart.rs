@@ -0,0 +1,63 @@
+use crate::SweepPoint;
+
+/// The eighths of a block, from empty to full, that draw the bars of [`chart`].
+const BLOCKS: [char; 9] = [' ', '▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Draw the total tax of `curve` as a bar chart of `height` rows, one column per point, with
+/// `optimum` in the column closest to it, marked under the axis, as the points of a curve may miss
+/// it. The bars rise from the lowest tax, so that the differences between movements show.
+pub fn chart(curve: &[SweepPoint], optimum: &SweepPoint, height: usize) -> String {
+    let (Some(first), Some(last)) = (curve.first(), curve.last()) else {
+        return String::new();
+    };
+    let height = height.max(1);
+    let marked = curve
+        .iter()
+        .enumerate()
+        .min_by(|(_, a), (_, b)| {
+            (a.movement - optimum.movement)
+                .abs()
+                .total_cmp(&(b.movement - optimum.movement).abs())
+        })
+        .map_or(0, |(i, _)| i);
+    let totals: Vec<f64> = curve
+        .iter()
+        .enumerate()
+        .map(|(i, p)| if i == marked { optimum.total } else { p.total })
+        .collect();
+    let (lo, hi) = totals
+        .iter()
+        .fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), t| {
+            (lo.min(*t), hi.max(*t))
+        });
+    let labels = [format!("{hi:.2}"), format!("{lo:.2}")];
+    let pad = labels.iter().map(String::len).max().unwrap_or(0);
+    // The height of each bar in eighths of a row, at least one so that the lowest tax shows.
+    let eighths: Vec<usize> = totals
+        .iter()
+        .map(|t| match hi - lo {
+            0.0 => 1,
+            range => 1 + ((t - lo) / range * (height * 8 - 1) as f64).round() as usize,
+        })
+        .collect();
+    let mut ret = String::new();
+    for row in (0..height).rev() {
+        let label = match row {
+            r if r == height - 1 => &labels[0],
+            0 => &labels[1],
+            _ => "",
+        };
+        let bars: String = eighths
+            .iter()
+            .map(|e| BLOCKS[e.saturating_sub(row * 8).min(8)])
+            .collect();
+        ret += &format!("{label:>pad$} │{}\n", bars.trim_end());
+    }
+    ret += &format!("{:pad$} └{}\n", "", "─".repeat(curve.len()));
+    ret += &format!("{:pad$}  {:marked$}▲ {}\n", "", "", optimum.movement);
+    ret += &format!(
+        "{:pad$}  movement from {} to {}\n",
+        "", first.movement, last.movement
+    );
+    ret
+}
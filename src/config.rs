@@ -0,0 +1,2037 @@
+use std::borrow::Cow;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+
+use crate::{
+    allowance_exemptions, foreign_credits, AnnuityRules, BusinessRules, Credit, Date,
+    DonationRules, ExemptAllowance, HousingFundRules, NonResidentRules, PayrollTax, PensionRules,
+    Rebate, Record, RemunerationRules, RentalRules, ResidentStatus, SocialInsurance,
+    SpecialDeductionRules, StateTax, Surtax, SurtaxBase, Tax, VatRules,
+};
+
+/// A number in the config, either an integer or a float.
+pub(crate) fn number(v: &toml::Value) -> Option<f64> {
+    v.as_float().or_else(|| v.as_integer().map(|v| v as f64))
+}
+
+/// A ratio in the config, either a float such as `0.1` or a percentage, an integer such as `10`
+/// or a string such as `"10%"`.
+pub(crate) fn ratio(v: &toml::Value) -> Option<f64> {
+    match v {
+        toml::Value::Float(f) => Some(*f),
+        toml::Value::Integer(i) => Some(*i as f64 / 100.0),
+        toml::Value::String(s) => {
+            Some(s.trim().strip_suffix('%')?.trim().parse::<f64>().ok()? / 100.0)
+        }
+        _ => None,
+    }
+}
+
+/// An amount in the config, either a number or a string with an optional `k` or `m` suffix such
+/// as `"36k"`.
+pub(crate) fn amount(v: &toml::Value) -> Option<f64> {
+    let toml::Value::String(s) = v else {
+        return number(v);
+    };
+    let s = s.trim();
+    let (digits, scale) = match s.chars().last()? {
+        'k' | 'K' => (&s[..s.len() - 1], 1e3),
+        'm' | 'M' => (&s[..s.len() - 1], 1e6),
+        _ => (s, 1.0),
+    };
+    Some(digits.trim().parse::<f64>().ok()? * scale)
+}
+
+/// How the rules of a [`Table`] are applied.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Each bracket taxes the part of the income inside it. The year bonus is looked up by its
+    /// monthly average and the ratio of the hit bracket applies to the whole bonus.
+    #[default]
+    Marginal,
+    /// The ratio of the hit bracket applies to the whole income, minus the quick deduction of the
+    /// bracket (速算扣除数), as in the officially published tables.
+    QuickDeduction,
+    /// The polynomial of the hit bracket gives the tax of the whole income, as in the formula of
+    /// the German income tax.
+    Formula,
+}
+
+/// A bracket of a [`Table`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Rule {
+    pub ratio: f64,
+    /// The quick deduction, only used in [`Mode::QuickDeduction`].
+    pub deduction: f64,
+    /// The coefficients of the polynomial in `(income - offset) / divisor`, lowest degree first,
+    /// only used in [`Mode::Formula`].
+    pub coefficients: Vec<f64>,
+    /// The income the polynomial starts from, the lower bound of the bracket if not given.
+    pub offset: Option<f64>,
+    pub divisor: f64,
+}
+
+impl Rule {
+    /// The tax of the whole `amount` and the marginal ratio at it by the polynomial of the
+    /// bracket starting at `lower`.
+    fn formula(&self, lower: f64, amount: f64) -> (f64, f64) {
+        let y = (amount - self.offset.unwrap_or(lower)) / self.divisor;
+        let (tax, slope) = self
+            .coefficients
+            .iter()
+            .rev()
+            .fold((0.0, 0.0), |(t, s), c| (t * y + c, s * y + t));
+        (tax, slope / self.divisor)
+    }
+
+    /// Scale the amounts of the rule by `factor`, see [`Table::scale`].
+    fn scale(&mut self, factor: f64) {
+        self.deduction *= factor;
+        for c in &mut self.coefficients {
+            *c *= factor;
+        }
+        self.offset = self.offset.map(|o| o * factor);
+        self.divisor *= factor;
+    }
+
+    /// Write the polynomial of the bracket starting at `lower`.
+    fn write_formula(&self, f: &mut std::fmt::Formatter<'_>, lower: &str) -> std::fmt::Result {
+        for (i, c) in self.coefficients.iter().enumerate() {
+            match i {
+                0 => write!(f, "{c}")?,
+                1 => write!(f, " + {c}y")?,
+                _ => write!(f, " + {c}y^{i}")?,
+            }
+        }
+        if self.coefficients.len() < 2 {
+            return Ok(());
+        }
+        let y = match self.offset.map_or(lower.to_string(), |o| o.to_string()) {
+            offset if offset == "0" => "x".to_string(),
+            offset => format!("x - {offset}"),
+        };
+        match self.divisor {
+            1.0 => write!(f, ", y = {y}"),
+            d => write!(f, ", y = ({y}) / {d}"),
+        }
+    }
+}
+
+/// A bracket table mapping the upper bound of each bracket to its rule.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Table {
+    pub mode: Mode,
+    pub rules: BTreeMap<i32, Rule>,
+    /// The open-ended bracket above the highest bound, declared with `bound = "inf"`.
+    pub top: Option<Rule>,
+}
+
+impl Table {
+    /// Parse the table `name` of a config, with its `mode` and `rule` array.
+    pub(crate) fn parse(name: &str, value: &toml::Value) -> Result<Self> {
+        let mode = match value.get("mode").map(|m| m.as_str()) {
+            None => Mode::Marginal,
+            Some(Some("marginal")) => Mode::Marginal,
+            Some(Some("quick_deduction")) => Mode::QuickDeduction,
+            Some(Some("formula")) => Mode::Formula,
+            Some(m) => bail!("{name}: unknown mode {m:?}"),
+        };
+        let mut rules = BTreeMap::new();
+        let mut top = None;
+        let rule_array = value
+            .get("rule")
+            .ok_or_else(|| anyhow!("{name}: missing rule"))?
+            .as_array()
+            .ok_or_else(|| anyhow!("{name}.rule is not an array"))?;
+        anyhow::ensure!(!rule_array.is_empty(), "{name}: no rules");
+        for (i, r) in rule_array.iter().enumerate() {
+            // Rules are counted from 1, as they read in the file.
+            let name = format!("{name}.rule[{}]", i + 1);
+            anyhow::ensure!(
+                top.is_none(),
+                "{name}: comes after the rule with bound = \"inf\""
+            );
+            let deduction = match (mode, r.get("deduction")) {
+                (_, Some(d)) => {
+                    amount(d).ok_or_else(|| anyhow!("{name}: deduction is not a number"))?
+                }
+                (Mode::Marginal | Mode::Formula, None) => 0.0,
+                (Mode::QuickDeduction, None) => bail!("{name}: missing deduction"),
+            };
+            let coefficients = match (mode, r.get("coefficients")) {
+                (_, Some(toml::Value::Array(cs))) => cs
+                    .iter()
+                    .map(|c| {
+                        number(c).ok_or_else(|| anyhow!("{name}: coefficient is not a number"))
+                    })
+                    .try_collect()?,
+                (_, Some(_)) => bail!("{name}: coefficients is not an array"),
+                (Mode::Formula, None) => bail!("{name}: missing coefficients"),
+                (_, None) => Vec::new(),
+            };
+            let get = |key: &str| {
+                r.get(key)
+                    .map(|v| amount(v).ok_or_else(|| anyhow!("{name}: {key} is not a number")))
+                    .transpose()
+            };
+            let rule = Rule {
+                ratio: match (mode, r.get("ratio")) {
+                    (Mode::Formula, None) => 0.0,
+                    (_, None) => bail!("{name}: missing ratio"),
+                    (_, Some(v)) => ratio(v).ok_or_else(|| {
+                        anyhow!("{name}: ratio {v} is neither a number nor a percentage")
+                    })?,
+                },
+                deduction,
+                coefficients,
+                offset: get("offset")?,
+                divisor: match get("divisor")? {
+                    Some(0.0) => bail!("{name}: divisor is zero"),
+                    d => d.unwrap_or(1.0),
+                },
+            };
+            anyhow::ensure!(
+                (0.0..=1.0).contains(&rule.ratio),
+                "{name}: ratio {} is not between 0 and 1 (0% and 100%)",
+                rule.ratio
+            );
+            anyhow::ensure!(
+                rule.deduction >= 0.0,
+                "{name}: deduction {} is negative",
+                rule.deduction
+            );
+            match r.get("bound") {
+                Some(toml::Value::String(s)) if s == "inf" => top = Some(rule),
+                None => bail!("{name}: missing bound"),
+                Some(b) => {
+                    let bound = amount(b)
+                        .ok_or_else(|| anyhow!("{name}: bound {b} is not a number or \"inf\""))?;
+                    anyhow::ensure!(
+                        bound.fract() == 0.0 && bound.abs() <= i32::MAX as f64,
+                        "{name}: bound {b} is not a whole number"
+                    );
+                    let bound = bound as i32;
+                    match rules.last_key_value() {
+                        Some((last, _)) => anyhow::ensure!(
+                            bound > *last,
+                            "{name}: bound {bound} is not above the bound {last} before it"
+                        ),
+                        None => anyhow::ensure!(bound > 0, "{name}: bound {bound} is not positive"),
+                    }
+                    rules.insert(bound, rule);
+                }
+            }
+        }
+        Ok(Self { mode, rules, top })
+    }
+
+    /// Scale the bounds of the table by `factor`, rounded to whole amounts, e.g. to index them to
+    /// inflation. The quick deductions and the formulas scale alike, so that the tax of an amount
+    /// scaled by `factor` scales by it too.
+    pub fn scale(&mut self, factor: f64) {
+        self.rules = std::mem::take(&mut self.rules)
+            .into_iter()
+            .map(|(bound, mut rule)| {
+                rule.scale(factor);
+                ((bound as f64 * factor).round() as i32, rule)
+            })
+            .collect();
+        if let Some(top) = &mut self.top {
+            top.scale(factor);
+        }
+    }
+
+    /// The ratio of the lowest bracket, at which credits such as a basic personal amount are
+    /// given.
+    pub fn lowest_ratio(&self) -> f64 {
+        self.rules
+            .first_key_value()
+            .map(|(_, r)| r)
+            .or(self.top.as_ref())
+            .map_or(0.0, |r| r.ratio)
+    }
+
+    /// Warnings about suspicious rules of the table `name` that parse nonetheless: a tax that
+    /// jumps at a bound, a ratio that falls and a missing open-ended bracket.
+    pub fn lint(&self, name: &str) -> Vec<String> {
+        let mut ret = Vec::new();
+        let rules: Vec<_> = self
+            .rules
+            .iter()
+            .map(|(b, r)| (Some(*b as f64), r))
+            .chain(self.top.iter().map(|r| (None, r)))
+            .collect();
+        let first = rules.first().map_or(0.0, |(_, r)| r.deduction);
+        if self.mode == Mode::QuickDeduction && first != 0.0 {
+            ret.push(format!(
+                "{name}: the lowest bracket deducts {first}, so small amounts are taxed negatively"
+            ));
+        }
+        let mut lower = 0.0;
+        for w in rules.windows(2) {
+            let ((Some(bound), below), (_, above)) = (w[0], w[1]) else {
+                continue;
+            };
+            let jump = match self.mode {
+                Mode::Marginal => 0.0,
+                Mode::QuickDeduction => {
+                    (bound * above.ratio - above.deduction)
+                        - (bound * below.ratio - below.deduction)
+                }
+                Mode::Formula => above.formula(bound, bound).0 - below.formula(lower, bound).0,
+            };
+            // Published formulas are rounded, e.g. the German one jumps by cents.
+            if jump.abs() >= 1.0 {
+                let mut warning =
+                    format!("{name}: the tax jumps by {jump:.2} at the bound {bound}");
+                if self.mode == Mode::QuickDeduction {
+                    let deduction = below.deduction + bound * (above.ratio - below.ratio);
+                    warning +=
+                        &format!(", the deduction above it would be {deduction:.2} without a gap");
+                }
+                ret.push(warning);
+            }
+            if self.mode != Mode::Formula && above.ratio < below.ratio {
+                ret.push(format!(
+                    "{name}: the ratio falls from {:.2}% to {:.2}% at the bound {bound}",
+                    below.ratio * 100.0,
+                    above.ratio * 100.0
+                ));
+            }
+            lower = bound;
+        }
+        if let (None, Some(last)) = (&self.top, self.bounds().last()) {
+            ret.push(format!(
+                "{name}: no rule with bound = \"inf\", so amounts above {last} fail"
+            ));
+        }
+        ret
+    }
+
+    /// The upper bounds of the brackets, excluding the open-ended one.
+    pub fn bounds(&self) -> impl Iterator<Item = f64> + '_ {
+        self.rules.keys().map(|b| *b as f64)
+    }
+
+    /// The bracket that `amount` falls in, with the bounds of the bracket.
+    fn bracket(&self, amount: f64) -> Result<Slice> {
+        let cursor = self
+            .rules
+            .lower_bound(std::ops::Bound::Included(&(amount.ceil() as i32)));
+        let lower = cursor.peek_prev().map_or(0.0, |(b, _)| *b as f64);
+        let (upper, rule) = match cursor.peek_next() {
+            Some((b, r)) => (Some(*b as f64), r),
+            None => (
+                None,
+                self.top.as_ref().ok_or_else(|| self.exceeded(amount))?,
+            ),
+        };
+        if self.mode == Mode::Formula {
+            // Shown as `amount x ratio - deduction = tax` with the marginal ratio at the amount.
+            let (tax, ratio) = rule.formula(lower, amount);
+            let tax = 0f64.max(tax);
+            return Ok(Slice {
+                lower,
+                upper,
+                amount,
+                ratio,
+                deduction: ((amount * ratio - tax) * 100.0).round() / 100.0,
+                tax,
+            });
+        }
+        Ok(Slice {
+            lower,
+            upper,
+            amount,
+            ratio: rule.ratio,
+            deduction: rule.deduction,
+            tax: 0.0,
+        })
+    }
+
+    fn exceeded(&self, amount: f64) -> anyhow::Error {
+        anyhow!(
+            "{amount} exceeds the highest bound {} and there is no rule with bound = \"inf\"",
+            self.rules.last_key_value().map_or(0, |(b, _)| *b)
+        )
+    }
+
+    /// The brackets hit by the annual income `amount` and the tax in each of them.
+    pub fn slices(&self, amount: f64) -> Result<Vec<Slice>> {
+        match self.mode {
+            Mode::Marginal => {}
+            Mode::QuickDeduction => {
+                let mut slice = self.bracket(amount)?;
+                slice.tax = 0f64.max(amount * slice.ratio - slice.deduction);
+                return Ok(vec![slice]);
+            }
+            Mode::Formula => return Ok(vec![self.bracket(amount)?]),
+        }
+        let mut ret = Vec::new();
+        let mut last = 0.0;
+        for (rb, rule) in &self.rules {
+            let budget = (*rb as f64).min(amount) - last;
+            ret.push(Slice {
+                lower: last,
+                upper: Some(*rb as f64),
+                amount: budget,
+                ratio: rule.ratio,
+                deduction: 0.0,
+                tax: budget * rule.ratio,
+            });
+            if *rb as f64 >= amount {
+                return Ok(ret);
+            }
+            last = *rb as f64;
+        }
+        let top = self.top.as_ref().ok_or_else(|| self.exceeded(amount))?;
+        ret.push(Slice {
+            lower: last,
+            upper: None,
+            amount: amount - last,
+            ratio: top.ratio,
+            deduction: 0.0,
+            tax: (amount - last) * top.ratio,
+        });
+        Ok(ret)
+    }
+
+    /// The tax for the annual income `amount`.
+    pub fn tax(&self, amount: f64) -> Result<f64> {
+        Ok(self.slices(amount)?.iter().fold(0.0, |a, s| a + s.tax))
+    }
+
+    /// The bracket hit by the year bonus `amount`, which is found by the monthly average of the
+    /// bonus, and the tax for the whole bonus. A formula has no ratio to apply to the whole bonus,
+    /// so it taxes the bonus as an annual income of its own.
+    pub fn bonus_slice(&self, amount: f64) -> Result<Slice> {
+        if self.mode == Mode::Formula {
+            return self.bracket(amount);
+        }
+        let mut slice = self.bracket(amount / 12.0)?;
+        slice.amount = amount;
+        slice.tax = match self.mode {
+            Mode::Marginal => slice.ratio * amount,
+            _ => 0f64.max(slice.ratio * amount - slice.deduction),
+        };
+        Ok(slice)
+    }
+
+    /// The tax for the year bonus `amount`.
+    pub fn bonus_tax(&self, amount: f64) -> Result<f64> {
+        Ok(self.bonus_slice(amount)?.tax)
+    }
+
+    /// The trap intervals (盲区) of a year bonus table: right above each bound the whole bonus
+    /// jumps to a higher ratio, so a bonus inside the interval leaves less after tax than a bonus
+    /// right at the bound. A formula is continuous and has none.
+    pub fn bonus_traps(&self) -> Vec<Trap> {
+        if self.mode == Mode::Formula {
+            return Vec::new();
+        }
+        let deduction = |rule: &Rule| match self.mode {
+            Mode::Marginal | Mode::Formula => 0.0,
+            Mode::QuickDeduction => rule.deduction,
+        };
+        let rules: Vec<_> = (self.rules.iter().map(|(b, r)| (Some(*b), r)))
+            .chain(self.top.iter().map(|r| (None, r)))
+            .collect();
+        rules
+            .windows(2)
+            .filter_map(|w| {
+                let ((Some(bound), low), (_, high)) = (w[0], w[1]) else {
+                    return None;
+                };
+                let lower = bound as f64 * 12.0;
+                // The bonus above the bound whose net income equals the net income at the bound.
+                let net = lower * (1.0 - low.ratio) + deduction(low);
+                let upper = (net - deduction(high)) / (1.0 - high.ratio);
+                (upper > lower).then_some(Trap { lower, upper })
+            })
+            .collect()
+    }
+}
+
+/// A range of year bonus `(lower, upper]` that leaves less after tax than a bonus of `lower`.
+#[derive(Clone, Copy, Debug, Serialize)]
+pub struct Trap {
+    pub lower: f64,
+    pub upper: f64,
+}
+
+impl Trap {
+    pub fn contains(&self, amount: f64) -> bool {
+        amount > self.lower && amount <= self.upper
+    }
+
+    /// The closest amount to `amount` that is outside of the trap.
+    pub fn nearest_safe(&self, amount: f64) -> f64 {
+        if amount - self.lower <= self.upper - amount {
+            self.lower
+        } else {
+            self.upper
+        }
+    }
+}
+
+/// The part of an income taxed in one bracket.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Slice {
+    pub lower: f64,
+    /// The upper bound of the bracket, `None` for the open-ended one.
+    pub upper: Option<f64>,
+    /// The amount taxed in the bracket.
+    pub amount: f64,
+    pub ratio: f64,
+    /// The quick deduction subtracted from the tax.
+    pub deduction: f64,
+    pub tax: f64,
+}
+
+impl std::fmt::Display for Slice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let upper = self.upper.map_or("inf".to_string(), |u| u.to_string());
+        write!(
+            f,
+            "{:>12} - {upper:<12} {:>12.2} x {:>6.2}%",
+            self.lower,
+            self.amount,
+            self.ratio * 100.0
+        )?;
+        if self.deduction != 0.0 {
+            write!(f, " - {}", self.deduction)?;
+        }
+        write!(f, " = {:.2}", self.tax)
+    }
+}
+
+/// The brackets behind a [`Tax`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Explanation {
+    pub salary: Vec<Slice>,
+    pub year_bonus: Vec<Slice>,
+}
+
+impl std::fmt::Display for Explanation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, slices) in [("salary", &self.salary), ("year bonus", &self.year_bonus)] {
+            writeln!(f, "  {name}:")?;
+            for s in slices {
+                writeln!(f, "    {s}")?;
+            }
+            let total = slices.iter().fold(0.0, |a, s| a + s.tax);
+            writeln!(f, "    subtotal: {total:.2}")?;
+        }
+        Ok(())
+    }
+}
+
+/// The reduction of the standard deduction by `ratio` of the income above `threshold`.
+#[derive(Clone, Copy, Debug, Default, Serialize)]
+pub struct Taper {
+    pub threshold: f64,
+    pub ratio: f64,
+}
+
+impl TryFrom<&toml::Table> for Taper {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "threshold" => &mut ret.threshold,
+                "ratio" => &mut ret.ratio,
+                _ => bail!("unknown standard_deduction_taper key `{key}`"),
+            };
+            *field = number(value)
+                .ok_or_else(|| anyhow!("standard_deduction_taper.{key} is not a number"))?;
+        }
+        anyhow::ensure!(
+            ret.ratio > 0.0,
+            "standard_deduction_taper.ratio must be positive"
+        );
+        Ok(ret)
+    }
+}
+
+/// The file format of a config.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ConfigFormat {
+    #[default]
+    Toml,
+    Yaml,
+    Json,
+}
+
+impl std::str::FromStr for ConfigFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "toml" => Self::Toml,
+            "yaml" | "yml" => Self::Yaml,
+            "json" => Self::Json,
+            _ => bail!("unknown config format `{s}`, expected toml, yaml or json"),
+        })
+    }
+}
+
+impl ConfigFormat {
+    /// The format of the config file `path` by its extension, TOML if it has no other.
+    pub fn from_path(path: &std::path::Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) => ext.to_ascii_lowercase().parse().unwrap_or_default(),
+            None => Self::Toml,
+        }
+    }
+}
+
+/// The error of a YAML config when pto is built without the `yaml` feature.
+#[cfg(not(feature = "yaml"))]
+const NO_YAML: &str = "pto is built without YAML configs, build it with `--features yaml`";
+
+/// Parse a config in `format` into the tables that [`TaxConfig::with_overrides`] takes. YAML and
+/// JSON configs have the same structure as TOML ones, with the same keys.
+pub fn parse_config(text: &str, format: ConfigFormat) -> Result<toml::Table> {
+    Ok(match format {
+        ConfigFormat::Toml => toml::from_str(text)?,
+        #[cfg(feature = "yaml")]
+        ConfigFormat::Yaml => serde_yaml::from_str(text).context("invalid YAML config")?,
+        #[cfg(not(feature = "yaml"))]
+        ConfigFormat::Yaml => bail!(NO_YAML),
+        ConfigFormat::Json => serde_json::from_str(text).context("invalid JSON config")?,
+    })
+}
+
+/// Write `tbl` as the text of a config in `format`.
+pub fn render_config(tbl: &toml::Table, format: ConfigFormat) -> Result<String> {
+    Ok(match format {
+        ConfigFormat::Toml => toml::to_string(tbl)?,
+        #[cfg(feature = "yaml")]
+        ConfigFormat::Yaml => serde_yaml::to_string(tbl)?,
+        #[cfg(not(feature = "yaml"))]
+        ConfigFormat::Yaml => bail!(NO_YAML),
+        ConfigFormat::Json => serde_json::to_string_pretty(tbl)? + "\n",
+    })
+}
+
+/// The version of the config format this pto reads, given by `schema_version` in a config.
+/// Version 1 is the format of the first releases, whose tables had no `mode`: the salary table
+/// was marginal, the year bonus one a flat ratio of the whole bonus by the bracket of a month of
+/// it, and the top bound was 2147483647.
+pub const SCHEMA_VERSION: i64 = 2;
+
+/// Check that `schema_version` of a config, if given, is the one this pto reads. Configs without
+/// it are taken to be of the current version.
+fn check_schema_version(tbl: &toml::Table) -> Result<()> {
+    let version = match tbl.get("schema_version") {
+        None => return Ok(()),
+        Some(v) => v
+            .as_integer()
+            .ok_or_else(|| anyhow!("schema_version is not an integer"))?,
+    };
+    anyhow::ensure!(
+        version <= SCHEMA_VERSION,
+        "the config is of schema_version {version}, but this pto only reads up to \
+         {SCHEMA_VERSION}; upgrade pto to use it"
+    );
+    anyhow::ensure!(
+        version == SCHEMA_VERSION,
+        "the config is of schema_version {version}, run `pto config migrate` to rewrite it to \
+         {SCHEMA_VERSION}"
+    );
+    Ok(())
+}
+
+/// Rewrite a config of an older `schema_version` to [`SCHEMA_VERSION`]; a config without the key
+/// is taken to be of version 1. Returns the changes made.
+pub fn migrate_config(tbl: &mut toml::Table) -> Result<Vec<String>> {
+    let version = match tbl.get("schema_version") {
+        None => 1,
+        Some(v) => v
+            .as_integer()
+            .ok_or_else(|| anyhow!("schema_version is not an integer"))?,
+    };
+    anyhow::ensure!(
+        version <= SCHEMA_VERSION,
+        "the config is of schema_version {version}, newer than {SCHEMA_VERSION} of this pto"
+    );
+    let mut ret = Vec::new();
+    if version < 2 {
+        for (name, mode) in [("salary", "marginal"), ("year_bonus", "quick_deduction")] {
+            let Some(toml::Value::Table(table)) = tbl.get_mut(name) else {
+                continue;
+            };
+            if !table.contains_key("mode") {
+                table.insert("mode".to_string(), mode.into());
+                ret.push(format!("{name}: added mode = \"{mode}\""));
+            }
+            let Some(toml::Value::Array(rules)) = table.get_mut("rule") else {
+                continue;
+            };
+            for (i, rule) in rules.iter_mut().enumerate() {
+                let toml::Value::Table(rule) = rule else {
+                    continue;
+                };
+                if rule.get("bound").and_then(|b| b.as_integer()) == Some(i32::MAX.into()) {
+                    rule.insert("bound".to_string(), "inf".into());
+                    ret.push(format!(
+                        "{name}.rule[{}]: bound 2147483647 is now \"inf\"",
+                        i + 1
+                    ));
+                }
+                // The flat ratio of version 1 is a quick deduction of zero.
+                if mode == "quick_deduction" && !rule.contains_key("deduction") {
+                    rule.insert("deduction".to_string(), 0.into());
+                }
+            }
+        }
+    }
+    if version < SCHEMA_VERSION {
+        tbl.insert("schema_version".to_string(), SCHEMA_VERSION.into());
+        ret.push(format!("schema_version: {version} is now {SCHEMA_VERSION}"));
+    }
+    Ok(ret)
+}
+
+/// Whether `--config` names a remote config rather than a file.
+pub fn is_remote(config: &str) -> bool {
+    config.starts_with("https://")
+}
+
+/// Read the config file `path` in `format`, detected by its extension if not given. The files
+/// listed in its `include` key, relative to it, are read the same way and merged under it in
+/// order, table by table, so that a later file replaces the keys and arrays of an earlier one.
+pub fn read_config(path: &Path, format: Option<ConfigFormat>) -> Result<toml::Table> {
+    read_included(path, format, &mut Vec::new())
+}
+
+/// [`read_config`] within the chain of files `including` it.
+fn read_included(
+    path: &Path,
+    format: Option<ConfigFormat>,
+    including: &mut Vec<PathBuf>,
+) -> Result<toml::Table> {
+    let canonical = path
+        .canonicalize()
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    anyhow::ensure!(
+        !including.contains(&canonical),
+        "{} includes itself",
+        path.display()
+    );
+    let text =
+        std::fs::read_to_string(path).with_context(|| format!("cannot read {}", path.display()))?;
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(path));
+    let mut tbl = parse_config(&text, format).with_context(|| format!("in {}", path.display()))?;
+    let includes: Vec<String> = match tbl.remove("include") {
+        None => Vec::new(),
+        Some(toml::Value::String(s)) => vec![s],
+        Some(toml::Value::Array(a)) => a
+            .into_iter()
+            .map(|v| match v {
+                toml::Value::String(s) => Ok(s),
+                _ => bail!("{}: include is not a list of paths", path.display()),
+            })
+            .try_collect()?,
+        Some(_) => bail!("{}: include is not a list of paths", path.display()),
+    };
+    including.push(canonical);
+    let mut ret = toml::Table::new();
+    for include in includes {
+        let dir = path.parent().unwrap_or(Path::new("."));
+        deep_merge(
+            &mut ret,
+            read_included(&dir.join(include), None, including)?,
+        );
+    }
+    including.pop();
+    deep_merge(&mut ret, tbl);
+    Ok(ret)
+}
+
+/// Merge `other` into `tbl` table by table, replacing any other value, arrays included.
+fn deep_merge(tbl: &mut toml::Table, other: toml::Table) {
+    for (key, value) in other {
+        match (tbl.get_mut(&key), value) {
+            (Some(toml::Value::Table(t)), toml::Value::Table(value)) => deep_merge(t, value),
+            (_, value) => {
+                tbl.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Replace the tables of the config `overrides` with those of its `[profile.NAME]` section named
+/// `profile`, if given, and drop the profiles. Surtaxes and credits are replaced by name.
+pub fn select_profile(overrides: &mut toml::Table, profile: Option<&str>) -> Result<()> {
+    let profiles = match overrides.remove("profile") {
+        Some(toml::Value::Table(t)) => t,
+        Some(_) => bail!("profile is not a table"),
+        None => toml::Table::new(),
+    };
+    let Some(name) = profile else {
+        return Ok(());
+    };
+    match profiles.get(name) {
+        Some(toml::Value::Table(t)) => merge(overrides, t.clone()),
+        Some(_) => bail!("profile.{name} is not a table"),
+        None if profiles.is_empty() => bail!("unknown profile `{name}`, the config has none"),
+        None => {
+            let names: Vec<_> = profiles.keys().map(String::as_str).collect();
+            bail!(
+                "unknown profile `{name}`, expected one of {}",
+                names.join(", ")
+            )
+        }
+    }
+    Ok(())
+}
+
+/// The day a version of a table takes effect from, for its key in a table keyed by year, e.g.
+/// `[salary.2024]` from January 1, or by date, e.g. `[salary.2024-07-01]`.
+fn version_date(key: &str) -> Option<Date> {
+    match key.len() {
+        4 => key.parse().ok().map(Date::year_start),
+        10 => key.parse().ok(),
+        _ => None,
+    }
+}
+
+/// The dates of the versions of `tbl`, if it is keyed by them.
+fn versions(tbl: &toml::Table) -> Option<Vec<Date>> {
+    let dates: Vec<_> = tbl.keys().map_while(|k| version_date(k)).collect();
+    (!dates.is_empty() && dates.len() == tbl.len()).then_some(dates)
+}
+
+/// Resolve the tables of `overrides` keyed by year, e.g. `[salary.2024]` and `[salary.2025]`, or
+/// by the date they take effect, e.g. `[salary.2024-07-01]`, to the version in force on `date`.
+pub fn select_date(overrides: &mut toml::Table, date: Date) -> Result<()> {
+    select_date_in(overrides, date, "")
+}
+
+/// [`select_date`] within the table at `path`.
+fn select_date_in(tbl: &mut toml::Table, date: Date, path: &str) -> Result<()> {
+    for (key, value) in tbl.iter_mut() {
+        let path = if path.is_empty() {
+            key.clone()
+        } else {
+            format!("{path}.{key}")
+        };
+        while let toml::Value::Table(t) = value {
+            let Some(dates) = versions(t) else {
+                select_date_in(t, date, &path)?;
+                break;
+            };
+            let Some(i) = (0..dates.len())
+                .filter(|i| dates[*i] <= date)
+                .max_by_key(|i| dates[*i])
+            else {
+                bail!(
+                    "{path} has no table in force on {date}, the earliest is from {}",
+                    dates.iter().min().unwrap()
+                );
+            };
+            let key = t.keys().nth(i).unwrap().clone();
+            *value = t.remove(&key).unwrap();
+        }
+    }
+    Ok(())
+}
+
+/// The periods of `year` in which the same versions of the tables of `overrides` are in force,
+/// each as the day it starts and its share of the year. A single period of the whole year if no
+/// table changes within it.
+pub fn date_segments(overrides: &toml::Table, year: i32) -> Vec<(Date, f64)> {
+    fn collect(tbl: &toml::Table, dates: &mut Vec<Date>) {
+        if let Some(versions) = versions(tbl) {
+            dates.extend(versions);
+        }
+        for value in tbl.values() {
+            if let toml::Value::Table(t) = value {
+                collect(t, dates);
+            }
+        }
+    }
+    let start = Date::year_start(year);
+    let end = Date::year_start(year + 1);
+    let mut dates = vec![start];
+    collect(overrides, &mut dates);
+    dates.retain(|d| *d >= start && *d < end);
+    dates.sort();
+    dates.dedup();
+    let bounds: Vec<_> = dates.iter().chain([&end]).map(Date::days).collect();
+    let days = (end.days() - start.days()) as f64;
+    dates
+        .into_iter()
+        .zip(bounds.windows(2))
+        .map(|(d, w)| (d, (w[1] - w[0]) as f64 / days))
+        .collect()
+}
+
+/// The built-in China IIT tables, effective since 2019.
+pub const DEFAULT_CONFIG: &str = include_str!("../tables/cn.toml");
+
+/// The built-in regimes, selected by name with the `region` key or `--region`. Each of them
+/// replaces the China tables as a whole.
+pub const REGIONS: [(&str, &str); 10] = [
+    ("cn", DEFAULT_CONFIG),
+    ("hk", include_str!("../tables/region/hk.toml")),
+    ("us-2024", include_str!("../tables/region/us-2024.toml")),
+    ("uk-2024", include_str!("../tables/region/uk-2024.toml")),
+    ("de-2024", include_str!("../tables/region/de-2024.toml")),
+    ("in-2024", include_str!("../tables/region/in-2024.toml")),
+    ("sg-2024", include_str!("../tables/region/sg-2024.toml")),
+    ("ca-2024", include_str!("../tables/region/ca-2024.toml")),
+    ("au-2024", include_str!("../tables/region/au-2024.toml")),
+    ("jp-2024", include_str!("../tables/region/jp-2024.toml")),
+];
+
+/// A config for the region `name` to start from: the built-in tables of the region with every
+/// key commented out, so that uncommenting a table replaces it, and examples of what to add.
+pub fn init_config(name: &str) -> Result<String> {
+    let (region, preset) = find_region(name)?;
+    let mut ret = format!(
+        "# The config of pto, see `pto --help`. It starts from the built-in tables of the region\n\
+         # below. To change a table, uncomment all of it, e.g. the `[salary]` header with every\n\
+         # `[[salary.rule]]`, and edit it; the other tables stay those of the region. Check the\n\
+         # result with `pto config lint`.\n\
+         region = \"{region}\"\n\n"
+    );
+    for line in preset.lines() {
+        match line.trim_start() {
+            "" => ret.push('\n'),
+            l if l.starts_with('#') => ret += &format!("{line}\n"),
+            _ => ret += &format!("# {line}\n"),
+        }
+    }
+    ret += INIT_EXAMPLES;
+    Ok(ret)
+}
+
+/// The examples at the end of [`init_config`].
+const INIT_EXAMPLES: &str = "
+# Examples of what the tables of a region may be extended with.
+#
+# Social insurance and housing fund contributions derived from the salary, instead of giving them
+# in the deduction of each record (or pass `--city`):
+#
+#   [social_insurance]
+#   floor = 6821
+#   cap = 35283
+#   [[social_insurance.item]]
+#   name = \"pension\"
+#   employee = 0.08
+#   employer = 0.16
+#
+# A tax on top of the income tax, e.g. church tax:
+#
+#   [surtax.church]
+#   base = \"tax\"
+#   ratio = 0.09
+#
+# A credit against the income tax, e.g. for each child claimed as `children_education`:
+#
+#   [credit.child]
+#   amount = 2000
+#   per = \"children_education\"
+#   refundable = false
+#   phase_out = { threshold = 200000, ratio = 0.05 }
+";
+
+/// The tables of the region `name`, downloaded by `pto update-tables` or built in.
+pub fn region_preset(name: &str) -> Result<toml::Table> {
+    let (_, preset) = find_region(name)?;
+    Ok(toml::from_str(&preset)?)
+}
+
+/// The name and tables of the region `name`, ignoring case: the one downloaded by
+/// `pto update-tables` if any, or else the entry of [`REGIONS`].
+fn find_region(name: &str) -> Result<(String, Cow<'static, str>)> {
+    let name = name.to_ascii_lowercase();
+    #[cfg(feature = "remote")]
+    if let Some(preset) = crate::remote::downloaded_region(&name)? {
+        return Ok((name, preset.into()));
+    }
+    REGIONS
+        .iter()
+        .find(|(region, _)| region.eq_ignore_ascii_case(&name))
+        .map(|(region, preset)| (region.to_string(), Cow::Borrowed(*preset)))
+        .ok_or_else(|| {
+            let regions: Vec<_> = REGIONS.iter().map(|(region, _)| *region).collect();
+            anyhow!(
+                "unknown region `{name}`, expected one of {}",
+                regions.join(", ")
+            )
+        })
+}
+
+/// The built-in tables of the region that `overrides` declares, China by default, with the tables
+/// of each filing status in `overrides` replacing those of the status in the region.
+fn base_tables(overrides: &toml::Table) -> Result<toml::Table> {
+    let mut tbl = match overrides.get("region") {
+        Some(toml::Value::String(region)) => region_preset(region)?,
+        Some(_) => bail!("region is not a string"),
+        None => toml::from_str(DEFAULT_CONFIG)?,
+    };
+    if let Some(statuses) = overrides.get("filing_statuses") {
+        let toml::Value::Table(statuses) = statuses else {
+            bail!("filing_statuses is not a table");
+        };
+        let toml::Value::Table(all) = tbl
+            .entry("filing_statuses")
+            .or_insert_with(|| toml::Table::new().into())
+        else {
+            bail!("filing_statuses is not a table");
+        };
+        for (name, status) in statuses {
+            match (all.get_mut(name), status) {
+                (Some(toml::Value::Table(t)), toml::Value::Table(status)) => {
+                    t.extend(status.clone())
+                }
+                _ => {
+                    all.insert(name.clone(), status.clone());
+                }
+            }
+        }
+    }
+    Ok(tbl)
+}
+
+/// The tables of a config whose entries are replaced by name instead of as a whole.
+const NAMED: [&str; 2] = ["credit", "surtax"];
+
+/// Extend `tbl` with the tables of `other`, merging the [`NAMED`] ones by name.
+pub(crate) fn merge(tbl: &mut toml::Table, mut other: toml::Table) {
+    for key in NAMED {
+        let Some(toml::Value::Table(more)) = other.get(key) else {
+            continue;
+        };
+        match tbl.get_mut(key) {
+            Some(toml::Value::Table(named)) => named.extend(more.clone()),
+            _ => {
+                tbl.insert(key.to_string(), more.clone().into());
+            }
+        }
+        other.remove(key);
+    }
+    tbl.extend(other);
+}
+
+/// The filing statuses of the region that `overrides` declares, in the order of their names.
+pub fn filing_statuses(overrides: &toml::Table) -> Result<Vec<String>> {
+    let tbl = base_tables(overrides)?;
+    match tbl.get("filing_statuses") {
+        Some(toml::Value::Table(statuses)) => Ok(statuses.keys().cloned().collect()),
+        Some(_) => bail!("filing_statuses is not a table"),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// The built-in social insurance presets of cities, selected by name.
+pub const CITIES: [(&str, &str); 4] = [
+    ("beijing", include_str!("../tables/city/beijing.toml")),
+    ("shanghai", include_str!("../tables/city/shanghai.toml")),
+    ("shenzhen", include_str!("../tables/city/shenzhen.toml")),
+    ("hangzhou", include_str!("../tables/city/hangzhou.toml")),
+];
+
+/// The tables of the city preset `name`, to be passed to [`TaxConfig::with_overrides`].
+pub fn city_preset(name: &str) -> Result<toml::Table> {
+    let (_, preset) = CITIES
+        .iter()
+        .find(|(city, _)| city.eq_ignore_ascii_case(name))
+        .ok_or_else(|| {
+            let cities: Vec<_> = CITIES.iter().map(|(city, _)| *city).collect();
+            anyhow!(
+                "unknown city `{name}`, expected one of {}",
+                cities.join(", ")
+            )
+        })?;
+    Ok(toml::from_str(preset)?)
+}
+
+/// Tax rules for salary and year bonus.
+#[derive(Clone, Serialize)]
+pub struct TaxConfig {
+    /// The name of the built-in regime the config is based on.
+    pub region: String,
+    pub salary: Table,
+    /// Whether the year bonus is taxed apart from salary. Without it the bonus is salary and
+    /// `year_bonus` is not needed.
+    pub separate_bonus: bool,
+    pub year_bonus: Table,
+    pub special_deduction: SpecialDeductionRules,
+    pub social_insurance: SocialInsurance,
+    /// The tax-exempt part of severance, 3 times the local average annual wage.
+    pub severance_exemption: f64,
+    /// The annual deductible premium of tax-preferred commercial health insurance.
+    pub health_insurance_cap: f64,
+    /// The allowances exempt from the income tax by name, declared as `[exempt_allowance.NAME]`.
+    pub exempt_allowance: BTreeMap<String, ExemptAllowance>,
+    /// The consecutive years of residence in which foreign income stays exempt, 5 under the
+    /// six-year rule.
+    pub foreign_exemption_years: u32,
+    pub remuneration: RemunerationRules,
+    pub rental: RentalRules,
+    pub vat: VatRules,
+    pub business: Option<BusinessRules>,
+    pub private_pension: PensionRules,
+    pub enterprise_annuity: AnnuityRules,
+    pub supplementary_housing_fund: HousingFundRules,
+    pub donation: DonationRules,
+    pub resident_status: ResidentStatus,
+    pub non_resident: NonResidentRules,
+    /// The first and the last month employed in the year, 1 to 12, e.g. 5 when joining in May.
+    /// Salary is paid, and the standard deduction, the monthly deductions and the social insurance
+    /// are prorated, by the months in between.
+    pub start_month: u32,
+    pub end_month: u32,
+    /// The annual deduction from the salary income that every taxpayer gets, e.g. the US
+    /// standard deduction.
+    pub standard_deduction: f64,
+    /// How the standard deduction shrinks for high incomes, e.g. the UK personal allowance.
+    pub standard_deduction_taper: Option<Taper>,
+    /// The filing status picked from the `filing_statuses` of the region, if it has any.
+    pub filing_status: Option<String>,
+    pub payroll_tax: PayrollTax,
+    pub state: Option<StateTax>,
+    /// The basic personal amount, credited against the salary tax at the lowest ratio of the
+    /// salary table, e.g. in Canada.
+    pub personal_amount: f64,
+    /// Whether the salary income is taxed as a couple's, twice the tax of half of it, e.g. the
+    /// splitting (Ehegattensplitting) of Germany.
+    pub splitting: bool,
+    pub rebate: Option<Rebate>,
+    /// The taxes charged on top of the income tax by name, declared as `[surtax.NAME]`.
+    pub surtax: BTreeMap<String, Surtax>,
+    /// The credits against the income tax by name, declared as `[credit.NAME]`.
+    pub credit: BTreeMap<String, Credit>,
+    /// A table whose tax on the annual wages is deducted from them instead, e.g. the employment
+    /// income deduction of Japan. It must have an open-ended bracket.
+    pub employment_deduction: Option<Table>,
+    /// A table applied to the salary income before the allowances, whose tax caps the one of
+    /// `salary`, e.g. the standard rate of Hong Kong.
+    pub standard_rate: Option<Table>,
+}
+
+impl TryFrom<toml::Table> for TaxConfig {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: toml::Table) -> Result<Self> {
+        let parse = |name: &str| {
+            let value = tbl
+                .get(name)
+                .ok_or_else(|| anyhow!("missing table {name}"))?;
+            Table::parse(name, value)
+        };
+        let month = |name: &str, default: u32| -> Result<u32> {
+            match tbl.get(name) {
+                Some(toml::Value::Integer(n)) if (1..=12).contains(n) => Ok(*n as u32),
+                Some(_) => bail!("{name} is not a month from 1 to 12"),
+                None => Ok(default),
+            }
+        };
+        let separate_bonus = match tbl.get("separate_bonus") {
+            Some(toml::Value::Boolean(b)) => *b,
+            Some(_) => bail!("separate_bonus is not a boolean"),
+            None => true,
+        };
+        let (start_month, end_month) = (month("start_month", 1)?, month("end_month", 12)?);
+        anyhow::ensure!(
+            start_month <= end_month,
+            "start_month {start_month} is after end_month {end_month}"
+        );
+        Ok(Self {
+            region: match tbl.get("region") {
+                Some(toml::Value::String(s)) => s.clone(),
+                Some(_) => bail!("region is not a string"),
+                None => "cn".to_string(),
+            },
+            salary: parse("salary")?,
+            separate_bonus,
+            year_bonus: match tbl.get("year_bonus") {
+                None if !separate_bonus => Default::default(),
+                _ => parse("year_bonus")?,
+            },
+            special_deduction: match tbl.get("special_deduction") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("special_deduction is not a table"),
+                None => Default::default(),
+            },
+            social_insurance: match tbl.get("social_insurance") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("social_insurance is not a table"),
+                None => Default::default(),
+            },
+            severance_exemption: match tbl.get("severance_exemption") {
+                Some(v) => {
+                    number(v).ok_or_else(|| anyhow!("severance_exemption is not a number"))?
+                }
+                None => 0.0,
+            },
+            health_insurance_cap: match tbl.get("health_insurance_cap") {
+                Some(v) => {
+                    number(v).ok_or_else(|| anyhow!("health_insurance_cap is not a number"))?
+                }
+                None => 0.0,
+            },
+            exempt_allowance: match tbl.get("exempt_allowance") {
+                Some(toml::Value::Table(t)) => t
+                    .iter()
+                    .map(|(name, v)| match v {
+                        toml::Value::Table(v) => {
+                            Ok((name.clone(), ExemptAllowance::parse(name, v)?))
+                        }
+                        _ => bail!("exempt_allowance.{name} is not a table"),
+                    })
+                    .try_collect()?,
+                Some(_) => bail!("exempt_allowance is not a table"),
+                None => Default::default(),
+            },
+            foreign_exemption_years: match tbl.get("foreign_exemption_years") {
+                Some(toml::Value::Integer(n)) => (*n)
+                    .try_into()
+                    .context("foreign_exemption_years is negative")?,
+                Some(_) => bail!("foreign_exemption_years is not an integer"),
+                None => 0,
+            },
+            remuneration: match tbl.get("remuneration") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("remuneration is not a table"),
+                None => Default::default(),
+            },
+            rental: match tbl.get("rental") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("rental is not a table"),
+                None => Default::default(),
+            },
+            vat: match tbl.get("vat") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("vat is not a table"),
+                None => Default::default(),
+            },
+            business: match tbl.get("business") {
+                Some(toml::Value::Table(t)) => Some(t.try_into()?),
+                Some(_) => bail!("business is not a table"),
+                None => None,
+            },
+            private_pension: match tbl.get("private_pension") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("private_pension is not a table"),
+                None => Default::default(),
+            },
+            enterprise_annuity: match tbl.get("enterprise_annuity") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("enterprise_annuity is not a table"),
+                None => Default::default(),
+            },
+            supplementary_housing_fund: match tbl.get("supplementary_housing_fund") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("supplementary_housing_fund is not a table"),
+                None => Default::default(),
+            },
+            donation: match tbl.get("donation") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("donation is not a table"),
+                None => Default::default(),
+            },
+            resident_status: match tbl.get("resident_status") {
+                Some(toml::Value::String(s)) => s.parse()?,
+                Some(_) => bail!("resident_status is not a string"),
+                None => Default::default(),
+            },
+            non_resident: match tbl.get("non_resident") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("non_resident is not a table"),
+                None => Default::default(),
+            },
+            start_month,
+            end_month,
+            standard_deduction: match tbl.get("standard_deduction") {
+                Some(v) => {
+                    number(v).ok_or_else(|| anyhow!("standard_deduction is not a number"))?
+                }
+                None => 0.0,
+            },
+            standard_deduction_taper: match tbl.get("standard_deduction_taper") {
+                Some(toml::Value::Table(t)) => Some(t.try_into()?),
+                Some(_) => bail!("standard_deduction_taper is not a table"),
+                None => None,
+            },
+            filing_status: match tbl.get("filing_status") {
+                Some(toml::Value::String(s)) => Some(s.clone()),
+                Some(_) => bail!("filing_status is not a string"),
+                None => None,
+            },
+            payroll_tax: match tbl.get("payroll_tax") {
+                Some(toml::Value::Table(t)) => t.try_into()?,
+                Some(_) => bail!("payroll_tax is not a table"),
+                None => Default::default(),
+            },
+            state: match tbl.get("state") {
+                Some(toml::Value::Table(t)) => Some(t.try_into()?),
+                Some(_) => bail!("state is not a table"),
+                None => None,
+            },
+            personal_amount: match tbl.get("personal_amount") {
+                Some(v) => number(v).ok_or_else(|| anyhow!("personal_amount is not a number"))?,
+                None => 0.0,
+            },
+            splitting: match tbl.get("splitting") {
+                Some(toml::Value::Boolean(b)) => *b,
+                Some(_) => bail!("splitting is not a boolean"),
+                None => false,
+            },
+            rebate: match tbl.get("rebate") {
+                Some(toml::Value::Table(t)) => Some(t.try_into()?),
+                Some(_) => bail!("rebate is not a table"),
+                None => None,
+            },
+            employment_deduction: match tbl.get("employment_deduction") {
+                Some(v) => {
+                    let table = Table::parse("employment_deduction", v)?;
+                    anyhow::ensure!(
+                        table.top.is_some(),
+                        "employment_deduction: missing rule with bound = \"inf\""
+                    );
+                    Some(table)
+                }
+                None => None,
+            },
+            surtax: match tbl.get("surtax") {
+                Some(toml::Value::Table(t)) => t
+                    .iter()
+                    .map(|(name, v)| match v {
+                        toml::Value::Table(v) => Ok((name.clone(), Surtax::parse(name, v)?)),
+                        _ => bail!("surtax.{name} is not a table"),
+                    })
+                    .try_collect()?,
+                Some(_) => bail!("surtax is not a table"),
+                None => Default::default(),
+            },
+            credit: match tbl.get("credit") {
+                Some(toml::Value::Table(t)) => t
+                    .iter()
+                    .map(|(name, v)| match v {
+                        toml::Value::Table(v) => Ok((name.clone(), Credit::parse(name, v)?)),
+                        _ => bail!("credit.{name} is not a table"),
+                    })
+                    .try_collect()?,
+                Some(_) => bail!("credit is not a table"),
+                None => Default::default(),
+            },
+            standard_rate: match tbl.get("standard_rate") {
+                Some(v) => Some(Table::parse("standard_rate", v)?),
+                None => None,
+            },
+        })
+    }
+}
+
+impl Default for TaxConfig {
+    fn default() -> Self {
+        Self::with_overrides(toml::Table::new()).expect("the built-in tables are valid")
+    }
+}
+
+impl std::fmt::Display for TaxConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for (name, table) in self.tables() {
+            writeln!(f, "[{name}]")?;
+            let mut last = "0".to_string();
+            let bounds = table.rules.iter().map(|(b, r)| (b.to_string(), r));
+            for (bound, rule) in bounds.chain(table.top.iter().map(|r| ("inf".to_string(), r))) {
+                write!(f, "{last:>12} - {bound:<12} ")?;
+                match table.mode {
+                    Mode::Marginal => write!(f, "{:>6.2}%", rule.ratio * 100.0)?,
+                    Mode::QuickDeduction => {
+                        write!(f, "{:>6.2}% {:>12}", rule.ratio * 100.0, rule.deduction)?
+                    }
+                    Mode::Formula => rule.write_formula(f, &last)?,
+                }
+                writeln!(f)?;
+                last = bound;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TaxConfig {
+    /// Load the built-in tables of the region that `overrides` declares with the `region` key,
+    /// China by default, replacing each of them that `overrides` declares. The tables of the
+    /// `filing_status` in `filing_statuses` replace the ones of the region first, and a config may
+    /// replace tables of a filing status the same way. Surtaxes and credits are replaced one by
+    /// one, so a config adds one without restating those of the region.
+    pub fn with_overrides(mut overrides: toml::Table) -> Result<Self> {
+        check_schema_version(&overrides)?;
+        overrides.remove("schema_version");
+        let mut tbl = base_tables(&overrides)?;
+        overrides.remove("filing_statuses");
+        let status = overrides
+            .get("filing_status")
+            .or_else(|| tbl.get("filing_status"))
+            .cloned();
+        match (tbl.remove("filing_statuses"), status) {
+            (Some(toml::Value::Table(statuses)), Some(toml::Value::String(status))) => {
+                match statuses.get(&status) {
+                    Some(toml::Value::Table(t)) => merge(&mut tbl, t.clone()),
+                    Some(_) => bail!("filing_statuses.{status} is not a table"),
+                    None => {
+                        let names: Vec<_> = statuses.keys().map(String::as_str).collect();
+                        bail!(
+                            "unknown filing status `{status}`, expected one of {}",
+                            names.join(", ")
+                        )
+                    }
+                }
+            }
+            (Some(toml::Value::Table(_)), _) => bail!("filing_status is missing or not a string"),
+            (Some(_), _) => bail!("filing_statuses is not a table"),
+            (None, Some(_)) => bail!("the region has no filing statuses"),
+            (None, None) => {}
+        }
+        merge(&mut tbl, overrides);
+        Self::try_from(tbl)
+    }
+
+    /// Scale the bounds of every bracket table by `factor`, see [`Table::scale`], e.g. to index
+    /// them to inflation. The deductions and the social insurance stay as they are.
+    pub fn index_brackets(&mut self, factor: f64) {
+        self.salary.scale(factor);
+        self.year_bonus.scale(factor);
+        self.non_resident.table.scale(factor);
+        for table in [
+            self.standard_rate.as_mut(),
+            self.state.as_mut().map(|s| &mut s.table),
+            self.employment_deduction.as_mut(),
+            self.business.as_mut().map(|b| &mut b.table),
+        ]
+        .into_iter()
+        .flatten()
+        {
+            table.scale(factor);
+        }
+    }
+
+    /// The bracket tables in effect by name.
+    pub fn tables(&self) -> impl Iterator<Item = (&'static str, &Table)> {
+        [
+            ("salary", Some(&self.salary)),
+            (
+                "year_bonus",
+                self.separate_bonus.then_some(&self.year_bonus),
+            ),
+            ("standard_rate", self.standard_rate.as_ref()),
+            ("state.table", self.state.as_ref().map(|s| &s.table)),
+            ("employment_deduction", self.employment_deduction.as_ref()),
+            ("business.table", self.business.as_ref().map(|b| &b.table)),
+        ]
+        .into_iter()
+        .filter_map(|(name, table)| Some((name, table?)))
+    }
+
+    /// Warnings about the tables in effect, see [`Table::lint`]. Errors such as unordered bounds
+    /// already fail to load.
+    pub fn lint(&self) -> Vec<String> {
+        self.tables()
+            .flat_map(|(name, table)| table.lint(name))
+            .collect()
+    }
+
+    /// The trap interval of the year bonus table that `bonus` falls in, if any.
+    pub fn bonus_trap(&self, bonus: f64) -> Option<Trap> {
+        self.year_bonus
+            .bonus_traps()
+            .into_iter()
+            .find(|t| t.contains(bonus))
+    }
+
+    /// Explain the tax for the given record bracket by bracket.
+    pub fn explain(&self, r: &Record) -> Result<Explanation> {
+        if !self.separate_bonus && r.year_bonus != 0.0 {
+            return self.explain(&r.with_movement(self, r.year_bonus)?);
+        }
+        anyhow::ensure!(
+            self.resident_status == ResidentStatus::Resident,
+            "only the tax of residents can be explained"
+        );
+        Ok(Explanation {
+            salary: self.salary_tax_slices(r)?,
+            year_bonus: match self.separate_bonus {
+                true => vec![self
+                    .year_bonus
+                    .bonus_slice(r.year_bonus)
+                    .context("year bonus")?],
+                false => Vec::new(),
+            },
+        })
+    }
+
+    /// The brackets of the salary tax of the record: those of the salary table, or those of the
+    /// standard rate table if it taxes less.
+    pub fn salary_tax_slices(&self, r: &Record) -> Result<Vec<Slice>> {
+        let slices = self
+            .salary_slices(self.salary_income(r))
+            .context("salary")?;
+        let Some(standard_rate) = &self.standard_rate else {
+            return Ok(slices);
+        };
+        let standard = standard_rate
+            .slices(self.standard_rate_income(r))
+            .context("standard rate")?;
+        let total = |slices: &[Slice]| slices.iter().fold(0.0, |a, s| a + s.tax);
+        Ok(match total(&standard) < total(&slices) {
+            true => standard,
+            false => slices,
+        })
+    }
+
+    /// The salary income taxed by [`Self::standard_rate`], which is before the allowances: the
+    /// standard deduction and the special additional deductions.
+    pub fn standard_rate_income(&self, r: &Record) -> f64 {
+        let income = self.income_before_standard_deduction(r);
+        0f64.max(
+            self.raw_salary_income(r)
+                + self.standard_deduction(income)
+                + self.special_deduction.annual(&r.special),
+        )
+    }
+
+    /// The brackets of the salary table hit by the annual salary income `amount`. With
+    /// [`Self::splitting`], they are the brackets of half of it, doubled.
+    pub fn salary_slices(&self, amount: f64) -> Result<Vec<Slice>> {
+        if !self.splitting {
+            return self.salary.slices(amount);
+        }
+        let mut slices = self.salary.slices(amount / 2.0)?;
+        for s in &mut slices {
+            s.lower *= 2.0;
+            s.upper = s.upper.map(|u| u * 2.0);
+            s.amount *= 2.0;
+            s.deduction *= 2.0;
+            s.tax *= 2.0;
+        }
+        Ok(slices)
+    }
+
+    /// The bounds of the salary income at which it enters another bracket.
+    pub fn salary_bounds(&self) -> impl Iterator<Item = f64> + '_ {
+        let scale = if self.splitting { 2.0 } else { 1.0 };
+        self.salary.bounds().map(move |b| b * scale)
+    }
+
+    /// The surtaxes on the `income_tax`, the taxable `salary` income and the gross `total` income.
+    pub fn surtax(&self, income_tax: f64, salary: f64, total: f64) -> f64 {
+        self.surtax.values().fold(0.0, |a, s| {
+            a + s.tax(match s.base {
+                SurtaxBase::Tax => income_tax,
+                SurtaxBase::Salary => salary,
+                SurtaxBase::Total => total,
+            })
+        })
+    }
+
+    /// The credits of the record against the `income_tax`. The non-refundable ones reduce it to
+    /// zero at most, the refundable ones are paid out beyond it. They phase out with the income
+    /// before the standard deduction.
+    pub fn credits(&self, r: &Record, income_tax: f64) -> f64 {
+        let income = self.income_before_standard_deduction(r);
+        let (refundable, other) = self.credit.values().fold((0.0, 0.0), |(a, b), c| {
+            let amount = c.amount(income, &r.special);
+            match c.refundable {
+                true => (a + amount, b),
+                false => (a, b + amount),
+            }
+        });
+        refundable + other.min(0f64.max(income_tax))
+    }
+
+    /// The annual taxable salary income of the record, including the movement, the other bonuses
+    /// and the remuneration, after the monthly and the standard deduction, the social insurance,
+    /// the special additional deductions, the private pension, the enterprise annuity, the health
+    /// insurance and donations. The deductions offset the moved bonus as well, as they do at the
+    /// annual reconciliation.
+    pub fn salary_income(&self, r: &Record) -> f64 {
+        0f64.max(self.raw_salary_income(r))
+    }
+
+    /// The salary income before it is floored at zero, which keeps it linear in the movement.
+    pub(crate) fn raw_salary_income(&self, r: &Record) -> f64 {
+        let income = self.income_before_donations(r);
+        income - self.donation.deduction(r, income)
+    }
+
+    /// The shifts of [`Self::income_before_donations`] at which the salary income crosses a
+    /// bracket bound or zero, the shifts at which a taper of the standard deduction starts or
+    /// ends, and those at which the income of the state tax crosses one of its
+    /// [`StateTax::bounds`]. The salary tax is linear in such a shift between them.
+    pub(crate) fn salary_crossings(&self, r: &Record) -> Vec<f64> {
+        let base = self.income_before_donations(r);
+        let mut ret: Vec<_> = self
+            .salary_bounds()
+            .chain([0.0])
+            .chain(self.rebate.iter().flat_map(|r| r.bounds()))
+            .flat_map(|b| self.donation.crossings(r, b))
+            .map(|x| x - base)
+            .collect();
+        let income = self.salary_income(r);
+        for surtax in self
+            .surtax
+            .values()
+            .filter(|s| s.base == SurtaxBase::Salary)
+        {
+            ret.extend(surtax.bounds().into_iter().map(|b| b - income));
+        }
+        if let Some(standard_rate) = &self.standard_rate {
+            let income = self.standard_rate_income(r);
+            ret.extend(standard_rate.bounds().map(|b| b - income));
+        }
+        // The state tax of the previous year does not move with the movement.
+        if let Some(state) = self
+            .state
+            .as_ref()
+            .filter(|s| !s.prior_year || r.prior_wages.is_none())
+        {
+            let offset = state.standard_deduction - self.prorated(self.standard_deduction);
+            ret.extend(
+                state
+                    .bounds()
+                    .flat_map(|b| self.donation.crossings(r, b + offset))
+                    .map(|x| x - base),
+            );
+        }
+        let income = self.income_before_standard_deduction(r);
+        for credit in self.credit.values() {
+            ret.extend(credit.bounds(&r.special).into_iter().map(|b| b - income));
+        }
+        if let Some(taper) = &self.standard_deduction_taper {
+            ret.extend(
+                [
+                    taper.threshold,
+                    taper.threshold + self.standard_deduction / taper.ratio,
+                ]
+                .map(|x| x - income),
+            );
+        }
+        ret
+    }
+
+    /// The movements of the record at which its salary tax changes slope: those that shift the
+    /// income by one of the [`Self::salary_crossings`], and those at which the employment
+    /// deduction does.
+    pub(crate) fn movement_crossings(&self, r: &Record) -> Vec<f64> {
+        let wages = self.employment_wages(r);
+        self.salary_crossings(r)
+            .into_iter()
+            .map(|shift| self.movement_of_shift(r, shift))
+            .chain(
+                self.employment_deduction_bounds()
+                    .into_iter()
+                    .map(|b| b - wages),
+            )
+            .collect()
+    }
+
+    /// The movement of the record that shifts its income before the standard deduction by
+    /// `shift`. The employment deduction grows with the wages and takes part of the movement
+    /// with it, and all of it where it deducts the wages in whole.
+    fn movement_of_shift(&self, r: &Record, shift: f64) -> f64 {
+        let wages = self.employment_wages(r);
+        let mut bounds: Vec<_> = self
+            .employment_deduction_bounds()
+            .into_iter()
+            .map(|b| b - wages)
+            .collect();
+        let (Some(&first), Some(&last)) = (bounds.first(), bounds.last()) else {
+            return shift;
+        };
+        let income = |m: f64| {
+            m - self.employment_deduction_of(wages + m) + self.employment_deduction_of(wages)
+        };
+        // The income is linear in the movement between the bounds, so interpolate it on the
+        // pair around the shift, or extend the pair at either end.
+        bounds.insert(0, first - 1.0);
+        bounds.push(last + 1.0);
+        let i = bounds
+            .windows(2)
+            .position(|p| income(p[1]) >= shift)
+            .unwrap_or(bounds.len() - 2);
+        let (a, b) = (bounds[i], bounds[i + 1]);
+        let slope = (income(b) - income(a)) / (b - a);
+        match slope > 0.0 {
+            true => a + (shift - income(a)) / slope,
+            false => b,
+        }
+    }
+
+    /// The standard deduction for the income before it, less the taper if any, prorated by the
+    /// months employed.
+    pub fn standard_deduction(&self, income: f64) -> f64 {
+        self.prorated(match &self.standard_deduction_taper {
+            Some(t) => 0f64.max(self.standard_deduction - t.ratio * 0f64.max(income - t.threshold)),
+            None => self.standard_deduction,
+        })
+    }
+
+    /// The part of an `annual` amount for the months employed.
+    fn prorated(&self, annual: f64) -> f64 {
+        annual * self.months_employed() / 12.0
+    }
+
+    /// The taxable salary income before the donation deduction, whose cap depends on it.
+    pub fn income_before_donations(&self, r: &Record) -> f64 {
+        let income = self.income_before_standard_deduction(r);
+        income - self.standard_deduction(income)
+    }
+
+    /// The salary income after all deductions but the standard deduction and donations, e.g. the
+    /// adjusted net income of the UK that the personal allowance tapers with.
+    pub fn income_before_standard_deduction(&self, r: &Record) -> f64 {
+        r.movement
+            + r.other_bonus()
+            + r.remuneration()
+                .map(|(kind, p)| self.remuneration.annual(kind, p))
+                .fold(0.0, |a, b| a + b)
+            + self.annual_salary(r)
+            + self.taxable_allowances(r)
+            - r.monthly_tax_deduction * self.months_employed()
+            - self.employment_deduction(r)
+            - self.social_insurance(r)
+            - self.special_deduction.annual(&r.special)
+            - self.private_pension.deduction(r.private_pension)
+            - self.annuity_deduction(r)
+            - self.health_insurance_deduction(r)
+            + self.taxable_foreign_income(r)
+    }
+
+    /// The annual profit of the business of the record: the revenue invoiced, less the VAT, the
+    /// surcharges and the business expenses.
+    pub fn business_profit(&self, r: &Record) -> f64 {
+        r.invoices.iter().fold(0.0, |a, i| a + i - self.vat.tax(*i)) - r.business_expenses
+    }
+
+    /// The annual taxable business income of the record, its [`Self::business_profit`]. Without
+    /// any comprehensive income, the standard deduction and the personal deductions are deducted
+    /// from it instead.
+    pub fn business_income(&self, r: &Record) -> f64 {
+        let comprehensive = self.annual_salary(r)
+            + r.movement
+            + r.year_bonus
+            + r.other_bonus()
+            + r.remuneration()
+                .flat_map(|(_, p)| p)
+                .fold(0.0, |a, p| a + p);
+        let deductions = match comprehensive {
+            0.0 => 0f64.min(self.income_before_donations(r)),
+            _ => 0.0,
+        };
+        self.business_profit(r) + deductions
+    }
+
+    /// The foreign income that is taxed in China. Non-residents are only taxed on income from
+    /// China, and residents only after the exempt years of the six-year rule.
+    pub fn taxable_foreign_income(&self, r: &Record) -> f64 {
+        match self.resident_status {
+            ResidentStatus::Resident if r.residence_years >= self.foreign_exemption_years => {
+                r.foreign_income()
+            }
+            _ => 0.0,
+        }
+    }
+
+    /// The annual deduction from the wages of the record by [`Self::employment_deduction`].
+    pub fn employment_deduction(&self, r: &Record) -> f64 {
+        self.employment_deduction_of(self.employment_wages(r))
+    }
+
+    /// The wages of the record that the employment deduction applies to: all but the year bonus.
+    fn employment_wages(&self, r: &Record) -> f64 {
+        self.wages(r) - r.year_bonus
+    }
+
+    /// The employment deduction from `wages`, at most the wages.
+    fn employment_deduction_of(&self, wages: f64) -> f64 {
+        let Some(table) = &self.employment_deduction else {
+            return 0.0;
+        };
+        table
+            .tax(0f64.max(wages))
+            .expect("the employment deduction has an open-ended bracket")
+            .min(wages)
+    }
+
+    /// The wages at which the employment deduction changes slope, in order: the bounds of its
+    /// table, and the wages up to which it deducts them in whole.
+    fn employment_deduction_bounds(&self) -> Vec<f64> {
+        let Some(table) = &self.employment_deduction else {
+            return Vec::new();
+        };
+        let mut ret: Vec<f64> = table.bounds().collect();
+        // The deduction grows slower than the wages, so it exceeds them up to a single point, in
+        // the first bracket whose upper bound it falls short of.
+        let deduction = |w| {
+            table
+                .tax(w)
+                .expect("the employment deduction has an open-ended bracket")
+        };
+        let mut lower = 0.0;
+        for upper in ret.clone() {
+            let (a, b) = (deduction(lower), deduction(upper));
+            if b < upper {
+                ret.push(lower + (a - lower) * (upper - lower) / (upper - lower - (b - a)));
+                break;
+            }
+            lower = upper;
+        }
+        ret.sort_by(f64::total_cmp);
+        ret
+    }
+
+    /// The months employed in the year, see [`Self::start_month`].
+    pub fn months(&self) -> std::ops::RangeInclusive<u32> {
+        self.start_month..=self.end_month
+    }
+
+    /// The number of months employed in the year.
+    pub fn months_employed(&self) -> f64 {
+        (self.end_month + 1 - self.start_month) as f64
+    }
+
+    /// The salary of the record over the months employed, excluding the movement.
+    pub fn annual_salary(&self, r: &Record) -> f64 {
+        match &r.salaries {
+            Some(_) => self
+                .months()
+                .map(|m| r.salary_of(m))
+                .fold(0.0, |a, s| a + s),
+            None => r.monthly_salary * self.months_employed(),
+        }
+    }
+
+    /// The annual wages from the employment, salary and all bonuses, on which payroll taxes are
+    /// charged. Pension contributions by salary sacrifice are not wages.
+    pub fn wages(&self, r: &Record) -> f64 {
+        let sacrifice = match self.private_pension.salary_sacrifice {
+            true => self.private_pension.deduction(r.private_pension),
+            false => 0.0,
+        };
+        self.annual_salary(r) + r.movement + r.other_bonus() + r.year_bonus - sacrifice
+    }
+
+    /// The monthly contribution base of the enterprise annuity.
+    pub fn annuity_base(&self, r: &Record) -> f64 {
+        let cap = match self.enterprise_annuity.base_cap {
+            0.0 => self.social_insurance.cap,
+            cap => cap,
+        };
+        if cap > 0.0 {
+            r.monthly_salary.min(cap)
+        } else {
+            r.monthly_salary
+        }
+    }
+
+    /// The annual enterprise annuity deduction: the employee contribution within its cap, minus the
+    /// employer contribution above its cap, which is taxed as salary.
+    pub fn annuity_deduction(&self, r: &Record) -> f64 {
+        let rules = &self.enterprise_annuity;
+        let base = self.annuity_base(r) * self.months_employed();
+        let employee = r.annuity.clamp(0.0, rules.employee_cap) * base;
+        let employer = 0f64.max(r.employer_annuity - rules.employer_cap) * base;
+        employee - employer
+    }
+
+    /// The allowances of the record paid in the year.
+    pub fn allowances(&self, r: &Record) -> f64 {
+        r.allowances.iter().fold(0.0, |a, b| a + b.amount) * self.months_employed()
+    }
+
+    /// The part of the allowances of the record taxed with the salary, beyond their exemptions.
+    pub fn taxable_allowances(&self, r: &Record) -> f64 {
+        allowance_exemptions(self, r)
+            .iter()
+            .fold(0.0, |a, e| a + e.taxable)
+    }
+
+    /// The record with the taxable allowances added to the salary of every month, which
+    /// non-residents are taxed on month by month.
+    pub(crate) fn with_taxable_allowances(&self, r: &Record) -> Record {
+        let monthly = self.taxable_allowances(r) / self.months_employed();
+        let mut ret = r.clone();
+        ret.monthly_salary += monthly;
+        if let Some(salaries) = &mut ret.salaries {
+            salaries.iter_mut().for_each(|s| *s += monthly);
+        }
+        ret
+    }
+
+    /// The annual deduction for tax-preferred commercial health insurance (税优健康险).
+    pub fn health_insurance_deduction(&self, r: &Record) -> f64 {
+        r.health_insurance.clamp(0.0, self.health_insurance_cap)
+    }
+
+    /// The annual social insurance and housing fund contributions of the employee, on the
+    /// bonuses too for the items that include them, and to the supplementary housing fund. The
+    /// bonuses moved into salary still count as bonuses.
+    pub fn social_insurance(&self, r: &Record) -> f64 {
+        let bonus = r.year_bonus + r.movement + r.other_bonus();
+        self.social_insurance
+            .annual_employee(r.social_base(), self.months_employed(), bonus)
+            + self.supplementary_housing(r)
+    }
+
+    /// The annual contribution of the employee to the supplementary housing fund, untaxed within
+    /// the highest ratio. The employer matches it.
+    pub fn supplementary_housing(&self, r: &Record) -> f64 {
+        let rules = &self.supplementary_housing_fund;
+        let cap = match rules.base_cap > 0.0 {
+            true => rules.base_cap,
+            false => self.social_insurance.cap,
+        };
+        let base = match cap > 0.0 {
+            true => r.social_base().min(cap),
+            false => r.social_base(),
+        };
+        r.supplementary_housing.clamp(0.0, rules.max_ratio)
+            * 0f64.max(base)
+            * self.months_employed()
+    }
+
+    /// The tax withheld from the remuneration of the record, which the reconciliation settles.
+    pub fn remuneration_withholding(&self, r: &Record) -> Result<f64> {
+        r.remuneration()
+            .flat_map(|(kind, p)| {
+                p.iter()
+                    .map(move |p| self.remuneration.withholding(kind, *p))
+            })
+            .try_fold(0.0, |a, t| Ok(a + t?))
+    }
+
+    /// The tax for `severance`. The part above the exemption is taxed on its own with the salary
+    /// table, apart from the annual income.
+    pub fn severance_tax(&self, severance: f64) -> Result<f64> {
+        self.salary
+            .tax(0f64.max(severance - self.severance_exemption))
+    }
+
+    /// Caluculate the tax for the given record. Equity income is taxed on its own with the salary
+    /// table, all exercises and vestings of the year together.
+    pub fn calc(&self, r: &Record) -> Result<Tax> {
+        if !self.separate_bonus && r.year_bonus != 0.0 {
+            return self.calc(&r.with_movement(self, r.year_bonus)?);
+        }
+        let ((salary, marginal_rate), bonus) = match self.resident_status {
+            ResidentStatus::Resident => {
+                let salary = self.salary_tax_slices(r)?;
+                let tax = 0f64.max(
+                    salary.iter().fold(0.0, |a, s| a + s.tax)
+                        - self.personal_amount * self.salary.lowest_ratio(),
+                );
+                (
+                    (
+                        match &self.rebate {
+                            Some(rebate) => rebate.apply(self.salary_income(r), tax)?,
+                            None => tax,
+                        },
+                        salary.last().map_or(0.0, |s| s.ratio),
+                    ),
+                    match self.separate_bonus {
+                        true => self.year_bonus.bonus_slice(r.year_bonus),
+                        false => Ok(Slice::default()),
+                    },
+                )
+            }
+            ResidentStatus::NonResident => (
+                self.non_resident
+                    .salary_tax(
+                        &self.with_taxable_allowances(r),
+                        self.months(),
+                        &self.remuneration,
+                    )
+                    .context("salary")?,
+                self.non_resident.bonus_slice(r.year_bonus),
+            ),
+        };
+        let bonus = bonus.context("year bonus")?;
+        let mut tax = Tax {
+            salary,
+            year_bonus: bonus.tax,
+            severance: self.severance_tax(r.severance).context("severance")?,
+            equity: self.salary.tax(r.equity_income()).context("equity")?,
+            rental: r
+                .rental_income
+                .iter()
+                .fold(0.0, |a, rent| a + self.rental.tax(*rent)),
+            vat: r.invoices.iter().fold(0.0, |a, i| a + self.vat.tax(*i)),
+            business: match &self.business {
+                Some(b) => b.tax(self.business_income(r)).context("business")?,
+                None => 0.0,
+            },
+            payroll: self.payroll_tax.tax(self.wages(r)),
+            state: match &self.state {
+                Some(state) => {
+                    let r = match state.prior_year {
+                        true => &r.prior_year(),
+                        false => r,
+                    };
+                    state
+                        .tax(self.raw_salary_income(r) + self.prorated(self.standard_deduction))
+                        .context("state")?
+                }
+                None => 0.0,
+            },
+            surtax: 0.0,
+            credits: 0.0,
+            foreign_credit: foreign_credits(self, r, salary)
+                .iter()
+                .fold(0.0, |a, c| a + c.credit),
+            marginal_rate,
+            bonus_rate: if r.year_bonus > 0.0 { bonus.ratio } else { 0.0 },
+            income: self.annual_salary(r)
+                + self.allowances(r)
+                + r.movement
+                + r.other_bonus()
+                + r.year_bonus,
+            other_income: r.severance
+                + r.foreign_income()
+                + r.equity_income()
+                + r.rental_income.iter().fold(0.0, |a, rent| a + rent)
+                + r.invoices.iter().fold(0.0, |a, i| a + i)
+                + r.remuneration()
+                    .flat_map(|(_, p)| p)
+                    .fold(0.0, |a, p| a + p),
+            effective_rate: 0.0,
+            social_insurance: self.social_insurance(r),
+            net: 0.0,
+            monthly_net: 0.0,
+            months: self.months_employed(),
+        };
+        tax.income += tax.other_income;
+        let income_tax = tax.salary + tax.year_bonus + tax.severance + tax.equity;
+        tax.credits = self.credits(r, income_tax);
+        let income = self.salary_income(r);
+        tax.surtax = self.surtax(0f64.max(income_tax - tax.credits), income, tax.income);
+        if tax.income > 0.0 {
+            tax.effective_rate = tax.total() / tax.income;
+        }
+        let paid_abroad = r.foreign_income.iter().fold(0.0, |a, s| a + s.tax_paid);
+        tax.net =
+            tax.income - tax.total() - tax.social_insurance - paid_abroad - r.business_expenses;
+        tax.monthly_net =
+            (self.annual_salary(r) + self.allowances(r) + r.movement + r.other_bonus()
+                - (tax.salary - tax.credits)
+                - tax.state
+                - tax.payroll
+                - self.surtax(0f64.max(tax.salary - tax.credits), income, tax.income)
+                - tax.social_insurance)
+                / self.months_employed();
+        Ok(tax)
+    }
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::{amount, number};
+use crate::OptimizeOptions;
+
+/// The payroll policy of a company: the movements its payroll allows, read from a TOML file such
+/// as
+///
+/// ```toml
+/// [movement]
+/// min = -12000        # salary that may be moved into the year bonus
+/// max = 60000
+/// granularity = 1000  # moved in multiples of 1000
+///
+/// [bonus]
+/// min = 10000         # kept of the year bonus
+/// payout_months = [1, 12]
+///
+/// [salary]
+/// max_monthly = 50000 # the most monthly salary, including the movement
+/// ```
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PayrollPolicy {
+    /// The lowest movement, negative to allow moving salary into the year bonus. Zero, moving
+    /// nothing, is always allowed.
+    pub min_movement: Option<f64>,
+    pub max_movement: Option<f64>,
+    pub granularity: Option<f64>,
+    pub min_bonus: Option<f64>,
+    /// The months the year bonus may be paid in, any if empty.
+    pub payout_months: Vec<u32>,
+    pub max_monthly_salary: Option<f64>,
+}
+
+impl PayrollPolicy {
+    /// Parse the text of a policy file.
+    pub fn parse(text: &str) -> Result<Self> {
+        let tbl: toml::Table = toml::from_str(text)?;
+        let mut ret = Self::default();
+        for (section, value) in &tbl {
+            let keys = value
+                .as_table()
+                .ok_or_else(|| anyhow!("{section} is not a table"))?;
+            for (key, value) in keys {
+                let get =
+                    || amount(value).ok_or_else(|| anyhow!("{section}.{key} is not a number"));
+                match (section.as_str(), key.as_str()) {
+                    ("movement", "min") => ret.min_movement = Some(get()?),
+                    ("movement", "max") => ret.max_movement = Some(get()?),
+                    ("movement", "granularity") => {
+                        ret.granularity = Some(get()?).filter(|g| *g > 0.0);
+                        anyhow::ensure!(
+                            ret.granularity.is_some(),
+                            "movement.granularity must be positive"
+                        );
+                    }
+                    ("bonus", "min") => ret.min_bonus = Some(get()?),
+                    ("bonus", "payout_months") => {
+                        ret.payout_months = value
+                            .as_array()
+                            .ok_or_else(|| anyhow!("bonus.payout_months is not an array"))?
+                            .iter()
+                            .map(|m| match number(m) {
+                                Some(m) if (1.0..=12.0).contains(&m) && m.fract() == 0.0 => {
+                                    Ok(m as u32)
+                                }
+                                _ => bail!("bonus.payout_months: {m} is not a month 1 to 12"),
+                            })
+                            .try_collect()?;
+                    }
+                    ("salary", "max_monthly") => ret.max_monthly_salary = Some(get()?),
+                    _ => bail!("unknown policy key `{section}.{key}`"),
+                }
+            }
+        }
+        if let Some(min) = ret.min_movement {
+            anyhow::ensure!(
+                min <= 0.0,
+                "movement.min must not be positive, no movement is always allowed"
+            );
+        }
+        Ok(ret)
+    }
+
+    /// Restrict `options` to the movements the policy allows, keeping the tighter of each
+    /// constraint.
+    pub fn apply(&self, options: &mut OptimizeOptions) -> Result<()> {
+        // Moving salary into the bonus is only tried when asked for, so the policy allows it.
+        if let Some(min) = self.min_movement {
+            options.max_reverse = match options.max_reverse {
+                0.0 => -min,
+                r => r.min(-min),
+            };
+        }
+        let tighter = |a: Option<f64>, b: Option<f64>| match (a, b) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+        options.max_movement = tighter(options.max_movement, self.max_movement);
+        options.max_monthly_salary = tighter(options.max_monthly_salary, self.max_monthly_salary);
+        options.min_bonus = options.min_bonus.max(self.min_bonus.unwrap_or(0.0));
+        if let Some(g) = self.granularity {
+            match options.granularity {
+                Some(other) => anyhow::ensure!(
+                    other % g == 0.0,
+                    "the granularity {other} is not a multiple of {g} of the policy"
+                ),
+                None => options.granularity = Some(g),
+            }
+        }
+        Ok(())
+    }
+
+    /// Check that the year bonus may be paid in `month`.
+    pub fn check_bonus_month(&self, month: u32) -> Result<()> {
+        anyhow::ensure!(
+            self.payout_months.is_empty() || self.payout_months.contains(&month),
+            "the policy pays the year bonus only in months {:?}, not {month}",
+            self.payout_months
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{deduction, SpecialDeductions};
+
+/// A credit against the income tax, e.g. the child tax credit of the US.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Credit {
+    /// The credit, for each unit of `per` if given.
+    pub amount: f64,
+    /// The special deduction whose claimed units the credit is given for, e.g.
+    /// `children_education` for each child.
+    pub per: Option<String>,
+    /// Whether the part of the credit above the tax is paid out. Otherwise it only reduces the
+    /// tax to zero.
+    pub refundable: bool,
+    /// The income above which the credit phases out.
+    pub threshold: f64,
+    /// The ratio of the income above the threshold withdrawn from the credit until it is gone. No
+    /// phase-out if zero.
+    pub ratio: f64,
+}
+
+impl Credit {
+    /// Parse the credit `name` of a config.
+    pub(crate) fn parse(name: &str, tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            match key.as_str() {
+                "amount" => {
+                    ret.amount = number(value)
+                        .ok_or_else(|| anyhow!("credit.{name}.amount is not a number"))?
+                }
+                "per" => {
+                    ret.per = match value.as_str() {
+                        Some(per) if deduction::KEYS.contains(&per) => Some(per.to_string()),
+                        _ => bail!("credit.{name}.per is not a special deduction"),
+                    }
+                }
+                "refundable" => {
+                    ret.refundable = value
+                        .as_bool()
+                        .ok_or_else(|| anyhow!("credit.{name}.refundable is not a boolean"))?
+                }
+                "phase_out" => {
+                    let phase_out = value
+                        .as_table()
+                        .ok_or_else(|| anyhow!("credit.{name}.phase_out is not a table"))?;
+                    for (key, value) in phase_out {
+                        let field = match key.as_str() {
+                            "threshold" => &mut ret.threshold,
+                            "ratio" => &mut ret.ratio,
+                            _ => bail!("unknown credit.{name}.phase_out key `{key}`"),
+                        };
+                        *field = number(value).ok_or_else(|| {
+                            anyhow!("credit.{name}.phase_out.{key} is not a number")
+                        })?;
+                    }
+                }
+                _ => bail!("unknown credit.{name} key `{key}`"),
+            }
+        }
+        Ok(ret)
+    }
+
+    /// The credit for the claimed `special` deductions, before the phase-out.
+    pub fn full(&self, special: &SpecialDeductions) -> f64 {
+        match &self.per {
+            Some(per) => self.amount * special.get(per),
+            None => self.amount,
+        }
+    }
+
+    /// The credit at `income` for the claimed `special` deductions.
+    pub fn amount(&self, income: f64, special: &SpecialDeductions) -> f64 {
+        0f64.max(self.full(special) - self.ratio * 0f64.max(income - self.threshold))
+    }
+
+    /// The incomes at which the credit starts to phase out and at which it is gone.
+    pub(crate) fn bounds(&self, special: &SpecialDeductions) -> Vec<f64> {
+        match self.ratio {
+            0.0 => Vec::new(),
+            ratio => vec![self.threshold, self.threshold + self.full(special) / ratio],
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use std::str::FromStr;
+
+use anyhow::{anyhow, Result};
+use serde::{Serialize, Serializer};
+
+/// A day of the Gregorian calendar, written as YYYY-MM-DD.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+}
+
+impl Date {
+    pub fn new(year: i32, month: u32, day: u32) -> Result<Self> {
+        let days_in_month = match month {
+            1 | 3 | 5 | 7 | 8 | 10 | 12 => 31,
+            4 | 6 | 9 | 11 => 30,
+            2 if is_leap(year) => 29,
+            2 => 28,
+            _ => return Err(anyhow!("invalid month {month}")),
+        };
+        anyhow::ensure!(
+            (1..=days_in_month).contains(&day),
+            "invalid day {day} of {year}-{month:02}"
+        );
+        Ok(Self { year, month, day })
+    }
+
+    /// The first day of `year`.
+    pub fn year_start(year: i32) -> Self {
+        Self {
+            year,
+            month: 1,
+            day: 1,
+        }
+    }
+
+    /// The last day of `year`.
+    pub fn year_end(year: i32) -> Self {
+        Self {
+            year,
+            month: 12,
+            day: 31,
+        }
+    }
+
+    /// Today in UTC.
+    pub fn today() -> Self {
+        let secs = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        Self::from_days((secs / 86400) as i64)
+    }
+
+    /// The number of days since 1970-01-01.
+    pub fn days(&self) -> i64 {
+        // Years starting in March, so that the leap day is the last day of a year.
+        let year = i64::from(self.year) - i64::from(self.month <= 2);
+        let era = year.div_euclid(400);
+        let yoe = year.rem_euclid(400);
+        let month = i64::from(self.month);
+        let doy =
+            (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + i64::from(self.day) - 1;
+        let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+        era * 146097 + doe - 719468
+    }
+
+    /// The day `days` days after 1970-01-01.
+    pub fn from_days(days: i64) -> Self {
+        let days = days + 719468;
+        let era = days.div_euclid(146097);
+        let doe = days.rem_euclid(146097);
+        let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+        let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+        let mp = (5 * doy + 2) / 153;
+        let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+        let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+        let year = (yoe + era * 400 + i64::from(month <= 2)) as i32;
+        Self { year, month, day }
+    }
+}
+
+fn is_leap(year: i32) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+impl FromStr for Date {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let err = || anyhow!("invalid date `{s}`, expected YYYY-MM-DD");
+        let mut parts = s.trim().splitn(3, '-');
+        let mut next = || parts.next().and_then(|p| p.parse().ok()).ok_or_else(err);
+        let (year, month, day) = (next()?, next()? as u32, next()? as u32);
+        Self::new(year, month, day)
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}-{:02}-{:02}", self.year, self.month, self.day)
+    }
+}
+
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
@@ -0,0 +1,166 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{Record, TaxConfig};
+
+/// Special additional deductions (专项附加扣除) claimed in a record. Each item is counted in
+/// units of the monthly amount configured in [`SpecialDeductionRules`], e.g. `children_education
+/// = 2` for two children or `elderly_care = 0.5` for a half share split with siblings.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SpecialDeductions {
+    pub children_education: f64,
+    pub continuing_education: f64,
+    pub housing_loan: f64,
+    pub housing_rent: f64,
+    pub elderly_care: f64,
+    pub infant_care: f64,
+    /// The annual out-of-pocket serious medical expense, not a unit count.
+    pub serious_medical: f64,
+}
+
+/// The keys of [`SpecialDeductions`], as accepted in records and configs.
+pub(crate) const KEYS: [&str; 7] = [
+    "children_education",
+    "continuing_education",
+    "housing_loan",
+    "housing_rent",
+    "elderly_care",
+    "infant_care",
+    "serious_medical",
+];
+
+impl SpecialDeductions {
+    /// The claimed value of `key`, which must be one of [`KEYS`].
+    pub(crate) fn get(&self, key: &str) -> f64 {
+        let mut d = self.clone();
+        *d.get_mut(key).expect("a special deduction key")
+    }
+
+    pub(crate) fn get_mut(&mut self, key: &str) -> Option<&mut f64> {
+        Some(match key {
+            "children_education" => &mut self.children_education,
+            "continuing_education" => &mut self.continuing_education,
+            "housing_loan" => &mut self.housing_loan,
+            "housing_rent" => &mut self.housing_rent,
+            "elderly_care" => &mut self.elderly_care,
+            "infant_care" => &mut self.infant_care,
+            "serious_medical" => &mut self.serious_medical,
+            _ => return None,
+        })
+    }
+
+    /// The claimed items as `(key, value)` pairs, skipping the unclaimed ones.
+    pub fn items(&self) -> impl Iterator<Item = (&'static str, f64)> {
+        let values = [
+            self.children_education,
+            self.continuing_education,
+            self.housing_loan,
+            self.housing_rent,
+            self.elderly_care,
+            self.infant_care,
+            self.serious_medical,
+        ];
+        KEYS.into_iter().zip(values).filter(|(_, v)| *v != 0.0)
+    }
+}
+
+/// The monthly amount of each special additional deduction per claimed unit.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SpecialDeductionRules {
+    pub children_education: f64,
+    pub continuing_education: f64,
+    pub housing_loan: f64,
+    pub housing_rent: f64,
+    pub elderly_care: f64,
+    pub infant_care: f64,
+    /// The annual cap of the serious medical deduction.
+    pub serious_medical: f64,
+    /// The part of the annual serious medical expense that is not deductible.
+    pub serious_medical_threshold: f64,
+}
+
+impl TryFrom<&toml::Table> for SpecialDeductionRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let value =
+                number(value).ok_or_else(|| anyhow!("special_deduction.{key} is not a number"))?;
+            let field = match key.as_str() {
+                "children_education" => &mut ret.children_education,
+                "continuing_education" => &mut ret.continuing_education,
+                "housing_loan" => &mut ret.housing_loan,
+                "housing_rent" => &mut ret.housing_rent,
+                "elderly_care" => &mut ret.elderly_care,
+                "infant_care" => &mut ret.infant_care,
+                "serious_medical" => &mut ret.serious_medical,
+                "serious_medical_threshold" => &mut ret.serious_medical_threshold,
+                _ => anyhow::bail!("unknown special deduction `{key}`"),
+            };
+            *field = value;
+        }
+        Ok(ret)
+    }
+}
+
+impl SpecialDeductionRules {
+    /// The annual deduction for the claimed items.
+    pub fn annual(&self, d: &SpecialDeductions) -> f64 {
+        self.monthly(d) * 12.0 + self.medical(d)
+    }
+
+    /// The monthly deduction for the items that are claimed month by month during withholding.
+    /// Only one of housing loan and housing rent may be claimed, so the larger one counts when
+    /// both are given.
+    pub fn monthly(&self, d: &SpecialDeductions) -> f64 {
+        let housing = (d.housing_loan * self.housing_loan).max(d.housing_rent * self.housing_rent);
+        d.children_education * self.children_education
+            + d.continuing_education * self.continuing_education
+            + housing
+            + d.elderly_care * self.elderly_care
+            + d.infant_care * self.infant_care
+    }
+
+    /// The serious medical deduction, which is only claimed at the annual reconciliation.
+    pub fn medical(&self, d: &SpecialDeductions) -> f64 {
+        (d.serious_medical - self.serious_medical_threshold).clamp(0.0, self.serious_medical)
+    }
+}
+
+/// Which of the housing loan and the housing rent deduction to claim.
+#[derive(Clone, Debug, Serialize)]
+pub struct HousingChoice {
+    /// `housing_loan` or `housing_rent`.
+    pub recommended: &'static str,
+    /// The tax saved over claiming the other one.
+    pub saving: f64,
+}
+
+impl std::fmt::Display for HousingChoice {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "claim {} (saves {:.2})", self.recommended, self.saving)
+    }
+}
+
+/// Compare the housing loan and the housing rent deduction if the record gives both.
+pub fn choose_housing(config: &TaxConfig, record: &Record) -> Result<Option<HousingChoice>> {
+    let d = &record.special;
+    if d.housing_loan == 0.0 || d.housing_rent == 0.0 {
+        return Ok(None);
+    }
+    let mut r = record.clone();
+    r.special.housing_rent = 0.0;
+    let loan = config.calc(&r)?.total();
+    (r.special.housing_loan, r.special.housing_rent) = (0.0, d.housing_rent);
+    let rent = config.calc(&r)?.total();
+    Ok(Some(HousingChoice {
+        recommended: if rent < loan {
+            "housing_rent"
+        } else {
+            "housing_loan"
+        },
+        saving: (loan - rent).abs(),
+    }))
+}
@@ -0,0 +1,106 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{npv, optimize, Objective, Optimization, OptimizeOptions, Record, TaxConfig};
+
+/// Part of a year bonus paid in January of the next year instead, with the best movement of each
+/// year.
+#[derive(Clone, Debug, Serialize)]
+pub struct Deferral {
+    pub deferred: f64,
+    pub this_year: Optimization,
+    pub next_year: Optimization,
+    /// The [`npv`] of both years at the start of this one, if the objective discounts.
+    pub npv: Option<f64>,
+}
+
+impl Deferral {
+    /// The tax of both years.
+    pub fn total(&self) -> f64 {
+        self.this_year.after.total() + self.next_year.after.total()
+    }
+
+    /// The score of the deferral, lower being better: the tax of both years, or less the more
+    /// its net present value when the objective discounts.
+    fn score(&self) -> f64 {
+        self.npv.map_or(self.total(), |npv| -npv)
+    }
+}
+
+/// The result of [`defer_bonus`].
+#[derive(Clone, Debug, Serialize)]
+pub struct DeferralPlan {
+    /// Nothing deferred.
+    pub before: Deferral,
+    /// The best deferral, with the least tax of both years unless the objective discounts.
+    pub after: Deferral,
+}
+
+/// Decide how much of the year bonus of `this` to defer to January of the next year, the record
+/// `next`, to pay the least tax of both years, each under its config and the best movement of
+/// `options`, or, under [`Objective::MaxNpv`], for the most net present value of both years. The
+/// deferred part is another bonus of the next year, so that either it or the year bonus of `next`
+/// is taxed separately, whichever is better. Deferrals in steps of `step` are tried, and those
+/// that bring either bonus to a bracket bound.
+pub fn defer_bonus(
+    config: &TaxConfig,
+    next_config: &TaxConfig,
+    this: &Record,
+    next: &Record,
+    options: &OptimizeOptions,
+    step: f64,
+) -> Result<DeferralPlan> {
+    anyhow::ensure!(step > 0.0, "the step must be positive");
+    let precision = options.granularity.unwrap_or(options.precision);
+    let at = |deferred: f64| -> Result<Deferral> {
+        let mut now = this.clone();
+        now.year_bonus -= deferred;
+        let mut later = next.clone();
+        if deferred > 0.0 {
+            later.other_bonuses.push(deferred);
+        }
+        let this_year = optimize(config, &now, options)?;
+        let next_year = optimize(next_config, &later, options)?;
+        let npv = match options.objective {
+            Objective::MaxNpv(rate) => {
+                // The schedule pays the bonuses in December, while the deferred one arrives in
+                // January, though its tax is still counted in December.
+                let early = deferred * ((1.0 + rate).powf(-1.0 / 12.0) - 1.0 / (1.0 + rate));
+                let next = npv(next_config, &next_year.record, &next_year.after, rate) + early;
+                Some(npv(config, &this_year.record, &this_year.after, rate) + next / (1.0 + rate))
+            }
+            _ => None,
+        };
+        Ok(Deferral {
+            deferred,
+            this_year,
+            next_year,
+            npv,
+        })
+    };
+    let max = this.year_bonus;
+    let mut tried: Vec<f64> = (0..=(max / step + 1e-9).floor() as usize)
+        .map(|i| i as f64 * step)
+        .collect();
+    // The bonus tax jumps right above a bound, so the deferrals on both sides of it are tried.
+    for b in config.year_bonus.bounds() {
+        let d = ((max - b * 12.0) / precision).ceil() * precision;
+        tried.extend([d, d - precision]);
+    }
+    for b in next_config.year_bonus.bounds() {
+        let d = (b * 12.0 / precision).floor() * precision;
+        tried.extend([d, d + precision]);
+    }
+    tried.retain(|d| (0.0..=max).contains(d));
+    tried.sort_by(|a, b| a.total_cmp(b));
+    tried.dedup();
+    let before = at(0.0)?;
+    let mut after = before.clone();
+    for d in tried {
+        let deferral = at(d)?;
+        if deferral.score() < after.score() - 0.005 {
+            after = deferral;
+        }
+    }
+    Ok(DeferralPlan { before, after })
+}
@@ -0,0 +1,124 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{Record, TaxConfig};
+
+/// Rules of the charitable donation deduction (公益慈善捐赠).
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct DonationRules {
+    /// The part of the taxable income that donations may offset, unless they are made through a
+    /// channel that is fully deductible.
+    pub cap_ratio: f64,
+}
+
+impl TryFrom<&toml::Table> for DonationRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "cap_ratio" => &mut ret.cap_ratio,
+                _ => bail!("unknown donation key `{key}`"),
+            };
+            *field = number(value).ok_or_else(|| anyhow!("donation.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+impl DonationRules {
+    /// The deduction for the donations of the record from the taxable `income` before them.
+    pub fn deduction(&self, r: &Record, income: f64) -> f64 {
+        let capped = r.donations.min(self.cap_ratio * 0f64.max(income));
+        capped + r.qualified_donations
+    }
+
+    /// The shifts of the taxable income before donations at which the income after them crosses
+    /// `bound`, in both the capped and the uncapped regime, and at which the cap starts to bind.
+    pub(crate) fn crossings(&self, r: &Record, bound: f64) -> Vec<f64> {
+        let mut ret = vec![bound + r.donations + r.qualified_donations];
+        if self.cap_ratio > 0.0 && self.cap_ratio < 1.0 {
+            ret.push((bound + r.qualified_donations) / (1.0 - self.cap_ratio));
+            ret.push(r.donations / self.cap_ratio);
+        }
+        ret
+    }
+}
+
+/// The effect of donating an amount with the 30% cap.
+#[derive(Clone, Debug, Serialize)]
+pub struct Donation {
+    pub amount: f64,
+    /// The part of the amount that is deducted.
+    pub deductible: f64,
+    /// The tax saved compared with not donating.
+    pub saving: f64,
+}
+
+impl std::fmt::Display for Donation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} ({:.2} deductible, saves {:.2})",
+            self.amount, self.deductible, self.saving
+        )
+    }
+}
+
+/// The effect of donating `amount` through a capped channel on top of the donations of the record.
+pub fn donation(config: &TaxConfig, record: &Record, amount: f64) -> Result<Donation> {
+    let before = config.calc(record)?.total();
+    let mut r = record.clone();
+    r.donations += amount;
+    let income = config.income_before_donations(&r);
+    let deductible =
+        config.donation.deduction(&r, income) - config.donation.deduction(record, income);
+    Ok(Donation {
+        amount,
+        deductible,
+        saving: before - config.calc(&r)?.total(),
+    })
+}
+
+/// The largest donation that is still fully deductible, or the smallest one that saves `target`
+/// if given. The saving never decreases with the amount, so the smallest one is found by
+/// bisection to the cent.
+pub fn advise_donation(
+    config: &TaxConfig,
+    record: &Record,
+    target: Option<f64>,
+) -> Result<Donation> {
+    let income = config.income_before_donations(record);
+    let max = 0f64.max(config.donation.cap_ratio * income - record.donations);
+    let Some(target) = target else {
+        return donation(config, record, max);
+    };
+    let best = donation(config, record, max)?;
+    anyhow::ensure!(
+        best.saving >= target,
+        "a donation saves at most {:.2}, by donating {:.2}",
+        best.saving,
+        max
+    );
+    let saves = |amount: f64| -> Result<bool> {
+        Ok(donation(config, record, amount)?.saving >= target - 1e-9)
+    };
+    let (mut lo, mut hi) = (0.0, max);
+    while hi - lo > 0.01 {
+        let mid = (lo + hi) / 2.0;
+        if saves(mid)? {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    let floor = (hi * 100.0).floor() / 100.0;
+    let amount = if saves(floor)? {
+        floor
+    } else {
+        (hi * 100.0).ceil() / 100.0
+    };
+    donation(config, record, amount)
+}
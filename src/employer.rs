@@ -0,0 +1,58 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{Record, TaxConfig};
+
+/// What employing the record costs the employer in a year, next to what the employee nets.
+#[derive(Clone, Debug, Serialize)]
+pub struct EmployerCost {
+    /// The pay: salary, allowances, bonuses and severance.
+    pub gross: f64,
+    /// The contributions of the employer to social insurance and the housing fund, the match of
+    /// the supplementary housing fund included.
+    pub social_insurance: f64,
+    /// The contributions of the employer to the enterprise annuity.
+    pub annuity: f64,
+    pub total: f64,
+    /// The annual net income of the employee.
+    pub net: f64,
+}
+
+impl std::fmt::Display for EmployerCost {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} (gross: {:.2}, social insurance and housing fund: {:.2}",
+            self.total, self.gross, self.social_insurance
+        )?;
+        if self.annuity != 0.0 {
+            write!(f, ", annuity: {:.2}", self.annuity)?;
+        }
+        write!(f, "), employee net: {:.2}", self.net)?;
+        if self.total > 0.0 {
+            write!(f, ", {:.2}% of the cost", self.net / self.total * 100.0)?;
+        }
+        Ok(())
+    }
+}
+
+/// The total cost of employing `record` to the employer and the net income of the employee, for
+/// both sides of a negotiation to reason from.
+pub fn employer_cost(config: &TaxConfig, record: &Record) -> Result<EmployerCost> {
+    let months = config.months_employed();
+    let bonus = record.year_bonus + record.movement + record.other_bonus();
+    let gross = config.annual_salary(record) + config.allowances(record) + bonus + record.severance;
+    let social_insurance =
+        config
+            .social_insurance
+            .annual_employer(record.social_base(), months, bonus)
+            + config.supplementary_housing(record);
+    let annuity = record.employer_annuity * config.annuity_base(record) * months;
+    Ok(EmployerCost {
+        gross,
+        social_insurance,
+        annuity,
+        total: gross + social_insurance + annuity,
+        net: config.calc(record)?.net,
+    })
+}
@@ -0,0 +1,130 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{Record, TaxConfig};
+
+/// The income from one country outside China and the tax already paid there.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct ForeignSource {
+    /// The country the income comes from, empty if not given.
+    pub country: String,
+    pub income: f64,
+    /// The tax paid abroad on the income this year.
+    pub tax_paid: f64,
+    /// The unused credit carried forward from the last 5 years.
+    pub carryforward: f64,
+}
+
+impl ForeignSource {
+    /// Parse `income` or `country:income[:tax_paid[:carryforward]]`.
+    pub(crate) fn parse(s: &str) -> Result<Self> {
+        let number = |field: &str| -> Result<f64> {
+            field
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid foreign income `{s}`"))
+        };
+        let Some((country, rest)) = s.split_once(':') else {
+            return Ok(Self {
+                income: number(s)?,
+                ..Default::default()
+            });
+        };
+        let numbers: Vec<_> = rest.split(':').map(number).try_collect()?;
+        anyhow::ensure!(
+            numbers.len() <= 3,
+            "too many fields in foreign income `{s}`"
+        );
+        let at = |i: usize| numbers.get(i).copied().unwrap_or_default();
+        Ok(Self {
+            country: country.trim().to_string(),
+            income: at(0),
+            tax_paid: at(1),
+            carryforward: at(2),
+        })
+    }
+}
+
+impl std::fmt::Display for ForeignSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.country.is_empty() && self.tax_paid == 0.0 && self.carryforward == 0.0 {
+            return write!(f, "{}", self.income);
+        }
+        write!(f, "{}:{}", self.country, self.income)?;
+        if self.tax_paid != 0.0 || self.carryforward != 0.0 {
+            write!(f, ":{}", self.tax_paid)?;
+        }
+        if self.carryforward != 0.0 {
+            write!(f, ":{}", self.carryforward)?;
+        }
+        Ok(())
+    }
+}
+
+/// The credit for the tax paid in one country.
+#[derive(Clone, Debug, Serialize)]
+pub struct ForeignCredit {
+    pub country: String,
+    /// The Chinese tax on the income from the country, which caps the credit.
+    pub limit: f64,
+    /// The tax paid abroad and carried forward that is credited this year.
+    pub credit: f64,
+    /// The uncredited tax to carry forward into the next years.
+    pub carryforward: f64,
+}
+
+impl std::fmt::Display for ForeignCredit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let country = match self.country.as_str() {
+            "" => "abroad",
+            c => c,
+        };
+        write!(
+            f,
+            "{country}: credit {:.2} of limit {:.2}, carry forward {:.2}",
+            self.credit, self.limit, self.carryforward
+        )
+    }
+}
+
+/// The credits for the tax paid abroad, given the tax `salary_tax` on the comprehensive income.
+/// The limit of each country is its share of the comprehensive income times that tax. Entries of
+/// the same country are pooled. Nothing is credited while the foreign income is exempt.
+pub fn foreign_credits(config: &TaxConfig, record: &Record, salary_tax: f64) -> Vec<ForeignCredit> {
+    let mut ret: Vec<ForeignCredit> = Vec::new();
+    if config.taxable_foreign_income(record) == 0.0 {
+        return ret;
+    }
+    let income = config.annual_salary(record)
+        + record.movement
+        + record.other_bonus()
+        + record
+            .remuneration()
+            .map(|(kind, p)| config.remuneration.annual(kind, p))
+            .fold(0.0, |a, b| a + b)
+        + record.foreign_income();
+    for source in &record.foreign_income {
+        let i = match ret.iter().position(|c| c.country == source.country) {
+            Some(i) => i,
+            None => {
+                ret.push(ForeignCredit {
+                    country: source.country.clone(),
+                    limit: 0.0,
+                    credit: 0.0,
+                    carryforward: 0.0,
+                });
+                ret.len() - 1
+            }
+        };
+        let c = &mut ret[i];
+        if income > 0.0 {
+            c.limit += salary_tax * source.income / income;
+        }
+        c.carryforward += source.tax_paid + source.carryforward;
+    }
+    for c in &mut ret {
+        c.credit = c.carryforward.min(c.limit);
+        c.carryforward -= c.credit;
+    }
+    ret
+}
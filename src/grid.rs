@@ -0,0 +1,99 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+use crate::{optimize, OptimizeOptions, Record, TaxConfig};
+
+/// The amounts from `from` to `to`, both included, in steps of `step`, see [`parse_steps`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Steps {
+    pub from: f64,
+    pub to: f64,
+    pub step: f64,
+}
+
+impl Steps {
+    /// The amounts of the range, from the lowest.
+    pub fn values(&self) -> Vec<f64> {
+        let n = ((self.to - self.from) / self.step + 1e-9).floor() as usize;
+        (0..=n).map(|i| self.from + i as f64 * self.step).collect()
+    }
+}
+
+/// The most amounts a range may have, so that a typo in the step does not run for hours.
+const MAX_STEPS: f64 = 1000.0;
+
+/// Parse a range of amounts as `from..to:step`, e.g. `25000..40000:1000`, or a single amount.
+pub fn parse_steps(arg: &str) -> Result<Steps> {
+    let number = |s: &str| -> Result<f64> {
+        s.trim()
+            .parse()
+            .with_context(|| format!("invalid amount `{s}` in `{arg}`"))
+    };
+    let Some((from, rest)) = arg.split_once("..") else {
+        let amount = number(arg)?;
+        return Ok(Steps {
+            from: amount,
+            to: amount,
+            step: 1.0,
+        });
+    };
+    let (to, step) = rest
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected from..to:step, got `{arg}`"))?;
+    let ret = Steps {
+        from: number(from)?,
+        to: number(to)?,
+        step: number(step)?,
+    };
+    anyhow::ensure!(ret.step > 0.0, "the step of `{arg}` must be positive");
+    anyhow::ensure!(ret.from <= ret.to, "`{arg}` ends before it starts");
+    anyhow::ensure!(
+        (ret.to - ret.from) / ret.step < MAX_STEPS,
+        "`{arg}` has more than {MAX_STEPS} steps"
+    );
+    Ok(ret)
+}
+
+/// The best movement of a salary and year bonus of [`grid`].
+#[derive(Clone, Debug, Serialize)]
+pub struct GridPoint {
+    pub monthly_salary: f64,
+    pub year_bonus: f64,
+    pub movement: f64,
+    /// The total tax under the movement.
+    pub tax: f64,
+    /// The annual net income under the movement.
+    pub net: f64,
+}
+
+/// Optimize `record` with every monthly salary of `salaries` and year bonus of `bonuses`, e.g. to
+/// weigh the splits of an offer. The salary of every month and the year bonus of the record are
+/// replaced, and its movement is dropped. The points go by salary, then by bonus.
+pub fn grid(
+    config: &TaxConfig,
+    record: &Record,
+    salaries: &Steps,
+    bonuses: &Steps,
+    options: &OptimizeOptions,
+) -> Result<Vec<GridPoint>> {
+    let mut ret = Vec::new();
+    for monthly_salary in salaries.values() {
+        for year_bonus in bonuses.values() {
+            let mut r = record.clone();
+            r.monthly_salary = monthly_salary;
+            r.salaries = None;
+            r.year_bonus = year_bonus;
+            r.movement = 0.0;
+            let best = optimize(config, &r, options)
+                .with_context(|| format!("salary {monthly_salary}, bonus {year_bonus}"))?;
+            ret.push(GridPoint {
+                monthly_salary,
+                year_bonus,
+                movement: best.movement,
+                tax: best.after.total(),
+                net: best.after.net,
+            });
+        }
+    }
+    Ok(ret)
+}
@@ -0,0 +1,142 @@
+use anyhow::{anyhow, Context, Result};
+use serde::Serialize;
+
+use crate::{optimize, OptimizeOptions, Record, SpecialDeductions, Tax, TaxConfig};
+
+/// The special additional deductions that spouses may split between them, per child either 50/50
+/// or all to one of them. Elderly care is claimed for each one's own parents, at most half each
+/// among siblings, so it is not split here.
+pub const SHARED_KEYS: [&str; 2] = ["children_education", "infant_care"];
+
+/// The records and taxes of a household of two taxpayers.
+#[derive(Clone, Debug, Serialize)]
+pub struct Household {
+    /// The records with the shared deductions assigned, before any movement.
+    pub records: [Record; 2],
+    /// The movement of each record from its year bonus into salary.
+    pub movements: [f64; 2],
+    pub taxes: [Tax; 2],
+}
+
+impl Household {
+    /// The combined tax of the household.
+    pub fn total(&self) -> f64 {
+        self.taxes[0].total() + self.taxes[1].total()
+    }
+}
+
+impl std::fmt::Display for Household {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2}", self.total())?;
+        for i in 0..2 {
+            write!(
+                f,
+                "\n  {}: {:.2} for {} (movement: {})",
+                i + 1,
+                self.taxes[i].total(),
+                self.records[i],
+                self.movements[i]
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The result of [`optimize_household`].
+#[derive(Clone, Debug, Serialize)]
+pub struct HouseholdOptimization {
+    /// The household as the records give it, with the shared deductions to the first taxpayer.
+    pub before: Household,
+    /// The household with the movements and the split of the shared deductions that minimize the
+    /// combined tax.
+    pub after: Household,
+}
+
+/// Parse deductions shared by a household, e.g. `children_education=2,infant_care=1`.
+pub fn parse_shared(arg: &str) -> Result<SpecialDeductions> {
+    let mut ret = SpecialDeductions::default();
+    for kv in arg.split(',') {
+        let (key, value) = kv
+            .split_once('=')
+            .ok_or_else(|| anyhow!("expected key=value, got `{kv}`"))?;
+        let key = key.trim();
+        anyhow::ensure!(
+            SHARED_KEYS.contains(&key),
+            "`{key}` is not shared, expected one of {}",
+            SHARED_KEYS.join(", ")
+        );
+        *ret.get_mut(key).unwrap() = value
+            .trim()
+            .parse()
+            .with_context(|| format!("invalid value `{value}` for key `{key}`"))?;
+    }
+    Ok(ret)
+}
+
+/// The ways to split `total` units between two taxpayers, as the units of the first one. Each
+/// unit goes to either of them or half to each.
+fn shares(total: f64) -> Vec<f64> {
+    let n = (total * 2.0).round() as usize;
+    (0..=n).map(|i| i as f64 / 2.0).collect()
+}
+
+/// Jointly choose the movement of each taxpayer and the split of the shared deductions (see
+/// [`SHARED_KEYS`]) to minimize the combined tax. The shared deductions are those claimed by
+/// either record plus `shared`. Given a split, the taxes of the two are independent, so each
+/// split is tried with the best movement of each record.
+pub fn optimize_household(
+    config: &TaxConfig,
+    records: [&Record; 2],
+    shared: &SpecialDeductions,
+    options: &OptimizeOptions,
+) -> Result<HouseholdOptimization> {
+    let mut splits = vec![Vec::new()];
+    for key in SHARED_KEYS {
+        let total = records[0].special.get(key) + records[1].special.get(key) + shared.get(key);
+        splits = splits
+            .into_iter()
+            .flat_map(|s: Vec<(&str, f64, f64)>| {
+                shares(total).into_iter().map(move |first| {
+                    let mut s = s.clone();
+                    s.push((key, first, total - first));
+                    s
+                })
+            })
+            .collect();
+    }
+    let assign = |split: &[(&str, f64, f64)]| {
+        let mut rs = [records[0].clone(), records[1].clone()];
+        for (key, first, second) in split {
+            *rs[0].special.get_mut(key).unwrap() = *first;
+            *rs[1].special.get_mut(key).unwrap() = *second;
+        }
+        rs
+    };
+    // Before, the shared deductions go to the first taxpayer on top of what each record claims.
+    let mut rs = [records[0].clone(), records[1].clone()];
+    for key in SHARED_KEYS {
+        *rs[0].special.get_mut(key).unwrap() += shared.get(key);
+    }
+    let taxes = [config.calc(&rs[0])?, config.calc(&rs[1])?];
+    let before = Household {
+        records: rs,
+        movements: [0.0; 2],
+        taxes,
+    };
+    let mut after = before.clone();
+    for split in splits {
+        let rs = assign(&split);
+        let [a, b] = [
+            optimize(config, &rs[0], options)?,
+            optimize(config, &rs[1], options)?,
+        ];
+        if a.after.total() + b.after.total() < after.total() {
+            after = Household {
+                records: rs,
+                movements: [a.movement, b.movement],
+                taxes: [a.after, b.after],
+            };
+        }
+    }
+    Ok(HouseholdOptimization { before, after })
+}
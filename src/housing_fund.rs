@@ -0,0 +1,80 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{optimize, OptimizeOptions, Record, TaxConfig};
+
+/// Rules of the supplementary housing fund (补充住房公积金), which employees may join on top of
+/// the basic one. The employer matches the ratio the employee contributes, and both are untaxed
+/// within the highest ratio, though locked in the fund until withdrawn for housing.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct HousingFundRules {
+    /// The highest ratio of the base each side may contribute, none if zero.
+    pub max_ratio: f64,
+    /// The cap of the monthly contribution base. The cap of the social insurance base if zero.
+    pub base_cap: f64,
+}
+
+impl TryFrom<&toml::Table> for HousingFundRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "max_ratio" => &mut ret.max_ratio,
+                "base_cap" => &mut ret.base_cap,
+                _ => bail!("unknown supplementary_housing_fund key `{key}`"),
+            };
+            *field = number(value)
+                .ok_or_else(|| anyhow!("supplementary_housing_fund.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+/// The effect of contributing a ratio to the supplementary housing fund.
+#[derive(Clone, Debug, Serialize)]
+pub struct HousingFundRatio {
+    pub ratio: f64,
+    /// The annual contribution of the employee, taken from the net pay into the fund.
+    pub employee: f64,
+    /// The annual contribution of the employer, the same.
+    pub employer: f64,
+    /// The tax saved compared with not joining, each with its best movement.
+    pub saving: f64,
+    /// `saving` per yuan the employee contributes.
+    pub saving_per_yuan: f64,
+}
+
+/// The effect of contributing each whole percent of the base, up to the highest ratio of the
+/// config, to the supplementary housing fund instead of the ratio of the record, with the best
+/// movement of `options` for each.
+pub fn housing_fund(
+    config: &TaxConfig,
+    record: &Record,
+    options: &OptimizeOptions,
+) -> Result<Vec<HousingFundRatio>> {
+    let max = config.supplementary_housing_fund.max_ratio;
+    anyhow::ensure!(
+        max > 0.0,
+        "the config has no supplementary housing fund, give supplementary_housing_fund.max_ratio"
+    );
+    let mut r = record.clone();
+    r.supplementary_housing = 0.0;
+    let without = optimize(config, &r, options)?.after.total();
+    (1..=(max * 100.0 + 1e-9).floor() as u32)
+        .map(|percent| {
+            r.supplementary_housing = percent as f64 / 100.0;
+            let employee = config.supplementary_housing(&r);
+            let saving = without - optimize(config, &r, options)?.after.total();
+            Ok(HousingFundRatio {
+                ratio: r.supplementary_housing,
+                employee,
+                employer: employee,
+                saving,
+                saving_per_yuan: saving / employee,
+            })
+        })
+        .try_collect()
+}
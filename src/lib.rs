@@ -0,0 +1,126 @@
+//! Personal Tax Optimizer.
+//!
+//! The crate computes the income tax for a [`Record`] under a [`TaxConfig`] and searches for the
+//! movement of year bonus into salary that minimizes the total payment:
+//!
+//! ```no_run
+//! # fn main() -> anyhow::Result<()> {
+//! let config = pto::TaxConfig::default();
+//! let record = pto::Record::new(30000.0, 5000.0, 120000.0);
+//! let best = pto::optimize(&config, &record, &Default::default())?;
+//! println!("After: {}\nMovement: {}", best.after, best.movement);
+//! # Ok(())
+//! # }
+//! ```
+#![feature(iterator_try_collect)]
+#![feature(btree_cursors)]
+
+mod allowance;
+mod benefits;
+mod business;
+mod chart;
+mod config;
+mod constraint;
+mod credit;
+mod date;
+mod deduction;
+mod defer;
+mod donation;
+mod employer;
+mod foreign;
+mod grid;
+mod household;
+mod housing_fund;
+mod marginal;
+#[cfg(feature = "milp")]
+mod milp;
+mod offer;
+mod optimize;
+mod payroll;
+mod pension;
+#[cfg(feature = "plot")]
+mod plot;
+mod policy;
+mod project;
+mod rebate;
+mod reconcile;
+mod record;
+#[cfg(feature = "remote")]
+mod remote;
+mod remuneration;
+mod rental;
+mod resident;
+mod rsu;
+mod schedule;
+mod schema;
+mod sensitivity;
+mod simulate;
+mod social;
+mod solve;
+mod split;
+mod state;
+mod surtax;
+mod tax;
+mod vat;
+
+pub use allowance::{allowance_exemptions, Allowance, AllowanceExemption, ExemptAllowance};
+pub use benefits::{elect, parse_benefits, Benefit, Election, Elections, Treatment};
+pub use business::{draw, BusinessRules, Draw, DrawAnalysis};
+pub use chart::chart;
+pub use config::{
+    city_preset, date_segments, filing_statuses, init_config, is_remote, migrate_config,
+    parse_config, read_config, region_preset, render_config, select_date, select_profile,
+    ConfigFormat, Explanation, Mode, Rule, Slice, Table, Taper, TaxConfig, Trap, CITIES,
+    DEFAULT_CONFIG, REGIONS, SCHEMA_VERSION,
+};
+pub use constraint::PayrollPolicy;
+pub use credit::Credit;
+pub use date::Date;
+pub use deduction::{choose_housing, HousingChoice, SpecialDeductionRules, SpecialDeductions};
+pub use defer::{defer_bonus, Deferral, DeferralPlan};
+pub use donation::{advise_donation, donation, Donation, DonationRules};
+pub use employer::{employer_cost, EmployerCost};
+pub use foreign::{foreign_credits, ForeignCredit, ForeignSource};
+pub use grid::{grid, parse_steps, GridPoint, Steps};
+pub use household::{
+    optimize_household, parse_shared, Household, HouseholdOptimization, SHARED_KEYS,
+};
+pub use housing_fund::{housing_fund, HousingFundRatio, HousingFundRules};
+pub use marginal::{marginal, Kept, Marginal};
+#[cfg(feature = "milp")]
+pub use milp::solve_milp;
+pub use offer::Offer;
+pub use optimize::{
+    evaluate, net_spread, npv, optimize, tax_curve, thresholds, tradeoff, Objective, Optimization,
+    OptimizeOptions, SweepPoint, Threshold, TradeOff,
+};
+pub use payroll::{PayrollItem, PayrollTax};
+pub use pension::{
+    advise_pension, annuity, pension, Annuity, AnnuityRules, Pension, PensionAdvice, PensionRules,
+};
+#[cfg(feature = "plot")]
+pub use plot::plot;
+pub use policy::{compare_policies, compare_statuses, Policy, PolicyComparison, StatusComparison};
+pub use project::{project, ProjectedYear};
+pub use rebate::Rebate;
+pub use reconcile::{combine, reconcile, Reconciliation};
+pub use record::{parse_record, parse_records, Purchase, Record, Vest};
+#[cfg(feature = "remote")]
+pub use remote::{
+    parse_trusted_key, read_remote_config, tables_dir, update_tables, Fetched, TableUpdate,
+};
+pub use remuneration::{Remuneration, RemunerationRules};
+pub use rental::RentalRules;
+pub use resident::{foreign_income, ForeignIncome, NonResidentRules, ResidentStatus};
+pub use rsu::{parse_rsu_vest, rsu, RsuPlan, RsuVest, RsuVestEvent};
+pub use schedule::{allocate, schedule, schedule_allocated, Allocation, Month};
+pub use schema::config_schema;
+pub use sensitivity::{sensitivity, SalaryChange, Sensitivity};
+pub use simulate::{parse_distribution, simulate, BonusDistribution, FixedMovement, Simulation};
+pub use social::{Contribution, SocialInsurance};
+pub use solve::{gross_up, solve_gross, GrossPackage, GrossUp};
+pub use split::{split, Split, SplitAnalysis};
+pub use state::StateTax;
+pub use surtax::{Surtax, SurtaxBase};
+pub use tax::Tax;
+pub use vat::VatRules;
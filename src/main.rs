@@ -1,156 +1,1852 @@
-#![feature(iterator_try_collect)]
-#![feature(btree_cursors)]
+use std::path::PathBuf;
 
-use std::{collections::BTreeMap, path::PathBuf};
-
-use anyhow::{anyhow, Result};
-use clap::Parser;
+use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use pto::{
+    parse_record, Month, Optimization, OptimizeOptions, Record, ResidentStatus, SpecialDeductions,
+    Tax, TaxConfig,
+};
+use serde_json::json;
 
 /// Personal Tax Optimizer. It tries to find the optimal movement to minimize your tax payment.
 #[derive(Parser)]
 struct Args {
-    /// Input your case in a comma delimited format: monthly_salary,monthly_tax_deduction,
-    /// year_bonus.
-    #[arg(short, long, value_parser=parse_record)]
-    record: Record,
-    #[arg(short, long, value_name = "FILE")]
+    /// A config file whose tables replace the built-in China tables, or an https:// URL of one,
+    /// which is cached for offline use if pto is built with the `remote` feature. Defaults to the
+    /// first of ./config.toml,
+    /// $XDG_CONFIG_HOME/pto/config.toml and ~/.config/pto/config.toml that exists.
+    #[arg(short, long, value_name = "FILE", env = "PTO_CONFIG", global = true)]
     config: Option<PathBuf>,
+    /// An ed25519 public key, in base64, that a remote config must be signed with. The signature
+    /// is fetched from the URL of the config plus `.sig`. Repeat it to trust several keys.
+    #[cfg(feature = "remote")]
+    #[arg(long, value_name = "KEY", value_parser = pto::parse_trusted_key, global = true)]
+    trusted_key: Vec<ed25519_dalek::VerifyingKey>,
+    /// The format of the config file: toml, yaml or json. Detected by its extension if not given,
+    /// TOML for any other. YAML needs the `yaml` feature.
+    #[arg(long, value_name = "FORMAT", global = true)]
+    config_format: Option<pto::ConfigFormat>,
+    /// Use the tables of the `[profile.NAME]` section of the config on top of the others, e.g.
+    /// for one client or employer among several.
+    #[arg(long, value_name = "NAME", global = true)]
+    profile: Option<String>,
+    // The help lists the built-in regions, see `region_help`.
+    #[arg(long, env = "PTO_REGION", global = true, help = region_help())]
+    region: Option<String>,
+    /// The filing status in regions that have them, e.g. single, mfj, mfs or hoh in the US, or
+    /// the tax class 1 to 6 in Germany, the old or new regime in India, or citizen or foreigner in
+    /// Singapore.
+    /// Overrides `filing_status` of the config.
+    #[arg(long, global = true)]
+    filing_status: Option<String>,
+    /// Use the social insurance preset of a city: beijing, shanghai, shenzhen or hangzhou.
+    #[arg(long, global = true)]
+    city: Option<String>,
+    /// Whether you are a tax resident (183 days or more in China in the year): resident or
+    /// non_resident. Overrides `resident_status` of the config.
+    #[arg(long, global = true)]
+    resident_status: Option<ResidentStatus>,
+    /// The first month employed in the year, 1 to 12, e.g. 5 when joining in May. Salary, the
+    /// standard deduction and the cumulative withholding only count the months employed.
+    /// Overrides `start_month` of the config.
+    #[arg(long, value_name = "MONTH", global = true)]
+    start_month: Option<u32>,
+    /// The last month employed in the year, 1 to 12, e.g. when leaving. Overrides `end_month` of
+    /// the config.
+    #[arg(long, value_name = "MONTH", global = true)]
+    end_month: Option<u32>,
+    /// The tax year, choosing among the tables of the config keyed by year, e.g. `[salary.2024]`
+    /// and `[salary.2025]`, the one of the latest year not after it. Defaults to the year of
+    /// `--as-of-date` or the current year. When tables keyed by date, e.g. `[salary.2024-07-01]`,
+    /// change within the year, `calc` prorates the tax of each version by the days it is in force,
+    /// and other commands use the version of the last day.
+    #[arg(long, global = true)]
+    year: Option<i32>,
+    /// Use the tables in force on this day, YYYY-MM-DD, without proration.
+    #[arg(long, value_name = "DATE", global = true)]
+    as_of_date: Option<pto::Date>,
+    /// Print which config is used to stderr.
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// How to print the results.
+    #[arg(long, value_enum, default_value_t = Format::Text, global = true)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
 }
 
-fn parse_record(arg: &str) -> Result<Record> {
-    let tokens: Vec<_> = arg.split(',').map(|s| s.parse::<f64>()).try_collect()?;
-    Ok(Record {
-        monthly_salary: tokens[0],
-        monthly_tax_deduction: tokens[1],
-        year_bonus: tokens[2],
-        movement: 0.0,
-    })
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Format {
+    Text,
+    Json,
 }
 
-#[derive(Clone)]
-struct Record {
-    monthly_salary: f64,
-    monthly_tax_deduction: f64,
-    year_bonus: f64,
-    movement: f64,
+/// How `schedule` finds the movement and its allocation.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Solver {
+    /// Search the bracket crossings, then allocate by a dynamic program.
+    Search,
+    /// Solve both at once as a mixed integer program, provably optimal. Needs pto built with the
+    /// `milp` feature.
+    Milp,
 }
 
-impl Record {
-    fn adjust(&mut self, budget: f64) -> Result<()> {
-        let budget = self.year_bonus.min(budget);
-        anyhow::ensure!(budget > 0.0, "budget is invalid");
-        self.year_bonus -= budget;
-        self.movement += budget;
-        Ok(())
-    }
+/// What the movement search counts as best.
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Objective {
+    /// The least total tax.
+    MinTax,
+    /// The most annual income after tax and social insurance.
+    MaxNet,
+    /// The least total tax that leaves at least `--min-monthly-net` in every month.
+    MinTaxSubjectToMinMonthlyNet,
 }
 
-struct Tax {
-    salary: f64,
-    year_bonus: f64,
+/// The help of `--region`, with the built-in regions.
+fn region_help() -> String {
+    let regions: Vec<_> = pto::REGIONS.iter().map(|(region, _)| *region).collect();
+    format!(
+        "Use the built-in tables of a region, China (cn) by default: {}, or one downloaded by \
+         `pto update-tables`. Overrides `region` of the config",
+        regions.join(", ")
+    )
 }
 
-impl std::fmt::Display for Tax {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let total = self.salary + self.year_bonus;
-        f.write_fmt(format_args!(
-            "{total} (tax for salary: {}, tax for year bonus: {})",
-            self.salary, self.year_bonus
-        ))
-    }
+/// The record of a subcommand.
+#[derive(clap::Args)]
+struct RecordArg {
+    /// Input your case as `salary=30000,deduction=5000,bonus=120000`, or in the positional format
+    /// monthly_salary,monthly_tax_deduction,year_bonus.
+    #[arg(short, long, value_parser=parse_record, env = "PTO_RECORD")]
+    record: Record,
 }
 
-impl Tax {
-    fn total(&self) -> f64 {
-        self.salary + self.year_bonus
+/// Options of the movement search.
+#[derive(clap::Args)]
+struct SearchArgs {
+    /// Sweep the movement in steps of this size instead of solving exactly.
+    #[arg(long)]
+    step: Option<f64>,
+    /// The resolution of the movement, e.g. 0.01 to optimize to the cent.
+    #[arg(long, default_value_t = 0.01)]
+    precision: f64,
+    /// Also try moving up to this much salary into the year bonus.
+    #[arg(long, default_value_t = 0.0)]
+    max_reverse: f64,
+    /// Keep at least this much of the year bonus, e.g. one month's bonus.
+    #[arg(long, default_value_t = 0.0)]
+    min_bonus: f64,
+    /// Move at most this much of the year bonus into salary.
+    #[arg(long)]
+    max_movement: Option<f64>,
+    /// Only move multiples of this amount, e.g. 1000.
+    #[arg(long)]
+    granularity: Option<f64>,
+    /// A TOML file of the payroll policy of the company: the movement allowed with `min`, `max`
+    /// and `granularity` under `[movement]`, the bonus kept and the months it may be paid in with
+    /// `min` and `payout_months` under `[bonus]`, and the cap of the monthly salary with
+    /// `max_monthly` under `[salary]`.
+    #[arg(long, value_name = "FILE")]
+    policy: Option<PathBuf>,
+    /// What the best movement is.
+    #[arg(long, value_enum, default_value_t = Objective::MinTax)]
+    objective: Objective,
+    /// The least net pay of every month, for `--objective min-tax-subject-to-min-monthly-net`.
+    #[arg(long)]
+    min_monthly_net: Option<f64>,
+    /// Give up this much tax for each unit less between the highest and the lowest net salary of
+    /// the months, and print the trade-off between the two.
+    #[arg(long)]
+    smoothness: Option<f64>,
+    /// Maximize the net pay discounted to the start of the year at this annual rate, e.g. 0.05,
+    /// by the month it arrives, instead of minimizing the tax.
+    #[arg(long)]
+    discount_rate: Option<f64>,
+}
+
+impl SearchArgs {
+    /// The payroll policy of `--policy`, if given.
+    fn policy(&self) -> Result<Option<pto::PayrollPolicy>> {
+        let Some(path) = &self.policy else {
+            return Ok(None);
+        };
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("cannot read {}", path.display()))?;
+        let policy =
+            pto::PayrollPolicy::parse(&text).with_context(|| format!("in {}", path.display()))?;
+        Ok(Some(policy))
+    }
+
+    /// The options of the search, within the payroll policy.
+    fn options(&self) -> Result<OptimizeOptions> {
+        anyhow::ensure!(
+            self.smoothness.is_none() || self.discount_rate.is_none(),
+            "--smoothness and --discount-rate cannot be given together"
+        );
+        let mut options = OptimizeOptions {
+            step: self.step,
+            precision: self.precision,
+            max_reverse: self.max_reverse,
+            min_bonus: self.min_bonus,
+            max_movement: self.max_movement,
+            granularity: self.granularity,
+            max_monthly_salary: None,
+            record_sweep: false,
+            objective: match (self.objective, self.min_monthly_net) {
+                (Objective::MinTax, None) if self.smoothness.is_some() => {
+                    pto::Objective::Smooth(self.smoothness.unwrap_or(0.0))
+                }
+                (_, _) if self.smoothness.is_some() => {
+                    anyhow::bail!("--smoothness only applies to --objective min-tax")
+                }
+                (Objective::MinTax | Objective::MaxNet, None) if self.discount_rate.is_some() => {
+                    pto::Objective::MaxNpv(self.discount_rate.unwrap_or(0.0))
+                }
+                (_, _) if self.discount_rate.is_some() => {
+                    anyhow::bail!("--discount-rate only applies to --objective min-tax or max-net")
+                }
+                (Objective::MinTaxSubjectToMinMonthlyNet, Some(floor)) => {
+                    pto::Objective::MinTaxWithMonthlyNet(floor)
+                }
+                (Objective::MinTaxSubjectToMinMonthlyNet, None) => {
+                    anyhow::bail!(
+                        "--objective min-tax-subject-to-min-monthly-net needs --min-monthly-net"
+                    )
+                }
+                (_, Some(_)) => anyhow::bail!(
+                    "--min-monthly-net needs --objective min-tax-subject-to-min-monthly-net"
+                ),
+                (Objective::MinTax, None) => pto::Objective::MinTax,
+                (Objective::MaxNet, None) => pto::Objective::MaxNet,
+            },
+        };
+        if let Some(policy) = self.policy()? {
+            policy.apply(&mut options)?;
+        }
+        Ok(options)
     }
 }
 
-struct TaxConfig {
-    salary: BTreeMap<i32, f64>,
-    year_bonus: BTreeMap<i32, f64>,
+#[derive(Subcommand)]
+enum Command {
+    /// Calculate the tax for a record as given.
+    Calc {
+        #[command(flatten)]
+        record: RecordArg,
+        /// Show the tax of every bracket that was hit.
+        #[arg(long)]
+        explain: bool,
+        /// Show the total cost of employment to the employer next to the net income, e.g. to
+        /// negotiate an offer from the same figures.
+        #[arg(long)]
+        employer_view: bool,
+    },
+    /// Find the movement between year bonus and salary that minimizes the tax.
+    Optimize {
+        #[command(flatten)]
+        record: RecordArg,
+        #[command(flatten)]
+        search: SearchArgs,
+        /// Show the tax of every bracket that was hit, before and after the movement.
+        #[arg(long)]
+        explain: bool,
+        /// Show the total cost of employment to the employer next to the net income, e.g. to
+        /// negotiate an offer from the same figures.
+        #[arg(long)]
+        employer_view: bool,
+        /// Write every movement evaluated and its tax to a CSV file with the columns movement,
+        /// salary_tax, bonus_tax and total, to plot the tax against the movement.
+        #[arg(long, value_name = "FILE")]
+        sweep_output: Option<PathBuf>,
+        /// Plot the total tax across the range of movements, with the best one marked.
+        #[arg(long)]
+        chart: bool,
+        /// Plot the total tax and the net income against the movement to an SVG file, with the
+        /// bracket bounds crossed and the best movement marked. Needs pto built with the `plot`
+        /// feature.
+        #[arg(long, value_name = "FILE")]
+        plot: Option<PathBuf>,
+        /// Report how much the result depends on the inputs: the tax 1000 of movement either
+        /// side of the best one, the best movement with the salary 1% higher or lower, and how
+        /// close it is to a bracket bound.
+        #[arg(long)]
+        sensitivity: bool,
+    },
+    /// Calculate the tax for a record with a given movement, e.g. the split the employer allows,
+    /// without searching for the best one.
+    Evaluate {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The amount moved from the year bonus into salary, negative to move salary into the
+        /// year bonus.
+        #[arg(long, allow_hyphen_values = true)]
+        movement: f64,
+        /// Show the tax of every bracket that was hit, before and after the movement.
+        #[arg(long)]
+        explain: bool,
+        /// Show the total cost of employment to the employer next to the net income, e.g. to
+        /// negotiate an offer from the same figures.
+        #[arg(long)]
+        employer_view: bool,
+    },
+    /// Compare taxing the year bonus separately with merging it into salary.
+    ComparePolicy {
+        #[command(flatten)]
+        record: RecordArg,
+    },
+    /// Compare the tax under each filing status of the region, e.g. the old and new regimes of
+    /// India, and recommend the cheapest.
+    CompareStatus {
+        #[command(flatten)]
+        record: RecordArg,
+    },
+    /// Show the tax saved by the private pension contribution of the record and recommend one.
+    Pension {
+        #[command(flatten)]
+        record: RecordArg,
+    },
+    /// Show what joining the enterprise annuity plan is worth, with the ratios of the record or the
+    /// caps.
+    Annuity {
+        #[command(flatten)]
+        record: RecordArg,
+    },
+    /// Show the tax saved by contributing each ratio to the supplementary housing fund
+    /// (补充住房公积金), and per yuan contributed.
+    HousingFund {
+        #[command(flatten)]
+        record: RecordArg,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Suggest the largest fully deductible donation, or the smallest one saving a target amount.
+    Donate {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The tax the donation should save.
+        #[arg(long)]
+        target: Option<f64>,
+    },
+    /// Print the monthly withholding and net pay, before and after the optimized movement.
+    Schedule {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The month the year bonus is paid in, the last month employed if not given.
+        #[arg(long)]
+        bonus_month: Option<u32>,
+        /// Allocate the movement to the months that withhold the least tax ahead of time,
+        /// within `--policy`, instead of spreading it evenly.
+        #[arg(long)]
+        allocate: bool,
+        /// How to find the movement and its allocation; `milp` always allocates.
+        #[arg(long, value_enum, default_value_t = Solver::Search)]
+        solver: Solver,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Compute the final annual tax and the refund or amount owed at the annual reconciliation.
+    Reconcile {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The tax actually withheld from each month's salary, comma delimited. Computed with the
+        /// cumulative method if not given.
+        #[arg(long, value_delimiter = ',')]
+        withheld: Vec<f64>,
+        /// The tax actually withheld from the year bonus. Computed if not given.
+        #[arg(long)]
+        bonus_withheld: Option<f64>,
+        /// The tax actually withheld from labor and author remuneration and royalties. Computed if
+        /// not given.
+        #[arg(long)]
+        remuneration_withheld: Option<f64>,
+        /// The salary and bonus from a second, concurrent employer, e.g.
+        /// `salary=8000,bonus=0,social_base=0` when it pays no social insurance. It withholds on
+        /// its own with another standard deduction, so the reconciliation usually owes tax.
+        #[arg(long, value_parser=|s: &str| parse_record(s).map(Box::new), value_name = "RECORD")]
+        second_employer: Option<Box<Record>>,
+        /// The tax actually withheld by the second employer, salary and bonus. Computed with the
+        /// cumulative method if not given.
+        #[arg(long, requires = "second_employer")]
+        second_withheld: Option<f64>,
+    },
+    /// Print the configured bracket tables.
+    Brackets,
+    /// Write a config to start from for the region of `--region`, China by default, to the file
+    /// of `--config` or ./config.toml.
+    Init {
+        /// Overwrite the file if it exists.
+        #[arg(long)]
+        force: bool,
+    },
+    /// Download the tax tables of new years, or corrected ones, from a published index into
+    /// ~/.config/pto/tables, where `--region` finds them, and report which regions changed. Needs
+    /// pto built with the `remote` feature.
+    UpdateTables {
+        /// The URL of the index, a TOML file listing `[tables.REGION]` with the `version` and the
+        /// `url` of each region, relative to the index.
+        #[arg(long, value_name = "URL", env = "PTO_TABLES_INDEX")]
+        index: String,
+    },
+    /// Check the config.
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+    /// Optimize several records or job offers and compare the results side by side, with the
+    /// difference of each from the first.
+    Compare {
+        /// A case in the same format as `optimize --record`. Repeat it for each scenario.
+        #[arg(short, long, value_parser=parse_record, required_unless_present = "offer")]
+        record: Vec<Record>,
+        /// A TOML file of a job offer: its `name`, its `record` in the same format as `--record`,
+        /// the social insurance preset of its `city`, and any keys of the config, e.g. `region`
+        /// for an offer abroad. Repeat it for each offer.
+        #[arg(long, value_name = "FILE")]
+        offer: Vec<PathBuf>,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Jointly choose the movements of two spouses and the split of the children education and
+    /// infant care deductions between them to minimize their combined tax.
+    Household {
+        /// The case of each spouse, in the same format as `optimize --record`. Give it twice.
+        #[arg(short, long, value_parser=parse_record, num_args = 1, required = true)]
+        record: Vec<Record>,
+        /// Deductions shared by the household, e.g. `children_education=2`, on top of those in
+        /// the records.
+        #[arg(long, value_parser=pto::parse_shared)]
+        shared: Option<SpecialDeductions>,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Optimize every record of a CSV file. The header names the columns with record keys, e.g.
+    /// `name,salary,deduction,bonus`; without one, every line is positional.
+    Batch {
+        #[arg(value_name = "FILE")]
+        file: PathBuf,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Optimize every combination of a range of monthly salaries and of year bonuses, e.g. to
+    /// weigh the splits of an offer, and print the best movement and the tax of each as a matrix.
+    Sweep {
+        /// The monthly salaries as `from..to:step`, e.g. `25000..40000:1000`.
+        #[arg(long, value_parser=pto::parse_steps)]
+        salary: pto::Steps,
+        /// The year bonuses as `from..to:step`, e.g. `50000..200000:10000`.
+        #[arg(long, value_parser=pto::parse_steps)]
+        bonus: pto::Steps,
+        /// The rest of the case, e.g. the deductions, in the same format as `optimize --record`.
+        /// Its salary and year bonus are replaced by those of the sweep.
+        #[arg(short, long, value_parser=parse_record)]
+        record: Option<Record>,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Find the least gross package, salary and bonuses, that leaves a net income under the best
+    /// movement.
+    Solve {
+        /// The annual net income to reach, after tax and social insurance.
+        #[arg(long)]
+        target_net: f64,
+        /// The shape of the package, in the same format as `optimize --record`: its salary and
+        /// bonuses are scaled alike, and its deductions kept. All salary if not given.
+        #[arg(short, long, value_parser=parse_record)]
+        record: Option<Record>,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Show how much of more salary or year bonus is kept after tax and social insurance, as
+    /// given and under the best movement.
+    Marginal {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The annual amount added, to the salary spread over the months or to the year bonus.
+        #[arg(long, default_value_t = 1000.0)]
+        delta: f64,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Split a compensation budget between base salary and year bonus to leave the most net
+    /// income, and show where paying more as salary stops helping.
+    Split {
+        /// The annual compensation, base salary and year bonus together.
+        #[arg(long)]
+        budget: f64,
+        /// The monthly salaries tried go up in steps of this.
+        #[arg(long, default_value_t = 100.0)]
+        step: f64,
+        /// The deductions and other income, in the same format as `optimize --record`. Its salary
+        /// and bonuses are replaced by the split.
+        #[arg(short, long, value_parser=parse_record)]
+        record: Option<Record>,
+    },
+    /// Recommend which benefits of a cafeteria plan to elect, e.g. extra housing fund, meal cards
+    /// or commercial health insurance, for the most net value.
+    Benefits {
+        #[command(flatten)]
+        record: RecordArg,
+        /// A TOML file of the benefits, each a `[[benefit]]` with its `name`, its annual `cost`,
+        /// its `value` to you if not the cost, and its `treatment`: pre_tax, post_tax or
+        /// health_insurance.
+        #[arg(long, value_name = "FILE")]
+        menu: PathBuf,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Compute the tax withheld from every vesting of RSUs and the shares sold to cover it, and
+    /// the tax of the year with them.
+    ///
+    /// The equity of the record is replaced by the vestings.
+    Rsu {
+        #[command(flatten)]
+        record: RecordArg,
+        /// A vesting as `shares@YYYY-MM-DD`. Repeat it for every vesting of the year.
+        #[arg(long, value_parser=pto::parse_rsu_vest, required = true)]
+        vest: Vec<pto::RsuVest>,
+        /// The assumed share price at every vesting.
+        #[arg(long)]
+        price: f64,
+    },
+    /// Decide how much of the profit of your business to pay yourself as salary and how much to
+    /// leave as business income, for the most net income.
+    ///
+    /// The record gives the revenue and expenses of the business and your deductions, e.g.
+    /// `salary=0,bonus=0,invoices=60000;60000,business_expenses=20000`. Its salary is replaced by
+    /// the draw.
+    Draw {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The monthly salaries tried go up in steps of this.
+        #[arg(long, default_value_t = 100.0)]
+        step: f64,
+    },
+    /// Decide how much of the year bonus to defer to January of the next year to pay the least
+    /// tax of both years, with the tables of each year.
+    ///
+    /// The record is this year's case.
+    Defer {
+        #[command(flatten)]
+        record: RecordArg,
+        /// Next year's case without the deferred bonus, in the same format as `--record`. Boxed,
+        /// as two records make this the largest command.
+        #[arg(long, value_parser=|s: &str| parse_record(s).map(Box::new))]
+        next: Box<Record>,
+        /// The deferrals tried go up in steps of this, besides those that bring a bonus to a
+        /// bracket bound.
+        #[arg(long, default_value_t = 1000.0)]
+        deferral_step: f64,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Project the tax, the net income and the best movement of the coming years under an annual
+    /// raise and, optionally, brackets indexed to inflation.
+    Project {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The number of years, from the tax year.
+        #[arg(long, default_value_t = 5)]
+        years: u32,
+        /// The annual raise of the salary and bonuses, e.g. 0.05 for 5%.
+        #[arg(long, default_value_t = 0.0)]
+        raise: f64,
+        /// The annual indexation of the bracket bounds, e.g. 0.02 for 2% inflation. The brackets
+        /// stay as they are if not given.
+        #[arg(long)]
+        indexation: Option<f64>,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Simulate an uncertain year bonus to see how the best movement varies, and find the
+    /// movement to agree before the bonus is known.
+    ///
+    /// The year bonus of the record is replaced by the draws.
+    Simulate {
+        #[command(flatten)]
+        record: RecordArg,
+        /// The distribution of the year bonus: `normal:MEAN,SD`, or `discrete:` and outcomes as
+        /// `AMOUNT@PROBABILITY` delimited by `;`, e.g. `discrete:100000@0.3;150000@0.7`.
+        #[arg(long, value_parser=pto::parse_distribution)]
+        bonus: pto::BonusDistribution,
+        /// The number of bonuses drawn.
+        #[arg(long, default_value_t = 1000)]
+        draws: usize,
+        /// The seed of the draws, to repeat a simulation.
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        #[command(flatten)]
+        search: SearchArgs,
+    },
+    /// Gross up a net-pay agreement, in which the employer bears the tax and social insurance:
+    /// find the gross monthly salary and year bonus that leave exactly the agreed net.
+    GrossUp {
+        /// The agreed monthly net salary, on average over the year.
+        #[arg(long)]
+        net_salary: f64,
+        /// The agreed net year bonus.
+        #[arg(long, default_value_t = 0.0)]
+        net_bonus: f64,
+        /// The deductions and other income, in the same format as `optimize --record`. Its salary
+        /// and bonuses are replaced by the gross ones.
+        #[arg(short, long, value_parser=parse_record)]
+        record: Option<Record>,
+    },
 }
 
-impl TryFrom<toml::Table> for TaxConfig {
-    type Error = anyhow::Error;
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Load the config, warn about suspicious tables, e.g. a tax that jumps at a bound or a
+    /// missing open-ended bracket, and print the tables in effect. A config that does not load,
+    /// e.g. with unordered or duplicate bounds, fails with the offending rule.
+    Lint,
+    /// Print a JSON Schema of the config file, for editors to validate and complete configs.
+    Schema,
+    /// Rewrite the config file of an older `schema_version` to the current one, keeping the old
+    /// file with the extension .bak. A config without `schema_version` is taken to be of version
+    /// 1, the format of the first releases. Comments are not kept.
+    Migrate,
+}
 
-    fn try_from(tbl: toml::Table) -> Result<Self> {
-        let parse = |name: &str| -> Result<BTreeMap<i32, f64>> {
-            let mut ret = BTreeMap::new();
-            for r in tbl[name]["rule"]
-                .as_array()
-                .ok_or_else(|| anyhow!("rule is not an array"))?
-            {
-                ret.insert(
-                    r["bound"]
-                        .as_integer()
-                        .map(|v| v as i32)
-                        .ok_or_else(|| anyhow!("missing bound"))?,
-                    r["ratio"]
-                        .as_float()
-                        .ok_or_else(|| anyhow!("missing ratio"))?,
-                );
-            }
-            Ok(ret)
-        };
-        Ok(Self {
-            salary: parse("salary")?,
-            year_bonus: parse("year_bonus")?,
+/// A warning if the year bonus falls in a trap interval of the bonus table.
+fn trap_warning(config: &TaxConfig, bonus: f64) -> Option<String> {
+    let trap = config.bonus_trap(bonus)?;
+    Some(format!(
+        "the year bonus {bonus:.2} is in the trap interval ({:.2}, {:.2}], which leaves less \
+         after tax than a bonus of {:.2}; the nearest safe amount is {:.2}",
+        trap.lower,
+        trap.upper,
+        trap.lower,
+        trap.nearest_safe(bonus)
+    ))
+}
+
+/// A warning if the equity income is taxed at a higher marginal rate than the salary, so that
+/// vesting or exercising part of it in another year would save tax.
+fn equity_warning(config: &TaxConfig, record: &Record, tax: &Tax) -> Result<Option<String>> {
+    let Some(last) = config.salary.slices(record.equity_income())?.pop() else {
+        return Ok(None);
+    };
+    Ok((last.ratio > tax.marginal_rate).then(|| {
+        format!(
+            "the equity income is taxed at a marginal rate of {:.2}%, above the {:.2}% of salary; \
+             moving some of it into another year may lower its tax",
+            last.ratio * 100.0,
+            tax.marginal_rate * 100.0
+        )
+    }))
+}
+
+/// What to print besides the result of an optimization.
+#[derive(Clone, Copy)]
+struct Details {
+    explain: bool,
+    employer_view: bool,
+}
+
+/// Print the tax of `record` before and after the movement of `best`.
+fn print_optimization(
+    tax_config: &TaxConfig,
+    record: &Record,
+    best: &Optimization,
+    tradeoff: Option<&[pto::TradeOff]>,
+    sensitivity: Option<&pto::Sensitivity>,
+    details: Details,
+    json: bool,
+) -> Result<()> {
+    let explanation = details
+        .explain
+        .then(|| -> Result<_> {
+            Ok((
+                tax_config.explain(record)?,
+                tax_config.explain(&best.record)?,
+            ))
         })
+        .transpose()?;
+    let mut warnings: Vec<_> = trap_warning(tax_config, record.year_bonus)
+        .into_iter()
+        .collect();
+    if best.record.year_bonus != record.year_bonus {
+        warnings.extend(trap_warning(tax_config, best.record.year_bonus));
     }
+    warnings.extend(equity_warning(tax_config, record, &best.after)?);
+    let employer = details
+        .employer_view
+        .then(|| -> Result<_> {
+            Ok((
+                pto::employer_cost(tax_config, record)?,
+                pto::employer_cost(tax_config, &best.record)?,
+            ))
+        })
+        .transpose()?;
+    if json {
+        let mut out = json!({ "record": record, "result": best, "warnings": warnings });
+        if let Some((before, after)) = explanation {
+            out["explain"] = json!({ "before": before, "after": after });
+        }
+        if let Some((before, after)) = &employer {
+            out["employer"] = json!({ "before": before, "after": after });
+        }
+        if let Some(tradeoff) = tradeoff {
+            out["tradeoff"] = json!(tradeoff);
+        }
+        if let Some(sensitivity) = sensitivity {
+            out["sensitivity"] = json!(sensitivity);
+        }
+        println!("{out}");
+    } else {
+        println!("Before: {}", best.before);
+        if let Some((before, _)) = &explanation {
+            print!("{before}");
+        }
+        println!("After: {}\nMovement: {}", best.after, best.movement);
+        if !best.binding.is_empty() {
+            println!("Binding constraints: {}", best.binding.join(", "));
+        }
+        if !record.other_bonuses.is_empty() {
+            println!(
+                "Separately taxed bonus: {}",
+                best.record.year_bonus + best.movement
+            );
+        }
+        if let Some((_, after)) = &explanation {
+            print!("{after}");
+        }
+        if let Some((before, after)) = &employer {
+            println!("Employer cost before: {before}");
+            println!("Employer cost after: {after}");
+        }
+        if let Some(tradeoff) = tradeoff {
+            println!("Tax against the spread of the monthly net salary:");
+            println!("{:>14} {:>12} {:>12}", "movement", "tax", "spread");
+            for p in tradeoff {
+                println!("{:>14.2} {:>12.2} {:>12.2}", p.movement, p.tax, p.spread);
+            }
+        }
+        if let Some(s) = sensitivity {
+            print_sensitivity(best, s);
+        }
+        for w in warnings {
+            println!("Warning: {w}");
+        }
+    }
+    Ok(())
 }
 
-impl TaxConfig {
-    /// Caluculate the tax for the given record. Return tax for salary and tax for year bouns in
-    /// tuple format.
-    fn calc(&self, r: &Record) -> Tax {
-        let total_salary = r.movement + 0f64.max(r.monthly_salary - r.monthly_tax_deduction) * 12.0;
-        let mut salary_tax = 0.0;
-        let mut last = 0.0;
-        for (rb, ratio) in &self.salary {
-            let budget = (*rb as f64).min(total_salary) - last;
-            salary_tax += budget * ratio;
-            if *rb as f64 >= total_salary {
-                break;
-            }
-            last = *rb as f64;
+/// Print how sensitive the movement of `best` is.
+fn print_sensitivity(best: &Optimization, s: &pto::Sensitivity) {
+    let total = best.after.total();
+    println!("Sensitivity:");
+    for (movement, tax) in [
+        (best.movement - s.step, s.below),
+        (best.movement + s.step, s.above),
+    ] {
+        match tax {
+            Some(tax) => println!("  Movement {movement}: tax {tax:.2} ({:+.2})", tax - total),
+            None => println!("  Movement {movement}: not allowed"),
         }
-        let cursor = self.year_bonus.lower_bound(std::ops::Bound::Included(
-            &((r.year_bonus / 12.0).ceil() as i32),
-        ));
-        let ratio = cursor.peek_next().unwrap().1;
-        let bonus_tax = ratio * r.year_bonus;
-        Tax {
-            salary: salary_tax,
-            year_bonus: bonus_tax,
+    }
+    for c in &s.salary {
+        println!(
+            "  Salary {:+}%: best movement {}, tax {:.2}, or {:.2} keeping the movement {}",
+            c.change * 100.0,
+            c.movement,
+            c.tax,
+            c.kept_tax,
+            best.movement
+        );
+    }
+    match &s.nearest {
+        Some(t) if s.on_edge => println!("  On the bracket bound {}", t.label),
+        Some(t) => println!(
+            "  Nearest bracket bound: {}, {:.2} away",
+            t.label,
+            (t.movement - best.movement).abs()
+        ),
+        None => println!("  No bracket bound within the range of movements"),
+    }
+}
+
+/// Print a withholding schedule as a table.
+fn print_schedule(months: &[Month]) {
+    // The equity columns are only shown for records with equity income.
+    let equity = months.iter().any(|m| m.equity != 0.0);
+    print!(
+        "{:>5} {:>12} {:>12} {:>14} {:>12} {:>12} {:>12}",
+        "month", "salary", "social", "cum. taxable", "withheld", "bonus", "bonus tax"
+    );
+    if equity {
+        print!(" {:>12} {:>12}", "equity", "equity tax");
+    }
+    println!(" {:>12}", "net");
+    for m in months {
+        print!(
+            "{:>5} {:>12.2} {:>12.2} {:>14.2} {:>12.2} {:>12.2} {:>12.2}",
+            m.month,
+            m.salary,
+            m.social_insurance,
+            m.cumulative_taxable,
+            m.withheld,
+            m.bonus,
+            m.bonus_tax
+        );
+        if equity {
+            print!(" {:>12.2} {:>12.2}", m.equity, m.equity_tax);
+        }
+        println!(" {:>12.2}", m.net);
+    }
+}
+
+/// Print the optimization results of named records as a table.
+fn print_results(rows: &[(String, Record, Optimization)], json: bool) {
+    if json {
+        let rows: Vec<_> = rows
+            .iter()
+            .map(|(name, r, best)| json!({ "name": name, "record": r, "result": best }))
+            .collect();
+        println!("{}", serde_json::Value::Array(rows));
+        return;
+    }
+    println!(
+        "{:<16} {:<44} {:>12} {:>12} {:>12} {:>12}",
+        "name", "record", "before", "after", "movement", "net"
+    );
+    for (name, r, best) in rows {
+        println!(
+            "{:<16} {:<44} {:>12.2} {:>12.2} {:>12.2} {:>12.2}",
+            name,
+            r.to_string(),
+            best.before.total(),
+            best.after.total(),
+            best.movement,
+            best.after.net
+        );
+    }
+}
+
+/// Print the points of a sweep as two matrices, the best movement and the total tax, with a row
+/// for each monthly salary and a column for each year bonus.
+fn print_grid(points: &[pto::GridPoint], bonuses: usize) {
+    for (title, tax) in [("Best movement", false), ("Total tax", true)] {
+        println!("{title} (rows: monthly salary, columns: year bonus):");
+        print!("{:>10}", "");
+        for p in points.iter().take(bonuses) {
+            print!(" {:>11}", p.year_bonus);
+        }
+        println!();
+        for row in points.chunks(bonuses) {
+            print!("{:>10}", row[0].monthly_salary);
+            for p in row {
+                print!(" {:>11.2}", if tax { p.tax } else { p.movement });
+            }
+            println!();
         }
     }
 }
 
 const DEFAULT_CONFIG_FILE_PATH: &str = "./config.toml";
 
+/// The config files looked for without `--config`, in order: the one in the working directory,
+/// then the one in the user config directory of XDG, which defaults to ~/.config.
+fn config_search_path() -> Vec<PathBuf> {
+    let mut ret = vec![PathBuf::from(DEFAULT_CONFIG_FILE_PATH)];
+    let home = std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config"));
+    let xdg = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        // Relative paths are invalid in XDG_CONFIG_HOME and are ignored.
+        .filter(|p| p.is_absolute());
+    for dir in xdg.into_iter().chain(home) {
+        let path = dir.join("pto").join("config.toml");
+        if !ret.contains(&path) {
+            ret.push(path);
+        }
+    }
+    ret
+}
+
+/// The error of the commands that fetch over HTTPS when pto is built without them.
+#[cfg(not(feature = "remote"))]
+const NO_REMOTE: &str = "pto is built without remote configs, build it with `--features remote`";
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
-    let raw_config: toml::Table = toml::from_str(
-        &tokio::fs::read_to_string(args.config.unwrap_or(DEFAULT_CONFIG_FILE_PATH.into())).await?,
-    )?;
-    let tax_config = TaxConfig::try_from(raw_config)?;
-    let mut payment = tax_config.calc(&args.record);
-
-    println!("Before: {payment}");
-
-    let mut r = args.record;
-    let mut movement = 0.0;
-    while r.year_bonus > 0.0 {
-        r.adjust(10.0)?;
-        let v = tax_config.calc(&r);
-        if v.total() < payment.total() {
-            payment = v;
-            movement = r.movement;
+    if let Command::Init { force } = args.command {
+        let path = args
+            .config
+            .unwrap_or_else(|| PathBuf::from(DEFAULT_CONFIG_FILE_PATH));
+        anyhow::ensure!(
+            !path.to_str().is_some_and(pto::is_remote),
+            "cannot write a remote config"
+        );
+        anyhow::ensure!(
+            force || !path.exists(),
+            "{} exists, pass --force to overwrite it",
+            path.display()
+        );
+        let config = pto::init_config(args.region.as_deref().unwrap_or("cn"))?;
+        tokio::fs::write(&path, config).await?;
+        println!("Wrote {}", path.display());
+        return Ok(());
+    }
+    if let Command::Config {
+        command: ConfigCommand::Schema,
+    } = args.command
+    {
+        println!("{}", serde_json::to_string_pretty(&pto::config_schema())?);
+        return Ok(());
+    }
+    #[cfg(not(feature = "remote"))]
+    if let Command::UpdateTables { .. } = &args.command {
+        anyhow::bail!(NO_REMOTE);
+    }
+    #[cfg(feature = "remote")]
+    if let Command::UpdateTables { index } = &args.command {
+        anyhow::ensure!(pto::is_remote(index), "{index} is not an https:// URL");
+        let updates = pto::update_tables(index, &args.trusted_key).await?;
+        if args.format == Format::Json {
+            println!("{}", json!(updates));
+            return Ok(());
+        }
+        for update in &updates {
+            match &update.old {
+                _ if !update.changed() => {
+                    println!("{}: {} is up to date", update.region, update.new)
+                }
+                Some(old) => println!("{}: updated from {old} to {}", update.region, update.new),
+                None => println!("{}: new, {}", update.region, update.new),
+            }
         }
+        if let Some(dir) = pto::tables_dir() {
+            println!("Tables are in {}", dir.display());
+        }
+        return Ok(());
+    }
+    let path = args
+        .config
+        .or_else(|| config_search_path().into_iter().find(|p| p.exists()));
+    if args.verbose {
+        match &path {
+            Some(path) => eprintln!("Config: {}", path.display()),
+            None => eprintln!("Config: none, the built-in tables"),
+        }
+    }
+    if let Command::Config {
+        command: ConfigCommand::Migrate,
+    } = args.command
+    {
+        let path = path.ok_or_else(|| anyhow::anyhow!("no config file to migrate"))?;
+        anyhow::ensure!(
+            !path.to_str().is_some_and(pto::is_remote),
+            "cannot migrate a remote config"
+        );
+        let format = args
+            .config_format
+            .unwrap_or_else(|| pto::ConfigFormat::from_path(&path));
+        let text = tokio::fs::read_to_string(&path).await?;
+        let mut tbl = pto::parse_config(&text, format)?;
+        let changes = pto::migrate_config(&mut tbl)?;
+        if changes.is_empty() {
+            println!(
+                "{} is of schema_version {} already",
+                path.display(),
+                pto::SCHEMA_VERSION
+            );
+            return Ok(());
+        }
+        let mut backup = path.clone().into_os_string();
+        backup.push(".bak");
+        tokio::fs::write(&backup, text).await?;
+        tokio::fs::write(&path, pto::render_config(&tbl, format)?).await?;
+        for change in &changes {
+            println!("{change}");
+        }
+        println!(
+            "Migrated {}, the old config is in {}",
+            path.display(),
+            PathBuf::from(backup).display()
+        );
+        return Ok(());
+    }
+    let mut overrides = match path {
+        Some(path) => match path.to_str().filter(|p| pto::is_remote(p)) {
+            #[cfg(not(feature = "remote"))]
+            Some(_) => anyhow::bail!(NO_REMOTE),
+            #[cfg(feature = "remote")]
+            Some(url) => {
+                let (tbl, fetched) =
+                    pto::read_remote_config(url, args.config_format, &args.trusted_key).await?;
+                if let pto::Fetched::Offline(e) = fetched {
+                    eprintln!("Warning: cannot fetch {url} ({e}), using the cached copy");
+                }
+                tbl
+            }
+            None => {
+                #[cfg(feature = "remote")]
+                anyhow::ensure!(
+                    args.trusted_key.is_empty(),
+                    "--trusted-key only applies to https:// configs"
+                );
+                pto::read_config(&path, args.config_format)?
+            }
+        },
+        None => toml::Table::new(),
+    };
+    pto::select_profile(&mut overrides, args.profile.as_deref())?;
+    if let Some(region) = args.region {
+        overrides.insert("region".to_string(), region.into());
+    }
+    if let Some(status) = args.filing_status {
+        overrides.insert("filing_status".to_string(), status.into());
     }
+    if let Some(month) = args.start_month {
+        overrides.insert("start_month".to_string(), i64::from(month).into());
+    }
+    if let Some(month) = args.end_month {
+        overrides.insert("end_month".to_string(), i64::from(month).into());
+    }
+    if let Some(city) = &args.city {
+        overrides.extend(pto::city_preset(city)?);
+    }
+    let year = args
+        .year
+        .or(args.as_of_date.map(|d| d.year))
+        .unwrap_or_else(|| pto::Date::today().year);
+    let dated = overrides.clone();
+    let date = args.as_of_date.unwrap_or(pto::Date::year_end(year));
+    pto::select_date(&mut overrides, date)?;
+    let load = |overrides: toml::Table| -> Result<TaxConfig> {
+        let mut config = TaxConfig::with_overrides(overrides)?;
+        if let Some(status) = args.resident_status {
+            config.resident_status = status;
+        }
+        Ok(config)
+    };
+    let tax_config = load(overrides.clone())?;
+    let json = args.format == Format::Json;
 
-    println!("After: {payment}\nMovement: {movement}");
+    match args.command {
+        Command::Calc {
+            record: RecordArg { record },
+            explain,
+            employer_view,
+        } => {
+            // The periods of the year with different tables, none if they do not change.
+            let segments = match args.as_of_date {
+                None => pto::date_segments(&dated, year),
+                Some(_) => Vec::new(),
+            };
+            let segments = if segments.len() > 1 {
+                segments
+            } else {
+                Vec::new()
+            };
+            let tax = if segments.is_empty() {
+                tax_config.calc(&record)?
+            } else {
+                let parts = segments
+                    .iter()
+                    .map(|(start, weight)| -> Result<_> {
+                        let mut overrides = dated.clone();
+                        pto::select_date(&mut overrides, *start)?;
+                        Ok((*weight, load(overrides)?.calc(&record)?))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                Tax::prorate(&parts)
+            };
+            let explanation = explain.then(|| tax_config.explain(&record)).transpose()?;
+            let warnings: Vec<_> = trap_warning(&tax_config, record.year_bonus)
+                .into_iter()
+                .collect();
+            let housing = pto::choose_housing(&tax_config, &record)?;
+            let remuneration_withholding = record
+                .remuneration()
+                .any(|(_, p)| !p.is_empty())
+                .then(|| tax_config.remuneration_withholding(&record))
+                .transpose()?;
+            let foreign = (!record.foreign_income.is_empty())
+                .then(|| pto::foreign_income(&tax_config, &record));
+            let credits = pto::foreign_credits(&tax_config, &record, tax.salary);
+            let allowances = pto::allowance_exemptions(&tax_config, &record);
+            let employer = employer_view
+                .then(|| pto::employer_cost(&tax_config, &record))
+                .transpose()?;
+            if json {
+                let mut out = json!({ "record": record, "tax": tax, "warnings": warnings });
+                if !segments.is_empty() {
+                    let prorated: Vec<_> = segments
+                        .iter()
+                        .map(|(start, weight)| json!({ "from": start, "share": weight }))
+                        .collect();
+                    out["prorated"] = json!(prorated);
+                }
+                if let Some(e) = explanation {
+                    out["explain"] = json!(e);
+                }
+                if let Some(w) = remuneration_withholding {
+                    out["remuneration_withholding"] = json!(w);
+                }
+                if let Some(h) = &housing {
+                    out["housing"] = json!(h);
+                }
+                if let Some(f) = &foreign {
+                    out["foreign_income"] = json!(f);
+                }
+                if !credits.is_empty() {
+                    out["foreign_credits"] = json!(credits);
+                }
+                if !allowances.is_empty() {
+                    out["allowances"] = json!(allowances);
+                }
+                if let Some(e) = &employer {
+                    out["employer"] = json!(e);
+                }
+                println!("{out}");
+            } else {
+                println!("Tax: {tax}");
+                if !segments.is_empty() {
+                    let shares: Vec<_> = segments
+                        .iter()
+                        .map(|(start, weight)| format!("from {start}: {:.2}%", weight * 100.0))
+                        .collect();
+                    println!("Prorated by the tables in force: {}", shares.join(", "));
+                }
+                if let Some(w) = remuneration_withholding {
+                    println!("Remuneration withholding: {w:.2}");
+                }
+                if let Some(h) = &housing {
+                    println!("Housing: {h}");
+                }
+                if let Some(f) = &foreign {
+                    println!("Foreign income: {f}");
+                }
+                for c in &credits {
+                    println!("Foreign tax credit, {c}");
+                }
+                for a in &allowances {
+                    println!("Allowance {a}");
+                }
+                if let Some(e) = &employer {
+                    println!("Employer cost: {e}");
+                }
+                if let Some(e) = explanation {
+                    print!("{e}");
+                }
+                for w in warnings {
+                    println!("Warning: {w}");
+                }
+            }
+        }
+        Command::Optimize {
+            record: RecordArg { record },
+            search,
+            explain,
+            employer_view,
+            sweep_output,
+            chart,
+            plot,
+            sensitivity,
+        } => {
+            #[cfg(not(feature = "plot"))]
+            if plot.is_some() {
+                anyhow::bail!("pto is built without plots, build it with `--features plot`");
+            }
+            let mut options = search.options()?;
+            options.record_sweep = sweep_output.is_some();
+            let best = pto::optimize(&tax_config, &record, &options)?;
+            if let Some(path) = &sweep_output {
+                let mut csv = String::from("movement,salary_tax,bonus_tax,total\n");
+                for p in &best.sweep {
+                    csv += &format!(
+                        "{:.2},{:.2},{:.2},{:.2}\n",
+                        p.movement, p.salary_tax, p.bonus_tax, p.total
+                    );
+                }
+                tokio::fs::write(path, csv)
+                    .await
+                    .with_context(|| format!("cannot write {}", path.display()))?;
+            }
+            #[cfg(feature = "plot")]
+            if let Some(path) = &plot {
+                let mut curve = pto::tax_curve(&tax_config, &record, &options, 400)?;
+                let optimum = pto::SweepPoint::new(best.movement, &best.after);
+                // The optimum may sit in a narrow dip between the points of the curve.
+                let i = curve.partition_point(|p| p.movement < optimum.movement);
+                curve.insert(i, optimum.clone());
+                let thresholds = pto::thresholds(&tax_config, &record, &options);
+                pto::plot(path, &curve, &optimum, &thresholds)?;
+            }
+            let tradeoff = search
+                .smoothness
+                .map(|_| pto::tradeoff(&tax_config, &record, &options))
+                .transpose()?;
+            // Stepped by 1000, or the multiple of the granularity closest above.
+            let step = options
+                .granularity
+                .map_or(1000.0, |g| (1000.0 / g).ceil() * g);
+            let sensitivity = sensitivity
+                .then(|| pto::sensitivity(&tax_config, &record, &best, &options, step))
+                .transpose()?;
+            print_optimization(
+                &tax_config,
+                &record,
+                &best,
+                tradeoff.as_deref(),
+                sensitivity.as_ref(),
+                Details {
+                    explain,
+                    employer_view,
+                },
+                json,
+            )?;
+            if chart && !json {
+                let curve = pto::tax_curve(&tax_config, &record, &options, 60)?;
+                let optimum = pto::SweepPoint::new(best.movement, &best.after);
+                print!("{}", pto::chart(&curve, &optimum, 10));
+            }
+        }
+        Command::Evaluate {
+            record: RecordArg { record },
+            movement,
+            explain,
+            employer_view,
+        } => {
+            let result = pto::evaluate(&tax_config, &record, movement)?;
+            let details = Details {
+                explain,
+                employer_view,
+            };
+            print_optimization(&tax_config, &record, &result, None, None, details, json)?;
+        }
+        Command::Pension {
+            record: RecordArg { record },
+        } => {
+            let advice = pto::advise_pension(&tax_config, &record)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": advice }));
+            } else {
+                println!("Current: {}", advice.current);
+                println!("Recommended: {}", advice.recommended);
+            }
+        }
+        Command::Annuity {
+            record: RecordArg { record },
+        } => {
+            let annuity = pto::annuity(&tax_config, &record)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": annuity }));
+            } else {
+                println!("Joining: {annuity}");
+            }
+        }
+        Command::HousingFund {
+            record: RecordArg { record },
+            search,
+        } => {
+            let ratios = pto::housing_fund(&tax_config, &record, &search.options()?)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": ratios }));
+            } else {
+                println!(
+                    "{:>6} {:>12} {:>12} {:>12} {:>16}",
+                    "ratio", "employee", "employer", "tax saved", "saved per yuan"
+                );
+                for r in &ratios {
+                    println!(
+                        "{:>5}% {:>12.2} {:>12.2} {:>12.2} {:>16.4}",
+                        r.ratio * 100.0,
+                        r.employee,
+                        r.employer,
+                        r.saving,
+                        r.saving_per_yuan
+                    );
+                }
+                println!(
+                    "Both contributions are locked in the fund until withdrawn for housing, e.g. \
+                     to buy a home or pay rent."
+                );
+            }
+        }
+        Command::Donate {
+            record: RecordArg { record },
+            target,
+        } => {
+            let donation = pto::advise_donation(&tax_config, &record, target)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": donation }));
+            } else {
+                println!("Donation: {donation}");
+            }
+        }
+        Command::ComparePolicy {
+            record: RecordArg { record },
+        } => {
+            let cmp = pto::compare_policies(&tax_config, &record)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": cmp }));
+            } else {
+                println!("Separate: {}", cmp.separate);
+                println!("Merged: {}", cmp.merged);
+                println!(
+                    "Recommended: {} (saves {:.2})",
+                    cmp.recommended,
+                    cmp.saving()
+                );
+            }
+        }
+        Command::CompareStatus {
+            record: RecordArg { record },
+        } => {
+            let configs: Vec<_> = pto::filing_statuses(&overrides)?
+                .into_iter()
+                .map(|status| {
+                    let mut overrides = overrides.clone();
+                    overrides.insert("filing_status".to_string(), status.into());
+                    load(overrides)
+                })
+                .collect::<Result<_>>()?;
+            let cmp = pto::compare_statuses(&configs, &record)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": cmp }));
+            } else {
+                for (status, tax) in &cmp.taxes {
+                    println!("{status}: {tax}");
+                }
+                println!(
+                    "Recommended: {} (saves {:.2})",
+                    cmp.recommended,
+                    cmp.saving()
+                );
+            }
+        }
+        Command::Schedule {
+            record: RecordArg { record },
+            bonus_month,
+            allocate,
+            solver,
+            search,
+        } => {
+            let bonus_month = bonus_month.unwrap_or(tax_config.end_month);
+            if let Some(policy) = search.policy()? {
+                policy.check_bonus_month(bonus_month)?;
+            }
+            let options = search.options()?;
+            let (best, allocation) = match solver {
+                Solver::Search => {
+                    let best = pto::optimize(&tax_config, &record, &options)?;
+                    let allocation = allocate
+                        .then(|| pto::allocate(&tax_config, &best.record, bonus_month, &options))
+                        .transpose()?;
+                    (best, allocation)
+                }
+                #[cfg(feature = "milp")]
+                Solver::Milp => {
+                    let (best, allocation) =
+                        pto::solve_milp(&tax_config, &record, bonus_month, &options)?;
+                    (best, Some(allocation))
+                }
+                #[cfg(not(feature = "milp"))]
+                Solver::Milp => {
+                    anyhow::bail!(
+                        "pto is built without the milp solver, build it with `--features milp`"
+                    )
+                }
+            };
+            let before = pto::schedule(&tax_config, &record, bonus_month)?;
+            let after = match &allocation {
+                Some(a) => a.schedule.clone(),
+                None => pto::schedule(&tax_config, &best.record, bonus_month)?,
+            };
+            if json {
+                let mut out = json!({
+                    "record": record,
+                    "movement": best.movement,
+                    "before": before,
+                    "after": after,
+                });
+                if let Some(a) = &allocation {
+                    out["allocation"] = json!({
+                        "months": a.months,
+                        "advance": a.advance,
+                        "even_advance": a.even_advance,
+                    });
+                }
+                println!("{out}");
+            } else {
+                println!("Before:");
+                print_schedule(&before);
+                println!("After (movement: {}):", best.movement);
+                print_schedule(&after);
+                if let Some(a) = &allocation {
+                    println!(
+                        "Tax withheld ahead of time, summed over the months: {:.2} (spread \
+                         evenly: {:.2})",
+                        a.advance, a.even_advance
+                    );
+                }
+            }
+        }
+        Command::Reconcile {
+            record: RecordArg { record },
+            withheld,
+            bonus_withheld,
+            remuneration_withheld,
+            second_employer,
+            second_withheld,
+        } => {
+            anyhow::ensure!(withheld.len() <= 12, "at most 12 monthly withholdings");
+            let months = pto::schedule(&tax_config, &record, tax_config.end_month)?;
+            let salary_withheld = if withheld.is_empty() {
+                months.iter().map(|m| m.withheld).sum()
+            } else {
+                withheld.iter().sum::<f64>()
+            };
+            let bonus_withheld =
+                bonus_withheld.unwrap_or_else(|| months.iter().map(|m| m.bonus_tax).sum());
+            let remuneration_withheld = match remuneration_withheld {
+                Some(w) => w,
+                None => tax_config.remuneration_withholding(&record)?,
+            };
+            let withheld = salary_withheld + bonus_withheld + remuneration_withheld;
+            let Some(second) = second_employer else {
+                let result = pto::reconcile(&tax_config, &record, withheld)?;
+                if json {
+                    println!("{}", json!({ "record": record, "result": result }));
+                } else {
+                    println!("{result}");
+                }
+                return Ok(());
+            };
+            let second_withheld = match second_withheld {
+                Some(w) => w,
+                None => pto::schedule(&tax_config, &second, tax_config.end_month)?
+                    .iter()
+                    .map(|m| m.withheld + m.bonus_tax)
+                    .sum(),
+            };
+            let combined = pto::combine(&tax_config, &record, &second);
+            let result = pto::reconcile(&tax_config, &combined, withheld + second_withheld)?;
+            if json {
+                let out = json!({
+                    "record": record,
+                    "second_employer": second,
+                    "combined": combined,
+                    "withheld": [withheld, second_withheld],
+                    "result": result,
+                });
+                println!("{out}");
+            } else {
+                println!("Combined: {combined}");
+                println!("Withheld by the first employer: {withheld:.2}");
+                println!("Withheld by the second employer: {second_withheld:.2}");
+                println!("{result}");
+            }
+        }
+        Command::Brackets => {
+            if json {
+                println!("{}", serde_json::to_string(&tax_config)?);
+            } else {
+                print!("{tax_config}");
+            }
+        }
+        Command::Init { .. }
+        | Command::UpdateTables { .. }
+        | Command::Config {
+            command: ConfigCommand::Schema | ConfigCommand::Migrate,
+        } => unreachable!("handled before loading the config"),
+        Command::Config {
+            command: ConfigCommand::Lint,
+        } => {
+            let warnings = tax_config.lint();
+            if json {
+                println!("{}", json!({ "warnings": warnings, "config": tax_config }));
+            } else {
+                for w in &warnings {
+                    println!("Warning: {w}");
+                }
+                if warnings.is_empty() {
+                    println!("No problems found.");
+                }
+                print!("{tax_config}");
+            }
+        }
+        Command::Compare {
+            record,
+            offer,
+            search,
+        } => {
+            let options = search.options()?;
+            let mut rows: Vec<_> = record
+                .into_iter()
+                .enumerate()
+                .map(|(i, r)| {
+                    Ok((
+                        (i + 1).to_string(),
+                        r.clone(),
+                        pto::optimize(&tax_config, &r, &options)?,
+                    ))
+                })
+                .collect::<Result<_>>()?;
+            for path in &offer {
+                let text = tokio::fs::read_to_string(path)
+                    .await
+                    .with_context(|| format!("cannot read {}", path.display()))?;
+                let name = path.file_stem().unwrap_or_default().to_string_lossy();
+                let offer = pto::Offer::parse(&text, &name)
+                    .with_context(|| format!("in {}", path.display()))?;
+                let mut overrides = offer.overrides(&dated);
+                pto::select_date(&mut overrides, date)?;
+                let config = load(overrides).with_context(|| format!("in {}", path.display()))?;
+                let best = pto::optimize(&config, &offer.record, &options)?;
+                rows.push((offer.name, offer.record, best));
+            }
+            print_results(&rows, json);
+            if let (false, [first, rest @ ..]) = (json, rows.as_slice()) {
+                if !rest.is_empty() {
+                    println!("Against {}:", first.0);
+                }
+                for (name, _, best) in rest {
+                    println!(
+                        "  {name}: tax {:+.2}, net {:+.2}",
+                        best.after.total() - first.2.after.total(),
+                        best.after.net - first.2.after.net
+                    );
+                }
+            }
+        }
+        Command::Household {
+            record,
+            shared,
+            search,
+        } => {
+            anyhow::ensure!(
+                record.len() == 2,
+                "expected 2 records, got {}",
+                record.len()
+            );
+            let best = pto::optimize_household(
+                &tax_config,
+                [&record[0], &record[1]],
+                &shared.unwrap_or_default(),
+                &search.options()?,
+            )?;
+            if json {
+                println!("{}", json!({ "result": best }));
+            } else {
+                println!("Before: {}", best.before);
+                println!("After: {}", best.after);
+                println!("Saving: {:.2}", best.before.total() - best.after.total());
+            }
+        }
+        Command::Batch { file, search } => {
+            let options = search.options()?;
+            let records = pto::parse_records(&tokio::fs::read_to_string(file).await?)?;
+            let rows: Vec<_> = records
+                .into_iter()
+                .map(|(name, r)| Ok((name, r.clone(), pto::optimize(&tax_config, &r, &options)?)))
+                .collect::<Result<_>>()?;
+            print_results(&rows, json);
+        }
+        Command::Sweep {
+            salary,
+            bonus,
+            record,
+            search,
+        } => {
+            let options = search.options()?;
+            let record = record.unwrap_or_else(|| Record::new(0.0, 0.0, 0.0));
+            let points = pto::grid(&tax_config, &record, &salary, &bonus, &options)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": points }));
+            } else {
+                print_grid(&points, bonus.values().len());
+            }
+        }
+        Command::Solve {
+            target_net,
+            record,
+            search,
+        } => {
+            let options = search.options()?;
+            let record = record.unwrap_or_else(|| Record::new(1.0, 0.0, 0.0));
+            let gross = pto::solve_gross(&tax_config, &record, target_net, &options)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": gross }));
+            } else {
+                println!(
+                    "Gross package: {:.2} (monthly salary: {:.2}, bonuses: {:.2})",
+                    gross.package,
+                    gross.record.monthly_salary,
+                    gross.record.year_bonus + gross.record.other_bonus()
+                );
+                print_optimization(
+                    &tax_config,
+                    &gross.record,
+                    &gross.best,
+                    None,
+                    None,
+                    Details {
+                        explain: false,
+                        employer_view: false,
+                    },
+                    false,
+                )?;
+            }
+        }
+        Command::Marginal {
+            record: RecordArg { record },
+            delta,
+            search,
+        } => {
+            anyhow::ensure!(delta != 0.0, "--delta must not be zero");
+            let marginal = pto::marginal(&tax_config, &record, delta, &search.options()?)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": marginal }));
+            } else {
+                println!("Kept of {delta} more:");
+                println!("{:<12} {:>20} {:>20}", "", "as given", "best movement");
+                for (name, kept) in [
+                    ("salary", &marginal.salary),
+                    ("year bonus", &marginal.bonus),
+                ] {
+                    println!(
+                        "{name:<12} {:>12.2} ({:>5.1}%) {:>12.2} ({:>5.1}%)",
+                        kept.before,
+                        kept.before / delta * 100.0,
+                        kept.after,
+                        kept.after / delta * 100.0
+                    );
+                }
+            }
+        }
+        Command::Split {
+            budget,
+            step,
+            record,
+        } => {
+            let record = record.unwrap_or_else(|| Record::new(0.0, 0.0, 0.0));
+            let split = pto::split(&tax_config, &record, budget, step)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": split }));
+            } else {
+                // The break-even points are where paying more as salary stops or starts helping.
+                println!(
+                    "{:<12} {:>14} {:>14} {:>12} {:>14}",
+                    "", "monthly salary", "year bonus", "tax", "net"
+                );
+                let best = std::iter::once(("best", &split.best));
+                for (label, s) in best.chain(split.breakeven.iter().map(|s| ("break-even", s))) {
+                    println!(
+                        "{label:<12} {:>14.2} {:>14.2} {:>12.2} {:>14.2}",
+                        s.monthly_salary, s.year_bonus, s.tax, s.net
+                    );
+                }
+            }
+        }
+        Command::Benefits {
+            record: RecordArg { record },
+            menu,
+            search,
+        } => {
+            let text = tokio::fs::read_to_string(&menu)
+                .await
+                .with_context(|| format!("cannot read {}", menu.display()))?;
+            let benefits =
+                pto::parse_benefits(&text).with_context(|| format!("in {}", menu.display()))?;
+            let elections = pto::elect(&tax_config, &record, &benefits, &search.options()?)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": elections }));
+            } else {
+                for (label, e) in [
+                    ("Elect nothing", &elections.none),
+                    ("Best", &elections.best),
+                ] {
+                    let chosen = match e.benefits.is_empty() {
+                        true => "nothing".to_string(),
+                        false => e.benefits.join(", "),
+                    };
+                    println!(
+                        "{label}: {chosen} (movement: {}, tax: {:.2}, net value: {:.2})",
+                        e.movement, e.tax, e.net_value
+                    );
+                }
+                println!(
+                    "Gain: {:.2}",
+                    elections.best.net_value - elections.none.net_value
+                );
+            }
+        }
+        Command::Rsu {
+            record: RecordArg { record },
+            vest,
+            price,
+        } => {
+            let plan = pto::rsu(&tax_config, &record, &vest, price)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": plan }));
+            } else {
+                println!(
+                    "{:<10} {:>10} {:>14} {:>12} {:>12} {:>12} {:>10}",
+                    "date", "shares", "income", "tax", "shares sold", "shares kept", "cash"
+                );
+                for e in &plan.events {
+                    println!(
+                        "{:<10} {:>10} {:>14.2} {:>12.2} {:>12} {:>12} {:>10.2}",
+                        e.date, e.shares, e.income, e.tax, e.shares_sold, e.shares_kept, e.cash
+                    );
+                }
+                println!("Record: {}", plan.record);
+                println!("Tax: {}", plan.tax);
+            }
+        }
+        Command::Draw {
+            record: RecordArg { record },
+            step,
+        } => {
+            let analysis = pto::draw(&tax_config, &record, step)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": analysis }));
+            } else {
+                println!(
+                    "{:<12} {:>14} {:>16} {:>12} {:>14}",
+                    "", "monthly salary", "business income", "tax", "net"
+                );
+                for (label, d) in [("no salary", &analysis.before), ("best", &analysis.after)] {
+                    println!(
+                        "{label:<12} {:>14.2} {:>16.2} {:>12.2} {:>14.2}",
+                        d.monthly_salary, d.business_income, d.tax, d.net
+                    );
+                }
+            }
+        }
+        Command::Defer {
+            record: RecordArg { record },
+            next,
+            deferral_step,
+            search,
+        } => {
+            let mut overrides = dated.clone();
+            pto::select_date(&mut overrides, pto::Date::year_end(year + 1))?;
+            let next_config = load(overrides)?;
+            let plan = pto::defer_bonus(
+                &tax_config,
+                &next_config,
+                &record,
+                &next,
+                &search.options()?,
+                deferral_step,
+            )?;
+            if json {
+                println!(
+                    "{}",
+                    json!({ "record": record, "next": next, "result": plan })
+                );
+            } else {
+                for (title, d) in [("Before", &plan.before), ("After", &plan.after)] {
+                    match d.npv {
+                        Some(npv) => println!(
+                            "{title}: {:.2} (deferred: {}, net present value: {npv:.2})",
+                            d.total(),
+                            d.deferred
+                        ),
+                        None => println!("{title}: {:.2} (deferred: {})", d.total(), d.deferred),
+                    }
+                    println!(
+                        "  this year: {:.2} (movement: {})",
+                        d.this_year.after.total(),
+                        d.this_year.movement
+                    );
+                    println!(
+                        "  next year: {:.2} (movement: {})",
+                        d.next_year.after.total(),
+                        d.next_year.movement
+                    );
+                }
+                println!("Saving: {:.2}", plan.before.total() - plan.after.total());
+            }
+        }
+        Command::Project {
+            record: RecordArg { record },
+            years,
+            raise,
+            indexation,
+            search,
+        } => {
+            let projection = pto::project(
+                &tax_config,
+                &record,
+                year,
+                years,
+                raise,
+                indexation,
+                &search.options()?,
+            )?;
+            if json {
+                println!("{}", json!({ "record": record, "result": projection }));
+            } else {
+                println!(
+                    "{:<6} {:>14} {:>12} {:>12} {:>12} {:>12}",
+                    "year", "monthly salary", "year bonus", "movement", "tax", "net"
+                );
+                for y in &projection {
+                    println!(
+                        "{:<6} {:>14.2} {:>12.2} {:>12.2} {:>12.2} {:>12.2}",
+                        y.year, y.monthly_salary, y.year_bonus, y.movement, y.tax, y.net
+                    );
+                }
+            }
+        }
+        Command::Simulate {
+            record: RecordArg { record },
+            bonus,
+            draws,
+            seed,
+            search,
+        } => {
+            let options = search.options()?;
+            let sim = pto::simulate(&tax_config, &record, &bonus, draws, seed, &options)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": sim }));
+            } else {
+                println!(
+                    "Draws: {} (mean year bonus: {:.2})",
+                    sim.draws, sim.mean_bonus
+                );
+                println!(
+                    "Expected tax with the best movement of each bonus: {:.2}",
+                    sim.expected_tax
+                );
+                let [min, p10, p50, p90, max] = sim.movement_percentiles;
+                println!(
+                    "Best movements: {min:.2} to {max:.2} (10th percentile: {p10:.2}, median: \
+                     {p50:.2}, 90th percentile: {p90:.2})"
+                );
+                for (title, f) in [
+                    ("Without movement", &sim.no_movement),
+                    ("Robust movement", &sim.robust),
+                ] {
+                    println!(
+                        "{title}: {:.2} (expected tax: {:.2}, most regret: {:.2})",
+                        f.movement, f.expected_tax, f.max_regret
+                    );
+                }
+            }
+        }
+        Command::GrossUp {
+            net_salary,
+            net_bonus,
+            record,
+        } => {
+            let record = record.unwrap_or_else(|| Record::new(0.0, 0.0, 0.0));
+            let gross = pto::gross_up(&tax_config, &record, net_salary, net_bonus)?;
+            if json {
+                println!("{}", json!({ "record": record, "result": gross }));
+            } else {
+                println!(
+                    "Gross monthly salary: {:.2}\nGross year bonus: {:.2}\nTax: {}",
+                    gross.record.monthly_salary, gross.record.year_bonus, gross.tax
+                );
+            }
+        }
+    }
     Ok(())
 }
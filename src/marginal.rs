@@ -0,0 +1,56 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{optimize, OptimizeOptions, Record, TaxConfig};
+
+/// What is kept of more pay, see [`marginal`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Marginal {
+    /// The annual amount added.
+    pub delta: f64,
+    /// Kept of the amount added to the salary, spread evenly over the months employed.
+    pub salary: Kept,
+    /// Kept of the amount added to the year bonus.
+    pub bonus: Kept,
+}
+
+/// The net income kept of an amount added to the pay.
+#[derive(Clone, Debug, Serialize)]
+pub struct Kept {
+    /// Kept with the record as given.
+    pub before: f64,
+    /// Kept with the best movement, searched again with the amount added.
+    pub after: f64,
+}
+
+/// How much of `delta` more annual salary, or year bonus, `record` keeps after tax and social
+/// insurance, as given and under the best movement of `options`.
+pub fn marginal(
+    config: &TaxConfig,
+    record: &Record,
+    delta: f64,
+    options: &OptimizeOptions,
+) -> Result<Marginal> {
+    let before = config.calc(record)?.net;
+    let after = optimize(config, record, options)?.after.net;
+    let kept = |r: &Record| -> Result<Kept> {
+        Ok(Kept {
+            before: config.calc(r)?.net - before,
+            after: optimize(config, r, options)?.after.net - after,
+        })
+    };
+    let mut salary = record.clone();
+    salary.monthly_salary += delta / config.months_employed();
+    if let Some(salaries) = &mut salary.salaries {
+        for s in salaries {
+            *s += delta / config.months_employed();
+        }
+    }
+    let mut bonus = record.clone();
+    bonus.year_bonus += delta;
+    Ok(Marginal {
+        delta,
+        salary: kept(&salary)?,
+        bonus: kept(&bonus)?,
+    })
+}
@@ -0,0 +1,288 @@
+use anyhow::{anyhow, bail, Result};
+use good_lp::{
+    constraint, microlp, variable, Expression, ProblemVariables, Solution, SolverModel, Variable,
+};
+
+use crate::config::Mode;
+use crate::schedule::{advance, monthly_deduction, schedule};
+use crate::{evaluate, schedule_allocated, Allocation, Objective, Optimization, OptimizeOptions};
+use crate::{Record, Table, TaxConfig};
+
+/// A bracket of a table on which its tax is `slope * amount + intercept`.
+struct Piece {
+    lower: f64,
+    upper: Option<f64>,
+    slope: f64,
+    intercept: f64,
+}
+
+impl Piece {
+    fn at(&self, amount: f64) -> f64 {
+        self.slope * amount + self.intercept
+    }
+}
+
+/// The linear pieces of the tax `f` of `table`, on its brackets with the bounds multiplied by
+/// `scale`, checked to be linear on each bracket.
+fn pieces(
+    name: &str,
+    table: &Table,
+    scale: f64,
+    f: impl Fn(f64) -> Result<f64>,
+) -> Result<Vec<Piece>> {
+    if table.mode == Mode::Formula {
+        bail!("{name}: the milp solver needs a marginal or quick_deduction table, not a formula");
+    }
+    let bounds: Vec<f64> = table.bounds().map(|b| b * scale).collect();
+    let uppers = bounds.iter().map(|b| Some(*b));
+    let uppers: Vec<_> = match table.top {
+        Some(_) => uppers.chain([None]).collect(),
+        None => uppers.collect(),
+    };
+    let mut ret = Vec::with_capacity(uppers.len());
+    let mut lower = 0.0;
+    for upper in uppers {
+        // Sampled inside the bracket, as the tax of a bonus jumps right above a bound.
+        let width = upper.map_or(1000.0, |u| u - lower);
+        let (p, q) = (lower + width / 4.0, lower + width * 3.0 / 4.0);
+        let slope = (f(q)? - f(p)?) / (q - p);
+        let piece = Piece {
+            lower,
+            upper,
+            slope,
+            intercept: f(p)? - slope * p,
+        };
+        if let Some(upper) = upper {
+            anyhow::ensure!(
+                (piece.at(upper) - f(upper)?).abs() < 0.01,
+                "{name}: the tax is not linear between {lower} and {upper}"
+            );
+            lower = upper;
+        }
+        ret.push(piece);
+    }
+    Ok(ret)
+}
+
+/// The problem of [`solve_milp`]: the movement paid in each month, the taxable income of each
+/// month before the movement, and the tables as pieces.
+struct Model<'a> {
+    record: &'a Record,
+    /// The movement of each month is a multiple of this, or any amount if not given.
+    granularity: Option<f64>,
+    /// The most movement of each month.
+    caps: Vec<f64>,
+    max_movement: f64,
+    base: Vec<f64>,
+    salary: Vec<Piece>,
+    /// The highest taxable income the salary table covers, if it has no open-ended bracket.
+    highest: Option<f64>,
+    bonus: Vec<Piece>,
+}
+
+impl Model<'_> {
+    /// Solve for the movement of each month. Without `movement`, the movement with the least
+    /// annual tax is sought; with it, the allocation of that movement with the least advance.
+    /// Returns the movements of the months.
+    fn solve(&self, movement: Option<f64>) -> Result<Vec<f64>> {
+        let mut vars = ProblemVariables::new();
+        // Each month as a variable and its multiple, the granularity or one.
+        let months: Vec<(Variable, f64)> = self
+            .caps
+            .iter()
+            .map(|cap| match self.granularity {
+                Some(g) => (
+                    vars.add(variable().integer().min(0).max((cap / g + 1e-9).floor())),
+                    g,
+                ),
+                None => (vars.add(variable().min(0).max(*cap)), 1.0),
+            })
+            .collect();
+        // The tax due on the cumulative taxable income of each month.
+        let due: Vec<Variable> = (0..12).map(|_| vars.add(variable().min(0))).collect();
+        let mut constraints = Vec::new();
+        let moved: Expression = months.iter().map(|(v, g)| *g * *v).sum();
+        let mut paid = Expression::from(0.0);
+        for (m, (v, g)) in months.iter().enumerate() {
+            paid += *g * *v;
+            let taxable = paid.clone() + self.base[m];
+            for piece in &self.salary {
+                constraints.push(constraint!(
+                    due[m] >= piece.slope * taxable.clone() + piece.intercept
+                ));
+            }
+            if let Some(highest) = self.highest {
+                constraints.push(constraint!(taxable <= highest));
+            }
+        }
+        let objective = match movement {
+            Some(movement) => {
+                constraints.push(constraint!(moved == self.record.movement + movement));
+                due.iter().sum()
+            }
+            None => {
+                let movement = moved - self.record.movement;
+                constraints.push(constraint!(movement.clone() >= 0));
+                constraints.push(constraint!(movement.clone() <= self.max_movement));
+                // The year bonus falls in exactly one bracket, chosen by a binary of each, as
+                // its tax jumps at the bounds.
+                let bonus: Vec<(Variable, Variable)> = self
+                    .bonus
+                    .iter()
+                    .map(|_| (vars.add(variable().binary()), vars.add(variable().min(0))))
+                    .collect();
+                let chosen: Expression = bonus.iter().map(|(z, _)| *z).sum();
+                constraints.push(constraint!(chosen == 1));
+                let left: Expression = bonus.iter().map(|(_, b)| *b).sum();
+                constraints.push(constraint!(
+                    left == self.record.year_bonus - movement.clone()
+                ));
+                let mut bonus_tax = Expression::from(0.0);
+                for (piece, (z, b)) in self.bonus.iter().zip(&bonus) {
+                    // Strictly above the lower bound, to the cent.
+                    let lower = if piece.lower > 0.0 {
+                        piece.lower + 0.01
+                    } else {
+                        0.0
+                    };
+                    let upper = piece.upper.unwrap_or(self.record.year_bonus);
+                    constraints.push(constraint!(*b >= lower * *z));
+                    constraints.push(constraint!(*b <= upper * *z));
+                    bonus_tax += piece.slope * *b + piece.intercept * *z;
+                }
+                // Of movements with the same tax, the smallest, as `optimize` keeps, by a weight
+                // that adds less than a cent.
+                bonus_tax + due[11] + 1e-9 * movement
+            }
+        };
+        let solution = vars
+            .minimise(objective)
+            .using(microlp)
+            .with_all(constraints)
+            .solve()
+            .map_err(|e| anyhow!("the milp solver failed: {e}"))?;
+        Ok(months
+            .iter()
+            .map(|(v, g)| match self.granularity {
+                Some(_) => solution.value(*v).round() * g,
+                None => solution.value(*v),
+            })
+            .collect())
+    }
+}
+
+/// Search for the movement of the year bonus into salary and its allocation to the months of the
+/// year exactly, as mixed integer programs: the movement of the least annual tax, then its
+/// allocation that withholds the least tax ahead of time like [`allocate`].
+///
+/// The salary tax must be convex, so that it is the largest of its linear pieces, while the year
+/// bonus may jump at its bounds, which binary variables choose between. The movement of each
+/// month is a multiple of the granularity of `options`, or rounded to its precision, and at most
+/// `max_monthly_salary` with the salary of the month. Only bonus moved into salary is tried, and
+/// the record's year bonus is the one taxed separately.
+///
+/// [`allocate`]: crate::allocate
+pub fn solve_milp(
+    config: &TaxConfig,
+    record: &Record,
+    bonus_month: u32,
+    options: &OptimizeOptions,
+) -> Result<(Optimization, Allocation)> {
+    anyhow::ensure!(
+        options.objective == Objective::MinTax,
+        "the milp solver only minimizes the tax"
+    );
+    let (deduction, _) = monthly_deduction(config, record, bonus_month)?;
+    anyhow::ensure!(
+        config.months_employed() == 12.0,
+        "the milp solver needs a whole year employed"
+    );
+    anyhow::ensure!(
+        record.movement >= 0.0,
+        "only a movement of year bonus into salary can be allocated"
+    );
+    let precision = options.granularity.unwrap_or(options.precision);
+    anyhow::ensure!(
+        precision > 0.0,
+        "granularity and precision must be positive"
+    );
+    let mut max_movement = record.year_bonus - options.min_bonus;
+    if let Some(m) = options.max_movement {
+        max_movement = max_movement.min(m);
+    }
+    let max_movement = max_movement.max(0.0);
+    let total = record.movement + max_movement;
+    if let Some(g) = options.granularity {
+        anyhow::ensure!(
+            total / g <= i32::MAX as f64,
+            "the movement has too many steps of {g}, give a larger granularity"
+        );
+    }
+    let caps = (1..=12)
+        .map(|month| match options.max_monthly_salary {
+            Some(cap) => 0f64.max(cap - record.salary_of(month)).min(total),
+            None => total,
+        })
+        .collect();
+    let mut income = 0.0;
+    let base = (1..=12u32)
+        .map(|month| {
+            income += record.salary_of(month);
+            if month == bonus_month {
+                income += record.other_bonus();
+            }
+            income - deduction * month as f64
+        })
+        .collect();
+    let salary = pieces("salary", &config.salary, 1.0, |x| config.salary.tax(x))?;
+    for pair in salary.windows(2) {
+        anyhow::ensure!(
+            pair[0].slope <= pair[1].slope,
+            "salary: the milp solver needs a table whose ratios only rise"
+        );
+    }
+    let bonus = pieces("year_bonus", &config.year_bonus, 12.0, |x| {
+        config.year_bonus.bonus_tax(x)
+    })?
+    .into_iter()
+    .filter(|p| p.lower == 0.0 || p.lower < record.year_bonus)
+    .collect();
+    let model = Model {
+        record,
+        granularity: options.granularity,
+        caps,
+        max_movement,
+        base,
+        highest: config
+            .salary
+            .top
+            .is_none()
+            .then(|| config.salary.bounds().last().unwrap_or(0.0)),
+        salary,
+        bonus,
+    };
+    let round = |x: f64| (x / precision).round() * precision;
+    // Rounding to the nearest step keeps the year bonus in the bracket the solver chose, as its
+    // bounds are whole amounts.
+    let movement = round(model.solve(None)?.iter().sum::<f64>() - record.movement);
+    let mut months = model.solve(Some(movement))?;
+    // The largest month takes what rounding the others leaves over.
+    for m in &mut months {
+        *m = round(*m);
+    }
+    let left = record.movement + movement - months.iter().sum::<f64>();
+    if let Some(m) = months.iter_mut().max_by(|a, b| a.total_cmp(b)) {
+        *m += left;
+    }
+    let best = evaluate(config, record, movement)?;
+    let allocation: [f64; 12] = months.clone().try_into().unwrap();
+    let even = schedule(config, &best.record, bonus_month)?;
+    let schedule = schedule_allocated(config, &best.record, bonus_month, &allocation)?;
+    let allocation = Allocation {
+        months,
+        advance: advance(&schedule),
+        even_advance: advance(&even),
+        schedule,
+    };
+    Ok((best, allocation))
+}
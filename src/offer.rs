@@ -0,0 +1,58 @@
+use anyhow::{anyhow, Result};
+
+use crate::config::merge;
+use crate::{city_preset, parse_record, Record};
+
+/// A job offer to compare with others, read from a TOML file such as
+///
+/// ```toml
+/// name = "Shanghai"
+/// record = "salary=40000,deduction=5000,bonus=160000"
+/// city = "shanghai"     # the social insurance preset of the city
+/// region = "cn"         # and any other key of the config, e.g. the tables
+/// ```
+#[derive(Clone, Debug)]
+pub struct Offer {
+    pub name: String,
+    pub record: Record,
+    /// The keys of the config the offer gives, with the tables of its city.
+    pub config: toml::Table,
+}
+
+impl Offer {
+    /// Parse the text of an offer file, named `name` unless it gives its own.
+    pub fn parse(text: &str, name: &str) -> Result<Self> {
+        let mut config: toml::Table = toml::from_str(text)?;
+        let mut string = |key: &str| -> Result<Option<String>> {
+            match config.remove(key) {
+                Some(toml::Value::String(s)) => Ok(Some(s)),
+                Some(_) => Err(anyhow!("{key} is not a string")),
+                None => Ok(None),
+            }
+        };
+        let name = string("name")?.unwrap_or_else(|| name.to_string());
+        let record = string("record")?.ok_or_else(|| anyhow!("missing key `record`"))?;
+        let city = string("city")?;
+        let record = parse_record(&record)?;
+        if let Some(city) = city {
+            merge(&mut config, city_preset(&city)?);
+        }
+        Ok(Self {
+            name,
+            record,
+            config,
+        })
+    }
+
+    /// The config overrides of the offer on top of `base`, those of `--config`. An offer in
+    /// another region starts from the built-in tables of it instead, as the tables of `base` are
+    /// for its own region.
+    pub fn overrides(&self, base: &toml::Table) -> toml::Table {
+        let mut ret = match self.config.get("region") {
+            Some(region) if base.get("region") != Some(region) => toml::Table::new(),
+            _ => base.clone(),
+        };
+        merge(&mut ret, self.config.clone());
+        ret
+    }
+}
@@ -0,0 +1,616 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{schedule, Record, ResidentStatus, Tax, TaxConfig};
+
+/// The result of [`optimize`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Optimization {
+    /// Tax of the record as given.
+    pub before: Tax,
+    /// Tax under the best movement found.
+    pub after: Tax,
+    /// The amount moved from the year bonus into salary, negative if salary is moved into the
+    /// bonus.
+    pub movement: f64,
+    /// The record under the best movement. Of several bonuses, its year bonus is the one that is
+    /// best taxed separately.
+    pub record: Record,
+    /// The constraints of [`OptimizeOptions`] that keep the movement from a better one, by the
+    /// name of their field, e.g. `min_bonus`, or `min_monthly_net` for the floor of the objective.
+    pub binding: Vec<String>,
+    /// Every movement of the record as given that the search evaluated, by movement, if
+    /// [`OptimizeOptions::record_sweep`] is set.
+    pub sweep: Vec<SweepPoint>,
+}
+
+/// A movement evaluated by [`optimize`] and its tax.
+#[derive(Clone, Debug, Serialize)]
+pub struct SweepPoint {
+    pub movement: f64,
+    pub salary_tax: f64,
+    pub bonus_tax: f64,
+    pub total: f64,
+    /// The annual net income, see [`Tax::net`].
+    pub net: f64,
+}
+
+impl SweepPoint {
+    pub fn new(movement: f64, tax: &Tax) -> Self {
+        Self {
+            movement,
+            salary_tax: tax.salary,
+            bonus_tax: tax.year_bonus,
+            total: tax.total(),
+            net: tax.net,
+        }
+    }
+}
+
+/// A movement at which the salary income or the monthly average of the year bonus crosses a
+/// bracket bound, see [`thresholds`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Threshold {
+    pub movement: f64,
+    /// The bound crossed, e.g. `salary 420000`.
+    pub label: String,
+}
+
+/// What [`optimize`] counts as the best movement.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Objective {
+    /// The least total tax.
+    #[default]
+    MinTax,
+    /// The most annual net income, after tax, social insurance and the tax paid abroad.
+    MaxNet,
+    /// The least total tax of the movements that leave at least this much net pay in every month.
+    MinTaxWithMonthlyNet(f64),
+    /// The least total tax plus this weight times the [`net_spread`], trading tax for steadier
+    /// paychecks.
+    Smooth(f64),
+    /// The most [`npv`] at this annual discount rate, e.g. 0.05, valuing the net pay that arrives
+    /// earlier.
+    MaxNpv(f64),
+}
+
+/// A movement on the trade-off between tax and paycheck stability, see [`tradeoff`].
+#[derive(Clone, Debug, Serialize)]
+pub struct TradeOff {
+    pub movement: f64,
+    /// The total tax.
+    pub tax: f64,
+    /// The [`net_spread`].
+    pub spread: f64,
+}
+
+/// How [`optimize`] searches for the best movement.
+#[derive(Clone, Debug)]
+pub struct OptimizeOptions {
+    /// Sweep the movement in steps of this size instead of enumerating the bracket crossings.
+    pub step: Option<f64>,
+    /// The resolution of the movement. A sweep is refined around its best point until its step
+    /// is no larger than this.
+    pub precision: f64,
+    /// The most salary that may be moved into the year bonus. Zero only moves bonus into salary.
+    pub max_reverse: f64,
+    /// The year bonus that must be kept, e.g. one month's bonus.
+    pub min_bonus: f64,
+    /// The most bonus that may be moved into salary.
+    pub max_movement: Option<f64>,
+    /// The movement must be a multiple of this, e.g. 1000. The precision applies if not given.
+    pub granularity: Option<f64>,
+    /// The most average monthly salary, including the movement, the payroll pays.
+    pub max_monthly_salary: Option<f64>,
+    pub objective: Objective,
+    /// Keep every movement evaluated in [`Optimization::sweep`], to plot the tax against the
+    /// movement.
+    pub record_sweep: bool,
+}
+
+impl Default for OptimizeOptions {
+    fn default() -> Self {
+        Self {
+            step: None,
+            precision: 0.01,
+            max_reverse: 0.0,
+            min_bonus: 0.0,
+            max_movement: None,
+            granularity: None,
+            max_monthly_salary: None,
+            objective: Objective::MinTax,
+            record_sweep: false,
+        }
+    }
+}
+
+/// The lowest net pay of the months of `record`: the net of each month of its withholding
+/// schedule, with the bonuses paid in the last month employed, where the schedule applies, and
+/// the average monthly net otherwise.
+fn lowest_monthly_net(config: &TaxConfig, record: &Record, tax: &Tax) -> f64 {
+    match schedule(config, record, config.end_month) {
+        Ok(months) => months.iter().map(|m| m.net).fold(f64::INFINITY, f64::min),
+        Err(_) => tax.monthly_net,
+    }
+}
+
+/// How lumpy the take-home pay of `record` is: the highest net salary of a month of its
+/// withholding schedule minus the lowest, with the bonuses paid in the last month employed. The
+/// cumulative withholding takes more from the later months, the more so the larger the salary.
+/// Zero where the schedule does not apply, as every month is then paid the same.
+pub fn net_spread(config: &TaxConfig, record: &Record) -> f64 {
+    let Ok(months) = schedule(config, record, config.end_month) else {
+        return 0.0;
+    };
+    let nets = months
+        .iter()
+        .map(|m| m.salary - m.social_insurance - m.withheld);
+    let (min, max) = nets.fold((f64::INFINITY, f64::NEG_INFINITY), |(min, max), n| {
+        (min.min(n), max.max(n))
+    });
+    max - min
+}
+
+/// The net present value of the pay of `record`, taxed `tax`, at the start of the year: the net
+/// pay of each month of its withholding schedule, with the bonuses paid in the last month
+/// employed, discounted at the annual `rate` for the months until it arrives at the end of its
+/// month. Where the schedule does not apply, every month employed is paid the average monthly net
+/// and the rest arrives in the last one.
+pub fn npv(config: &TaxConfig, record: &Record, tax: &Tax, rate: f64) -> f64 {
+    let discount = |month: u32| (1.0 + rate).powf(-(month as f64) / 12.0);
+    match schedule(config, record, config.end_month) {
+        Ok(months) => months.iter().map(|m| m.net * discount(m.month)).sum(),
+        Err(_) => {
+            let salary: f64 = config.months().map(|m| tax.monthly_net * discount(m)).sum();
+            salary + (tax.net - tax.monthly_net * tax.months) * discount(config.end_month)
+        }
+    }
+}
+
+/// The score of `record`, taxed `tax`, under `objective`, lower being better. A movement that
+/// breaks the constraint of the objective scores infinity.
+fn score(config: &TaxConfig, record: &Record, tax: &Tax, objective: Objective) -> f64 {
+    match objective {
+        Objective::MinTax => tax.total(),
+        Objective::MaxNet => -tax.net,
+        Objective::MinTaxWithMonthlyNet(floor) => {
+            if lowest_monthly_net(config, record, tax) >= floor - 0.005 {
+                tax.total()
+            } else {
+                f64::INFINITY
+            }
+        }
+        Objective::Smooth(weight) => tax.total() + weight * net_spread(config, record),
+        Objective::MaxNpv(rate) => -npv(config, record, tax, rate),
+    }
+}
+
+/// The movements at which the tax may reach its minimum. Both the salary tax and the year bonus
+/// tax are linear in the movement as long as neither the salary income nor the monthly average of
+/// the bonus crosses a bracket bound, so the minimum is at one of these crossings or at an end of
+/// the range, or, under a floor of the monthly net pay, where the floor is reached.
+fn candidates(config: &TaxConfig, record: &Record, options: &OptimizeOptions) -> Result<Vec<f64>> {
+    let (min, max) = range(config, record, options);
+    let precision = options.granularity.unwrap_or(options.precision);
+    let crossings = match config.resident_status {
+        ResidentStatus::Resident => {
+            let mut crossings = config.movement_crossings(record);
+            crossings.extend(
+                config
+                    .year_bonus
+                    .bounds()
+                    .map(|b| record.year_bonus - b * 12.0),
+            );
+            crossings
+        }
+        ResidentStatus::NonResident => config
+            .non_resident
+            .crossings(&config.with_taxable_allowances(record), config.months()),
+    };
+    // Zero goes first so that it is kept over a `-0.0` minimum by the dedup.
+    let mut ret = vec![0.0, min, max];
+    // The bonus tax jumps right above a bound, so try the grid points on both sides.
+    for m in crossings {
+        let m = (m / precision).floor() * precision;
+        ret.extend([m, m + precision]);
+    }
+    match options.objective {
+        Objective::MinTaxWithMonthlyNet(floor) => {
+            ret.extend(floor_crossing(config, record, floor, min, max, precision)?);
+        }
+        // The net pay of a month is linear in the movement until its withholding crosses a bound.
+        Objective::Smooth(_) | Objective::MaxNpv(_) => {
+            for m in withholding_crossings(config, record) {
+                let m = (m / precision).floor() * precision;
+                ret.extend([m, m + precision]);
+            }
+        }
+        Objective::MinTax | Objective::MaxNet => {}
+    }
+    ret.retain(|m| (min..=max).contains(m));
+    ret.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    ret.dedup();
+    Ok(ret)
+}
+
+/// The movements, spread evenly over the year, at which the cumulative taxable income of a month
+/// of the withholding schedule crosses a bracket bound, changing how the net salary of the month
+/// moves with the movement.
+fn withholding_crossings(config: &TaxConfig, record: &Record) -> Vec<f64> {
+    let Ok(months) = schedule(config, record, config.end_month) else {
+        return Vec::new();
+    };
+    let mut ret = Vec::new();
+    for m in &months {
+        for b in config.salary.bounds() {
+            let employed = (m.month + 1 - config.start_month) as f64;
+            ret.push((b - m.cumulative_taxable) * config.months_employed() / employed);
+        }
+    }
+    ret
+}
+
+/// The smallest movement between `min` and `max`, on the grid of `precision`, that leaves at
+/// least `floor` net pay in every month, found by bisection as the net pay rises with the
+/// movement. None if `min` already does, or `max` does not.
+fn floor_crossing(
+    config: &TaxConfig,
+    record: &Record,
+    floor: f64,
+    min: f64,
+    max: f64,
+    precision: f64,
+) -> Result<Option<f64>> {
+    let net = |m: f64| -> Result<f64> {
+        let r = record.with_movement(config, m)?;
+        let tax = config.calc(&r)?;
+        Ok(lowest_monthly_net(config, &r, &tax))
+    };
+    if net(min)? >= floor || net(max)? < floor {
+        return Ok(None);
+    }
+    let (mut lo, mut hi) = (min, max);
+    while hi - lo > precision {
+        let mid = (lo + hi) / 2.0;
+        if net(mid)? >= floor {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    // Divided by the inverse, so that a precision of 0.01 gives whole cents.
+    Ok(Some((hi / precision).ceil() / precision.recip()))
+}
+
+/// The range of movements of `record` allowed by the constraints of `options`, with its ends on
+/// the granularity. The movement of zero, the record as given, is always allowed.
+pub(crate) fn range(config: &TaxConfig, record: &Record, options: &OptimizeOptions) -> (f64, f64) {
+    let mut max = record.year_bonus - options.min_bonus;
+    if let Some(m) = options.max_movement {
+        max = max.min(m);
+    }
+    if let Some(m) = options.max_monthly_salary {
+        max =
+            max.min(m * config.months_employed() - config.annual_salary(record) - record.movement);
+    }
+    // At most the salary paid in the months employed may be moved into the bonus.
+    let reverse = options
+        .max_reverse
+        .min(config.annual_salary(record) + record.movement);
+    let (mut min, mut max) = (-reverse, max.max(0.0));
+    if let Some(g) = options.granularity {
+        (min, max) = ((min / g).ceil() * g, (max / g).floor() * g);
+    }
+    (min, max)
+}
+
+/// Search for the movement between year bonus and salary that is best under the objective of
+/// `options`, by default the least total tax. With other bonuses, each of them is tried as the
+/// separately taxed one.
+pub fn optimize(
+    config: &TaxConfig,
+    record: &Record,
+    options: &OptimizeOptions,
+) -> Result<Optimization> {
+    anyhow::ensure!(options.precision > 0.0, "precision must be positive");
+    anyhow::ensure!(
+        options.max_reverse >= 0.0,
+        "max_reverse must not be negative"
+    );
+    if let Some(step) = options.step {
+        anyhow::ensure!(step > 0.0, "step must be positive");
+    }
+    anyhow::ensure!(options.min_bonus >= 0.0, "min_bonus must not be negative");
+    if let Some(m) = options.max_movement {
+        anyhow::ensure!(m >= 0.0, "max_movement must not be negative");
+    }
+    if let Some(g) = options.granularity {
+        anyhow::ensure!(g > 0.0, "granularity must be positive");
+    }
+    if let Some(m) = options.max_monthly_salary {
+        anyhow::ensure!(m >= 0.0, "max_monthly_salary must not be negative");
+    }
+    let mut best = search_all(config, record, options)?;
+    if let Objective::MinTaxWithMonthlyNet(floor) = options.objective {
+        anyhow::ensure!(
+            score(config, &best.record, &best.after, options.objective).is_finite(),
+            "no movement allowed leaves a net pay of {floor} in every month"
+        );
+    }
+    // A constraint binds if the search without it finds a lower tax.
+    let mut relaxed = Vec::new();
+    if options.min_bonus > 0.0 {
+        relaxed.push((
+            "min_bonus",
+            OptimizeOptions {
+                min_bonus: 0.0,
+                ..options.clone()
+            },
+        ));
+    }
+    if options.max_movement.is_some() {
+        relaxed.push((
+            "max_movement",
+            OptimizeOptions {
+                max_movement: None,
+                ..options.clone()
+            },
+        ));
+    }
+    if options.max_monthly_salary.is_some() {
+        relaxed.push((
+            "max_monthly_salary",
+            OptimizeOptions {
+                max_monthly_salary: None,
+                ..options.clone()
+            },
+        ));
+    }
+    if options.max_reverse > 0.0 {
+        relaxed.push((
+            "max_reverse",
+            OptimizeOptions {
+                max_reverse: config.annual_salary(record) + record.movement,
+                ..options.clone()
+            },
+        ));
+    }
+    if options.granularity.is_some() {
+        relaxed.push((
+            "granularity",
+            OptimizeOptions {
+                granularity: None,
+                ..options.clone()
+            },
+        ));
+    }
+    if let Objective::MinTaxWithMonthlyNet(_) = options.objective {
+        relaxed.push((
+            "min_monthly_net",
+            OptimizeOptions {
+                objective: Objective::MinTax,
+                ..options.clone()
+            },
+        ));
+    }
+    let best_score = score(config, &best.record, &best.after, options.objective);
+    for (name, relaxed) in relaxed {
+        let free = search_all(config, record, &relaxed)?;
+        // Without the floor, the movement found may break it, so only its tax is compared.
+        let better = match name {
+            "min_monthly_net" => free.after.total() < best.after.total() - 0.005,
+            _ => score(config, &free.record, &free.after, options.objective) < best_score - 0.005,
+        };
+        if better {
+            best.binding.push(name.to_string());
+        }
+    }
+    Ok(best)
+}
+
+/// [`search`] the record as given and with each of its other bonuses taxed separately.
+fn search_all(
+    config: &TaxConfig,
+    record: &Record,
+    options: &OptimizeOptions,
+) -> Result<Optimization> {
+    let before = config.calc(record)?;
+    let mut best = Optimization {
+        before: before.clone(),
+        after: before,
+        movement: 0.0,
+        record: record.clone(),
+        binding: Vec::new(),
+        sweep: Vec::new(),
+    };
+    search(config, record, options, &mut best)?;
+    // The sweep is of the record as given, so that each movement has one tax.
+    let others = OptimizeOptions {
+        record_sweep: false,
+        ..options.clone()
+    };
+    for i in 0..record.other_bonuses.len() {
+        search(config, &record.with_separate_bonus(i), &others, &mut best)?;
+    }
+    best.sweep.sort_by(|a, b| a.movement.total_cmp(&b.movement));
+    best.sweep.dedup_by(|a, b| a.movement == b.movement);
+    Ok(best)
+}
+
+/// The tax of `record` before and after moving `movement` from its year bonus into salary, or
+/// salary into the year bonus if negative, without searching for a better one.
+pub fn evaluate(config: &TaxConfig, record: &Record, movement: f64) -> Result<Optimization> {
+    anyhow::ensure!(
+        movement <= record.year_bonus,
+        "the movement {movement} is more than the year bonus {}",
+        record.year_bonus
+    );
+    let salary = config.annual_salary(record) + record.movement;
+    anyhow::ensure!(
+        -movement <= salary,
+        "the movement {movement} is more than the annual salary {salary}"
+    );
+    let after = record.with_movement(config, movement)?;
+    Ok(Optimization {
+        before: config.calc(record)?,
+        after: config.calc(&after)?,
+        movement,
+        record: after,
+        binding: Vec::new(),
+        sweep: Vec::new(),
+    })
+}
+
+/// The golden ratio, by which a golden-section search narrows its interval each step.
+const GOLDEN: f64 = 1.618_033_988_749_895;
+
+/// Search the movements of `record`, updating `best` with any that beats it.
+fn search(
+    config: &TaxConfig,
+    record: &Record,
+    options: &OptimizeOptions,
+    best: &mut Optimization,
+) -> Result<()> {
+    // Returns the score of the movement `m`.
+    let (min, max) = range(config, record, options);
+    let eval = |best: &mut Optimization, m: f64| -> Result<f64> {
+        // A sweep may land between the multiples of the granularity.
+        let m = match options.granularity {
+            Some(g) => ((m / g).round() * g).clamp(min, max),
+            None => m,
+        };
+        let r = record.with_movement(config, m)?;
+        let v = config.calc(&r)?;
+        if options.record_sweep {
+            best.sweep.push(SweepPoint::new(m, &v));
+        }
+        let value = score(config, &r, &v, options.objective);
+        // Ties within a cent keep the earlier, smaller movement instead of rounding noise.
+        if value < score(config, &best.record, &best.after, options.objective) - 0.005 {
+            best.after = v;
+            best.movement = m;
+            best.record = r;
+        }
+        Ok(value)
+    };
+    let Some(mut step) = options.step else {
+        let candidates = candidates(config, record, options)?;
+        for m in &candidates {
+            eval(best, *m)?;
+        }
+        if let Objective::Smooth(_) = options.objective {
+            // Between the candidates every net salary is linear in the movement, so their spread
+            // is convex and so is the score: a golden-section search finds its minimum.
+            for pair in candidates.windows(2) {
+                let (mut lo, mut hi) = (pair[0], pair[1]);
+                while hi - lo > options.precision {
+                    let a = hi - (hi - lo) / GOLDEN;
+                    let b = lo + (hi - lo) / GOLDEN;
+                    if eval(best, a)? < eval(best, b)? {
+                        hi = b;
+                    } else {
+                        lo = a;
+                    }
+                }
+            }
+        }
+        return Ok(());
+    };
+    // Sweep the range, then sweep again around the best point with a tenth of the step until the
+    // step reaches the precision. The best point is local, as `best` may come from another record.
+    let (mut lo, mut hi) = (min, max);
+    loop {
+        let n = ((hi - lo) / step).floor() as usize;
+        let (mut local, mut center) = (f64::INFINITY, lo);
+        for m in (0..=n).map(|i| lo + i as f64 * step).chain([hi]) {
+            let v = eval(best, m)?;
+            if v < local {
+                (local, center) = (v, m);
+            }
+        }
+        if step <= options.precision {
+            return Ok(());
+        }
+        lo = (center - step).max(min);
+        hi = (center + step).min(max);
+        step = (step / 10.0).max(options.precision);
+    }
+}
+
+/// The trade-off between the total tax of `record` and the [`net_spread`] of its take-home pay:
+/// the movements among the candidates of the search under `options` that no other beats on both,
+/// from the least tax to the steadiest pay. Pick a point with [`Objective::Smooth`].
+pub fn tradeoff(
+    config: &TaxConfig,
+    record: &Record,
+    options: &OptimizeOptions,
+) -> Result<Vec<TradeOff>> {
+    let options = OptimizeOptions {
+        objective: Objective::Smooth(0.0),
+        ..options.clone()
+    };
+    let mut points = Vec::new();
+    for movement in candidates(config, record, &options)? {
+        let r = record.with_movement(config, movement)?;
+        points.push(TradeOff {
+            movement,
+            tax: config.calc(&r)?.total(),
+            spread: net_spread(config, &r),
+        });
+    }
+    points.sort_by(|a, b| a.tax.total_cmp(&b.tax).then(a.spread.total_cmp(&b.spread)));
+    let mut ret: Vec<TradeOff> = Vec::new();
+    for p in points {
+        if ret.last().is_none_or(|last| p.spread < last.spread - 0.005) {
+            ret.push(p);
+        }
+    }
+    Ok(ret)
+}
+
+/// The tax of `record` under `points` movements spread evenly over the range the constraints of
+/// `options` allow, from the least to the most, e.g. to plot the tax against the movement.
+pub fn tax_curve(
+    config: &TaxConfig,
+    record: &Record,
+    options: &OptimizeOptions,
+    points: usize,
+) -> Result<Vec<SweepPoint>> {
+    let (min, max) = range(config, record, options);
+    let step = (max - min) / points.saturating_sub(1).max(1) as f64;
+    (0..points)
+        .map(|i| {
+            let movement = min + i as f64 * step;
+            let tax = config.calc(&record.with_movement(config, movement)?)?;
+            Ok(SweepPoint::new(movement, &tax))
+        })
+        .try_collect()
+}
+
+/// The movements within the range the constraints of `options` allow at which the taxable salary
+/// income of `record` or the monthly average of its year bonus crosses a bracket bound, from the
+/// least movement, to annotate a plot of the tax.
+pub fn thresholds(
+    config: &TaxConfig,
+    record: &Record,
+    options: &OptimizeOptions,
+) -> Vec<Threshold> {
+    let (min, max) = range(config, record, options);
+    let income = config.salary_income(record);
+    let mut ret: Vec<_> = config
+        .salary_bounds()
+        .map(|b| Threshold {
+            movement: b - income,
+            label: format!("salary {b}"),
+        })
+        .collect();
+    if config.resident_status == ResidentStatus::Resident {
+        ret.extend(config.year_bonus.bounds().map(|b| Threshold {
+            movement: record.year_bonus - b * 12.0,
+            label: format!("bonus {}", b * 12.0),
+        }));
+    }
+    ret.retain(|t| (min..=max).contains(&t.movement));
+    ret.sort_by(|a, b| a.movement.total_cmp(&b.movement));
+    ret
+}
@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use serde::Serialize;
+
+use crate::config::number;
+
+/// A payroll tax on the annual wages, e.g. US Social Security or Medicare.
+#[derive(Clone, Debug, Serialize)]
+pub struct PayrollItem {
+    pub name: String,
+    pub ratio: f64,
+    /// The wages below which nothing is taxed.
+    pub threshold: f64,
+    /// The wages above which nothing more is taxed, unlimited if not given.
+    pub cap: Option<f64>,
+}
+
+/// Payroll taxes (e.g. FICA), charged on the wages apart from the income tax and not deductible
+/// from it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PayrollTax {
+    pub items: Vec<PayrollItem>,
+}
+
+impl TryFrom<&toml::Table> for PayrollTax {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let get = |t: &toml::Table, key: &str| -> Result<Option<f64>> {
+            t.get(key)
+                .map(|v| number(v).ok_or_else(|| anyhow!("payroll_tax: {key} is not a number")))
+                .transpose()
+        };
+        let mut items = Vec::new();
+        for item in tbl
+            .get("item")
+            .map(|i| i.as_array().ok_or_else(|| anyhow!("item is not an array")))
+            .transpose()?
+            .into_iter()
+            .flatten()
+        {
+            let item = item
+                .as_table()
+                .ok_or_else(|| anyhow!("payroll_tax.item is not a table"))?;
+            items.push(PayrollItem {
+                name: item
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .ok_or_else(|| anyhow!("payroll_tax.item: missing name"))?
+                    .to_string(),
+                ratio: get(item, "ratio")?
+                    .ok_or_else(|| anyhow!("payroll_tax.item: missing ratio"))?,
+                threshold: get(item, "threshold")?.unwrap_or(0.0),
+                cap: get(item, "cap")?,
+            });
+        }
+        Ok(Self { items })
+    }
+}
+
+impl PayrollTax {
+    /// The tax of each item for the annual `wages`.
+    pub fn items(&self, wages: f64) -> impl Iterator<Item = (&str, f64)> {
+        self.items.iter().map(move |item| {
+            let taxed = wages.min(item.cap.unwrap_or(f64::INFINITY)) - item.threshold;
+            (item.name.as_str(), 0f64.max(taxed) * item.ratio)
+        })
+    }
+
+    /// The wages at which an item starts or stops.
+    pub fn bounds(&self) -> impl Iterator<Item = f64> + '_ {
+        self.items
+            .iter()
+            .flat_map(|item| std::iter::once(item.threshold).chain(item.cap))
+    }
+
+    /// The total tax for the annual `wages`.
+    pub fn tax(&self, wages: f64) -> f64 {
+        self.items(wages).fold(0.0, |a, (_, t)| a + t)
+    }
+}
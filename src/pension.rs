@@ -0,0 +1,212 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{Record, TaxConfig};
+
+/// Rules of the private pension (个人养老金). Contributions are deducted from the comprehensive
+/// income now and taxed at a flat ratio when withdrawn.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct PensionRules {
+    /// The annual deductible contribution.
+    pub cap: f64,
+    /// The ratio taxed on withdrawal.
+    pub withdrawal_ratio: f64,
+    /// Whether contributions are made by salary sacrifice, so they are not wages for payroll
+    /// taxes either.
+    pub salary_sacrifice: bool,
+}
+
+impl TryFrom<&toml::Table> for PensionRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "salary_sacrifice" => {
+                    ret.salary_sacrifice = value.as_bool().ok_or_else(|| {
+                        anyhow!("private_pension.salary_sacrifice is not a boolean")
+                    })?;
+                    continue;
+                }
+                "cap" => &mut ret.cap,
+                "withdrawal_ratio" => &mut ret.withdrawal_ratio,
+                _ => bail!("unknown private_pension key `{key}`"),
+            };
+            *field =
+                number(value).ok_or_else(|| anyhow!("private_pension.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+impl PensionRules {
+    /// The deductible part of the annual `contribution`.
+    pub fn deduction(&self, contribution: f64) -> f64 {
+        contribution.clamp(0.0, self.cap)
+    }
+}
+
+/// Rules of the enterprise annuity (企业年金). Contributions within the caps are not taxed until
+/// they are withdrawn.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct AnnuityRules {
+    /// The ratio of the base the employee may deduct.
+    pub employee_cap: f64,
+    /// The ratio of the base the employer may contribute untaxed. Above it, the employer
+    /// contribution is taxed as salary.
+    pub employer_cap: f64,
+    /// The cap of the monthly contribution base, 3 times the local average monthly wage. The cap
+    /// of the social insurance base if zero.
+    pub base_cap: f64,
+    /// The expected ratio taxed on withdrawal.
+    pub withdrawal_ratio: f64,
+}
+
+impl TryFrom<&toml::Table> for AnnuityRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "employee_cap" => &mut ret.employee_cap,
+                "employer_cap" => &mut ret.employer_cap,
+                "base_cap" => &mut ret.base_cap,
+                "withdrawal_ratio" => &mut ret.withdrawal_ratio,
+                _ => bail!("unknown enterprise_annuity key `{key}`"),
+            };
+            *field =
+                number(value).ok_or_else(|| anyhow!("enterprise_annuity.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+/// The effect of a private pension contribution.
+#[derive(Clone, Debug, Serialize)]
+pub struct Pension {
+    pub contribution: f64,
+    /// The tax saved this year compared with no contribution.
+    pub saving: f64,
+    /// The tax paid when the contribution is withdrawn.
+    pub withdrawal_tax: f64,
+    /// `saving` minus `withdrawal_tax`.
+    pub benefit: f64,
+}
+
+impl std::fmt::Display for Pension {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} (saves {:.2} now, {:.2} taxed on withdrawal, benefit {:.2})",
+            self.contribution, self.saving, self.withdrawal_tax, self.benefit
+        )
+    }
+}
+
+/// The result of [`advise_pension`].
+#[derive(Clone, Debug, Serialize)]
+pub struct PensionAdvice {
+    /// The contribution of the record.
+    pub current: Pension,
+    /// The contribution with the largest benefit, the smallest one on a tie.
+    pub recommended: Pension,
+}
+
+/// The effect of contributing `contribution` to the private pension instead of the one of the
+/// record.
+pub fn pension(config: &TaxConfig, record: &Record, contribution: f64) -> Result<Pension> {
+    let mut r = record.clone();
+    r.private_pension = 0.0;
+    let without = config.calc(&r)?.total();
+    r.private_pension = contribution;
+    let saving = without - config.calc(&r)?.total();
+    let withdrawal_tax = contribution * config.private_pension.withdrawal_ratio;
+    Ok(Pension {
+        contribution,
+        saving,
+        withdrawal_tax,
+        benefit: saving - withdrawal_tax,
+    })
+}
+
+/// The effect of joining the enterprise annuity plan.
+#[derive(Clone, Debug, Serialize)]
+pub struct Annuity {
+    /// The annual contribution of the employee.
+    pub employee: f64,
+    /// The annual contribution of the employer.
+    pub employer: f64,
+    /// The tax saved this year compared with not joining.
+    pub saving: f64,
+    /// The tax paid when both contributions are withdrawn.
+    pub withdrawal_tax: f64,
+    /// The employer contribution plus `saving` minus `withdrawal_tax`, what joining is worth.
+    pub benefit: f64,
+}
+
+impl std::fmt::Display for Annuity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "contributions {:.2} (employee) + {:.2} (employer), saves {:.2} now, {:.2} taxed on \
+             withdrawal, benefit {:.2}",
+            self.employee, self.employer, self.saving, self.withdrawal_tax, self.benefit
+        )
+    }
+}
+
+/// The effect of joining the enterprise annuity plan with the ratios of the record, or with the
+/// caps if the record does not give them.
+pub fn annuity(config: &TaxConfig, record: &Record) -> Result<Annuity> {
+    let rules = &config.enterprise_annuity;
+    let mut r = record.clone();
+    if r.annuity == 0.0 && r.employer_annuity == 0.0 {
+        r.annuity = rules.employee_cap;
+        r.employer_annuity = rules.employer_cap;
+    }
+    let with = config.calc(&r)?.total();
+    let base = config.annuity_base(&r) * config.months_employed();
+    let (employee, employer) = (r.annuity * base, r.employer_annuity * base);
+    (r.annuity, r.employer_annuity) = (0.0, 0.0);
+    let saving = config.calc(&r)?.total() - with;
+    let withdrawal_tax = (employee + employer) * rules.withdrawal_ratio;
+    Ok(Annuity {
+        employee,
+        employer,
+        saving,
+        withdrawal_tax,
+        benefit: employer + saving - withdrawal_tax,
+    })
+}
+
+/// Find the private pension contribution that saves the most tax after the tax on withdrawal. The
+/// saving is linear in the contribution between the points where the salary income crosses a
+/// bracket bound, so only those and the ends of the range are tried. Under a formula the saving
+/// curves inside a bracket, and the best of those points is only close to the best contribution.
+pub fn advise_pension(config: &TaxConfig, record: &Record) -> Result<PensionAdvice> {
+    let cap = config.private_pension.cap;
+    let mut r = record.clone();
+    r.private_pension = 0.0;
+    let mut candidates = vec![0.0, cap];
+    candidates.extend(config.salary_crossings(&r).into_iter().map(|x| -x));
+    if config.private_pension.salary_sacrifice {
+        let wages = config.wages(&r);
+        candidates.extend(config.payroll_tax.bounds().map(|b| wages - b));
+    }
+    candidates.retain(|c| (0.0..=cap).contains(c));
+    candidates.sort_by(|a, b| a.partial_cmp(b).unwrap());
+    let mut recommended = pension(config, record, 0.0)?;
+    for c in candidates {
+        let p = pension(config, record, c)?;
+        if p.benefit > recommended.benefit + 1e-9 {
+            recommended = p;
+        }
+    }
+    Ok(PensionAdvice {
+        current: pension(config, record, record.private_pension)?,
+        recommended,
+    })
+}
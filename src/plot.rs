@@ -0,0 +1,111 @@
+use std::path::Path;
+
+use anyhow::{bail, Result};
+use plotters::prelude::*;
+use plotters::style::text_anchor::{HPos, Pos, VPos};
+
+use crate::{SweepPoint, Threshold};
+
+/// Plot the total tax and the net income of `curve` against the movement as an SVG file at
+/// `path`, with a line at each of the `thresholds` and the `optimum` marked, e.g. for a memo to
+/// the payroll.
+pub fn plot(
+    path: &Path,
+    curve: &[SweepPoint],
+    optimum: &SweepPoint,
+    thresholds: &[Threshold],
+) -> Result<()> {
+    if path.extension().and_then(|e| e.to_str()) != Some("svg") {
+        bail!(
+            "{}: only SVG plots are supported, e.g. out.svg",
+            path.display()
+        );
+    }
+    let (Some(first), Some(last)) = (curve.first(), curve.last()) else {
+        bail!("nothing to plot");
+    };
+    // Padded so that flat lines and the labels of the thresholds fit.
+    let bounds = |values: &mut dyn Iterator<Item = f64>| {
+        let (lo, hi) = values.fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), v| {
+            (lo.min(v), hi.max(v))
+        });
+        let pad = ((hi - lo) * 0.1).max(1.0);
+        (lo - pad, hi + pad)
+    };
+    let (tax_lo, tax_hi) = bounds(&mut curve.iter().map(|p| p.total));
+    let (net_lo, net_hi) = bounds(&mut curve.iter().map(|p| p.net));
+    let x = first.movement..last.movement.max(first.movement + 1.0);
+    let root = SVGBackend::new(path, (960, 540)).into_drawing_area();
+    root.fill(&WHITE)?;
+    let mut chart = ChartBuilder::on(&root)
+        .caption("Tax and net income by movement", ("sans-serif", 24))
+        .margin(16)
+        .x_label_area_size(40)
+        .y_label_area_size(80)
+        .right_y_label_area_size(80)
+        .build_cartesian_2d(x.clone(), tax_lo..tax_hi)?
+        .set_secondary_coord(x, net_lo..net_hi);
+    chart
+        .configure_mesh()
+        .disable_x_mesh()
+        .x_desc("movement of year bonus into salary")
+        .y_desc("total tax")
+        .x_label_formatter(&|v| format!("{v:.0}"))
+        .y_label_formatter(&|v| format!("{v:.0}"))
+        .draw()?;
+    chart
+        .configure_secondary_axes()
+        .y_desc("net income")
+        .y_label_formatter(&|v| format!("{v:.0}"))
+        .draw()?;
+    let label = ("sans-serif", 12)
+        .into_font()
+        .transform(FontTransform::Rotate90)
+        .color(&BLACK.mix(0.6))
+        .pos(Pos::new(HPos::Left, VPos::Bottom));
+    for t in thresholds {
+        chart.draw_series(LineSeries::new(
+            [(t.movement, tax_lo), (t.movement, tax_hi)],
+            BLACK.mix(0.2),
+        ))?;
+        chart.draw_series([Text::new(
+            t.label.clone(),
+            (t.movement, tax_hi),
+            label.clone(),
+        )])?;
+    }
+    chart
+        .draw_series(LineSeries::new(
+            curve.iter().map(|p| (p.movement, p.total)),
+            RED.stroke_width(2),
+        ))?
+        .label("total tax")
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], RED.stroke_width(2)));
+    chart
+        .draw_secondary_series(LineSeries::new(
+            curve.iter().map(|p| (p.movement, p.net)),
+            BLUE.stroke_width(2),
+        ))?
+        .label("net income")
+        .legend(|(x, y)| PathElement::new([(x, y), (x + 20, y)], BLUE.stroke_width(2)));
+    chart.draw_series([
+        Circle::new((optimum.movement, optimum.total), 5, RED.filled()).into_dyn(),
+        Text::new(
+            format!("best: {} (tax {:.2})", optimum.movement, optimum.total),
+            (optimum.movement, optimum.total),
+            ("sans-serif", 14)
+                .into_font()
+                .color(&BLACK)
+                .pos(Pos::new(HPos::Left, VPos::Top)),
+        )
+        .into_dyn(),
+    ])?;
+    chart
+        .configure_series_labels()
+        .position(SeriesLabelPosition::LowerLeft)
+        .background_style(WHITE.mix(0.8))
+        .border_style(BLACK)
+        .draw()?;
+    root.present()?;
+    Ok(())
+}
@@ -0,0 +1,104 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{Record, ResidentStatus, Tax, TaxConfig};
+
+/// How the year bonus is taxed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Policy {
+    /// Taxed on its own with the year bonus table (单独计税).
+    Separate,
+    /// Merged into the comprehensive income (并入综合所得).
+    Merged,
+}
+
+impl std::fmt::Display for Policy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Separate => "separate",
+            Self::Merged => "merged",
+        })
+    }
+}
+
+/// The result of [`compare_policies`].
+#[derive(Clone, Debug, Serialize)]
+pub struct PolicyComparison {
+    pub separate: Tax,
+    pub merged: Tax,
+    /// The cheaper policy, `Separate` on a tie.
+    pub recommended: Policy,
+}
+
+impl PolicyComparison {
+    /// How much the recommended policy saves over the other one.
+    pub fn saving(&self) -> f64 {
+        (self.separate.total() - self.merged.total()).abs()
+    }
+}
+
+/// Tax the year bonus of the record separately and merged into salary.
+pub fn compare_policies(config: &TaxConfig, record: &Record) -> Result<PolicyComparison> {
+    anyhow::ensure!(
+        config.resident_status == ResidentStatus::Resident,
+        "non-residents have no choice of bonus policy nor annual reconciliation"
+    );
+    let separate = config.calc(record)?;
+    let merged = config.calc(&record.with_movement(config, record.year_bonus)?)?;
+    let recommended = if merged.total() < separate.total() {
+        Policy::Merged
+    } else {
+        Policy::Separate
+    };
+    Ok(PolicyComparison {
+        separate,
+        merged,
+        recommended,
+    })
+}
+
+/// The result of [`compare_statuses`].
+#[derive(Clone, Debug, Serialize)]
+pub struct StatusComparison {
+    /// The tax under each filing status.
+    pub taxes: Vec<(String, Tax)>,
+    /// The cheapest status, the first one on a tie.
+    pub recommended: String,
+}
+
+impl StatusComparison {
+    /// How much the recommended status saves over the next cheapest one.
+    pub fn saving(&self) -> f64 {
+        let mut totals: Vec<_> = self.taxes.iter().map(|(_, t)| t.total()).collect();
+        totals.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        match totals[..] {
+            [best, next, ..] => next - best,
+            _ => 0.0,
+        }
+    }
+}
+
+/// Tax the record under each of `configs`, which differ in their filing status, e.g. the old and
+/// new regimes of India.
+pub fn compare_statuses(configs: &[TaxConfig], record: &Record) -> Result<StatusComparison> {
+    anyhow::ensure!(!configs.is_empty(), "the region has no filing statuses");
+    let taxes: Vec<_> = configs
+        .iter()
+        .map(|c| Ok((c.filing_status.clone().unwrap_or_default(), c.calc(record)?)))
+        .collect::<Result<_>>()?;
+    let (recommended, _) = taxes
+        .iter()
+        .reduce(|best, t| {
+            if t.1.total() < best.1.total() {
+                t
+            } else {
+                best
+            }
+        })
+        .expect("there is a status");
+    Ok(StatusComparison {
+        recommended: recommended.clone(),
+        taxes,
+    })
+}
@@ -0,0 +1,56 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::solve::scaled;
+use crate::{optimize, OptimizeOptions, Record, TaxConfig};
+
+/// A year of a [`project`]ion.
+#[derive(Clone, Debug, Serialize)]
+pub struct ProjectedYear {
+    pub year: i32,
+    pub monthly_salary: f64,
+    pub year_bonus: f64,
+    /// The best movement of the year.
+    pub movement: f64,
+    /// The total tax under the movement.
+    pub tax: f64,
+    /// The annual net income under the movement.
+    pub net: f64,
+}
+
+/// Project the tax of `record` over `years` years from `first_year`, its salary and bonuses rising
+/// by `raise` a year, e.g. 0.05 for 5%, and the bracket bounds of `config` by `indexation`, if
+/// given, with the best movement of `options` in each year. The deductions stay as given.
+pub fn project(
+    config: &TaxConfig,
+    record: &Record,
+    first_year: i32,
+    years: u32,
+    raise: f64,
+    indexation: Option<f64>,
+    options: &OptimizeOptions,
+) -> Result<Vec<ProjectedYear>> {
+    anyhow::ensure!(raise > -1.0, "the raise must be above -100%");
+    anyhow::ensure!(
+        indexation.is_none_or(|i| i > -1.0),
+        "the indexation must be above -100%"
+    );
+    (0..years)
+        .map(|n| {
+            let r = scaled(record, (1.0 + raise).powi(n as i32));
+            let mut config = config.clone();
+            if let Some(i) = indexation {
+                config.index_brackets((1.0 + i).powi(n as i32));
+            }
+            let best = optimize(&config, &r, options)?;
+            Ok(ProjectedYear {
+                year: first_year + n as i32,
+                monthly_salary: r.monthly_salary,
+                year_bonus: r.year_bonus,
+                movement: best.movement,
+                tax: best.after.total(),
+                net: best.after.net,
+            })
+        })
+        .try_collect()
+}
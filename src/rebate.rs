@@ -0,0 +1,78 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::Table;
+
+/// A reduction of the salary tax for low incomes, e.g. the rebate of section 87A in India or the
+/// low income tax offset of Australia.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Rebate {
+    /// The taxable salary income up to which the rebate is given, any income if not given.
+    pub threshold: Option<f64>,
+    /// The largest rebate.
+    pub cap: f64,
+    /// Whether the tax on an income above the threshold is at most the income above it, so that
+    /// crossing the threshold does not leave less after tax.
+    pub marginal_relief: bool,
+    /// A marginal table whose tax on the taxable salary income is withdrawn from the cap, so the
+    /// rebate phases out instead of stopping at the threshold.
+    pub phase_out: Option<Table>,
+}
+
+impl TryFrom<&toml::Table> for Rebate {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            match key.as_str() {
+                "marginal_relief" => {
+                    ret.marginal_relief = value
+                        .as_bool()
+                        .ok_or_else(|| anyhow!("rebate.marginal_relief is not a boolean"))?
+                }
+                "phase_out" => ret.phase_out = Some(Table::parse("rebate.phase_out", value)?),
+                "threshold" => {
+                    ret.threshold = Some(
+                        number(value).ok_or_else(|| anyhow!("rebate.threshold is not a number"))?,
+                    )
+                }
+                "cap" => {
+                    ret.cap = number(value).ok_or_else(|| anyhow!("rebate.cap is not a number"))?
+                }
+                _ => bail!("unknown rebate key `{key}`"),
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl Rebate {
+    /// The rebate for the taxable `income`, before it is limited to the tax.
+    pub fn amount(&self, income: f64) -> Result<f64> {
+        let withdrawn = match &self.phase_out {
+            Some(t) => t.tax(0f64.max(income)).context("rebate.phase_out")?,
+            None => 0.0,
+        };
+        Ok(0f64.max(self.cap - withdrawn))
+    }
+
+    /// The salary `tax` of the taxable `income` after the rebate.
+    pub fn apply(&self, income: f64, tax: f64) -> Result<f64> {
+        Ok(match self.threshold {
+            Some(threshold) if income > threshold => match self.marginal_relief {
+                true => tax.min(income - threshold),
+                false => tax,
+            },
+            _ => 0f64.max(tax - self.amount(income)?),
+        })
+    }
+
+    /// The taxable incomes at which the rebate starts to phase out or stops.
+    pub(crate) fn bounds(&self) -> impl Iterator<Item = f64> + '_ {
+        self.threshold
+            .into_iter()
+            .chain(self.phase_out.iter().flat_map(|t| t.bounds()))
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{compare_policies, Policy, Record, Tax, TaxConfig};
+
+/// The result of the annual reconciliation (汇算清缴).
+#[derive(Clone, Debug, Serialize)]
+pub struct Reconciliation {
+    /// The final annual tax under the cheaper year bonus policy.
+    pub liability: Tax,
+    pub policy: Policy,
+    /// The tax withheld during the year, year bonus included.
+    pub withheld: f64,
+    /// The amount owed at reconciliation, negative for a refund.
+    pub balance: f64,
+}
+
+impl std::fmt::Display for Reconciliation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Liability: {}", self.liability)?;
+        writeln!(f, "Year bonus: {}", self.policy)?;
+        writeln!(f, "Withheld: {:.2}", self.withheld)?;
+        if self.balance < 0.0 {
+            write!(f, "Refund: {:.2}", -self.balance)
+        } else {
+            write!(f, "Owed: {:.2}", self.balance)
+        }
+    }
+}
+
+/// Reconcile the annual tax of the record with the tax `withheld` during the year. The year bonus
+/// is taxed separately or merged, whichever is cheaper, as the taxpayer may choose at this point.
+/// Deductions only claimed at the reconciliation, like the serious medical one, count here.
+/// Severance, equity and rental income are settled when they are paid, so neither their tax nor
+/// their withholding is reconciled.
+pub fn reconcile(config: &TaxConfig, record: &Record, withheld: f64) -> Result<Reconciliation> {
+    let cmp = compare_policies(config, record)?;
+    let liability = match cmp.recommended {
+        Policy::Separate => cmp.separate,
+        Policy::Merged => cmp.merged,
+    };
+    Ok(Reconciliation {
+        balance: liability.total()
+            - liability.severance
+            - liability.equity
+            - liability.rental
+            - withheld,
+        liability,
+        policy: cmp.recommended,
+        withheld,
+    })
+}
+
+/// The record of the income from two concurrent employers, `first` and `second`, as the annual
+/// reconciliation sees it: the salaries and bonuses of both, only one standard deduction, and the
+/// social insurance withheld by `second` as a deduction of the first. The year bonus of `first`
+/// stays the one that may be taxed separately, and the special additional deductions are those of
+/// `first`, as they are claimed once.
+pub fn combine(config: &TaxConfig, first: &Record, second: &Record) -> Record {
+    let mut r = first.clone();
+    r.social_base = Some(first.social_base());
+    if first.salaries.is_some() || second.salaries.is_some() {
+        let salaries: Vec<f64> = (1..=12)
+            .map(|m| first.salary_of(m) + second.salary_of(m))
+            .collect();
+        r.salaries = Some(salaries);
+    }
+    r.monthly_salary += second.monthly_salary;
+    r.movement += second.movement;
+    r.monthly_tax_deduction +=
+        second.monthly_tax_deduction + config.social_insurance(second) / config.months_employed();
+    if second.year_bonus != 0.0 {
+        r.other_bonuses.push(second.year_bonus);
+    }
+    r.other_bonuses.extend(&second.other_bonuses);
+    r
+}
@@ -0,0 +1,736 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+
+use crate::{deduction, Allowance, ForeignSource, Remuneration, SpecialDeductions, TaxConfig};
+
+/// A personal case: what you earn and how much of the year bonus has been moved into salary.
+#[derive(Clone, Debug, Serialize)]
+pub struct Record {
+    pub monthly_salary: f64,
+    /// The personal monthly deductions not derived by the config, e.g. social insurance without a
+    /// city preset. The standard deduction of the config, the 5000 of China, is not part of it.
+    pub monthly_tax_deduction: f64,
+    pub year_bonus: f64,
+    /// The amount moved from the year bonus into salary. It is negative when salary is moved into
+    /// the year bonus.
+    pub movement: f64,
+    pub special: SpecialDeductions,
+    /// The monthly social insurance contribution base, the monthly salary if not given.
+    pub social_base: Option<f64>,
+    /// The salary of each month when it varies over the year, e.g. with a raise or commissions.
+    /// `monthly_salary` is their average then.
+    pub salaries: Option<Vec<f64>>,
+    /// Bonuses paid besides the year bonus, e.g. the 13th-month salary. Only one bonus a year may
+    /// be taxed separately, so these are taxed together with salary.
+    pub other_bonuses: Vec<f64>,
+    /// The allowances paid every month on top of the salary, exempt as the config declares.
+    pub allowances: Vec<Allowance>,
+    /// The one-off compensation for terminating the employment (解除劳动关系一次性补偿收入).
+    pub severance: f64,
+    /// The equity income of the year, taxed apart from the other income.
+    pub equity: Vec<Vest>,
+    /// The purchases of an employee stock purchase plan (ESPP), whose discount is equity income
+    /// at purchase.
+    pub espp: Vec<Purchase>,
+    /// The payments of labor remuneration (劳务报酬), e.g. freelance or consulting fees.
+    pub labor_income: Vec<f64>,
+    /// The payments of author remuneration (稿酬).
+    pub author_income: Vec<f64>,
+    /// The payments of royalties (特许权使用费).
+    pub royalty_income: Vec<f64>,
+    /// The rent received in each month (财产租赁所得).
+    pub rental_income: Vec<f64>,
+    /// The revenue invoiced through a sole proprietorship in each month, VAT included, on which
+    /// VAT and its surcharges are charged.
+    pub invoices: Vec<f64>,
+    /// The annual costs and expenses the business deducts from the revenue invoiced.
+    pub business_expenses: f64,
+    /// The annual contribution to the private pension (个人养老金).
+    pub private_pension: f64,
+    /// The ratio of the base the employee contributes to the enterprise annuity (企业年金).
+    pub annuity: f64,
+    /// The ratio of the base the employer contributes to the enterprise annuity.
+    pub employer_annuity: f64,
+    /// The ratio of the base contributed to the supplementary housing fund (补充住房公积金).
+    pub supplementary_housing: f64,
+    /// Charitable donations, deductible up to a part of the taxable income.
+    pub donations: f64,
+    /// Charitable donations through channels that are fully deductible.
+    pub qualified_donations: f64,
+    /// The annual premium of tax-preferred commercial health insurance.
+    pub health_insurance: f64,
+    /// The comprehensive income sourced and paid outside China, by country.
+    pub foreign_income: Vec<ForeignSource>,
+    /// The consecutive years of residence before this one, each with 183 days or more in China
+    /// and no single absence over 30 days.
+    pub residence_years: u32,
+    /// The annual wages of the previous year, on which taxes assessed a year late are charged, e.g.
+    /// the inhabitant tax of Japan. The wages of this year if not given.
+    pub prior_wages: Option<f64>,
+}
+
+/// Equity income (股权激励) from exercising stock options or vesting RSUs.
+#[derive(Clone, Debug, Serialize)]
+pub struct Vest {
+    /// The taxable amount, the market value minus what was paid for the shares.
+    pub amount: f64,
+    /// The date of the exercise or vesting, `YYYY-MM-DD`.
+    pub date: Option<String>,
+}
+
+impl Vest {
+    /// Parse `amount` or `amount@YYYY-MM-DD`.
+    pub(crate) fn parse(s: &str) -> Result<Self> {
+        let (amount, date) = match s.split_once('@') {
+            Some((a, d)) => (a, Some(d.trim().to_string())),
+            None => (s, None),
+        };
+        if let Some(date) = &date {
+            check_date(date)?;
+        }
+        Ok(Self {
+            amount: amount
+                .trim()
+                .parse()
+                .with_context(|| format!("invalid equity amount `{amount}`"))?,
+            date,
+        })
+    }
+
+    /// The month of the date, if any.
+    pub fn month(&self) -> Option<u32> {
+        month_of(self.date.as_ref()?)
+    }
+}
+
+/// The month of a date `YYYY-MM-DD`.
+fn month_of(date: &str) -> Option<u32> {
+    date.get(5..7)?.parse().ok()
+}
+
+/// Check that `date` is `YYYY-MM-DD`.
+fn check_date(date: &str) -> Result<()> {
+    let valid = date.len() == 10
+        && date.split('-').map(str::len).eq([4, 2, 2])
+        && month_of(date).is_some_and(|m| (1..=12).contains(&m));
+    anyhow::ensure!(valid, "invalid date `{date}`, expected YYYY-MM-DD");
+    Ok(())
+}
+
+impl std::fmt::Display for Vest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.amount)?;
+        if let Some(date) = &self.date {
+            write!(f, "@{date}")?;
+        }
+        Ok(())
+    }
+}
+
+/// A purchase of shares under an employee stock purchase plan (ESPP), at a discount to their
+/// market price.
+#[derive(Clone, Debug, Serialize)]
+pub struct Purchase {
+    pub shares: f64,
+    /// The market price of a share on the purchase date.
+    pub market_price: f64,
+    /// The price paid for a share.
+    pub purchase_price: f64,
+    /// The date of the purchase, `YYYY-MM-DD`.
+    pub date: Option<String>,
+}
+
+impl Purchase {
+    /// Parse `shares:market_price:purchase_price` or
+    /// `shares:market_price:purchase_price@YYYY-MM-DD`.
+    fn parse(s: &str) -> Result<Self> {
+        let (prices, date) = match s.split_once('@') {
+            Some((p, d)) => (p, Some(d.trim().to_string())),
+            None => (s, None),
+        };
+        if let Some(date) = &date {
+            check_date(date)?;
+        }
+        let numbers: Vec<f64> = prices
+            .split(':')
+            .map(|n| n.trim().parse())
+            .try_collect()
+            .with_context(|| format!("invalid ESPP purchase `{s}`"))?;
+        let [shares, market_price, purchase_price] = numbers[..] else {
+            bail!("expected shares:market_price:purchase_price, got `{s}`");
+        };
+        Ok(Self {
+            shares,
+            market_price,
+            purchase_price,
+            date,
+        })
+    }
+
+    /// The discount, taxable at purchase.
+    pub fn discount(&self) -> f64 {
+        self.shares * 0f64.max(self.market_price - self.purchase_price)
+    }
+
+    /// The month of the date, if any.
+    pub fn month(&self) -> Option<u32> {
+        month_of(self.date.as_ref()?)
+    }
+}
+
+impl std::fmt::Display for Purchase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}",
+            self.shares, self.market_price, self.purchase_price
+        )?;
+        if let Some(date) = &self.date {
+            write!(f, "@{date}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Keys that must be present in a named record.
+const REQUIRED_KEYS: [&str; 2] = ["salary", "bonus"];
+
+/// Keys of pay by the hour, accepted by [`Record::from_pairs`] instead of `salary` or `salaries`.
+const HOURLY_KEYS: [&str; 3] = ["hourly_rate", "hours", "overtime"];
+
+/// Pay by the hour, for those not on a fixed monthly salary, expanded into the salary of every
+/// month.
+#[derive(Default)]
+struct Hourly {
+    rate: Option<f64>,
+    /// The regular hours of every month, the same in every month if only one is given.
+    hours: Vec<f64>,
+    /// The overtime hours of every month with the multiple of the rate they are paid at, e.g. 1.5
+    /// on workdays, 2 on rest days and 3 on public holidays.
+    overtime: Vec<(f64, f64)>,
+}
+
+impl Hourly {
+    /// Set `hourly_rate`, `hours`, the regular hours of every month or of each month delimited by
+    /// `;`, or `overtime`, every kind of overtime of a month as `hours:multiple` delimited by `;`.
+    fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let number = |s: &str| -> Result<f64> {
+            s.trim()
+                .parse()
+                .with_context(|| format!("invalid value `{value}` for key `{key}`"))
+        };
+        match key {
+            "hourly_rate" => self.rate = Some(number(value)?),
+            "hours" => self.hours = value.split(';').map(number).try_collect()?,
+            _ => {
+                self.overtime = value
+                    .split(';')
+                    .map(|o| -> Result<(f64, f64)> {
+                        let (hours, multiple) = o
+                            .split_once(':')
+                            .ok_or_else(|| anyhow!("expected hours:multiple, got `{o}`"))?;
+                        Ok((number(hours)?, number(multiple)?))
+                    })
+                    .try_collect()?
+            }
+        }
+        Ok(())
+    }
+
+    /// The salary of every month.
+    fn salaries(&self) -> Result<Vec<f64>> {
+        let rate = self
+            .rate
+            .ok_or_else(|| anyhow!("missing key `hourly_rate`"))?;
+        let hours = match self.hours.len() {
+            0 => bail!("missing key `hours`"),
+            1 => vec![self.hours[0]; 12],
+            12 => self.hours.clone(),
+            n => bail!("expected the hours of every month or of 12 months, got {n}"),
+        };
+        let overtime = self.overtime.iter().fold(0.0, |a, (h, m)| a + h * m);
+        Ok(hours.iter().map(|h| rate * (h + overtime)).collect())
+    }
+}
+
+/// Parse a record either in the named format `salary=30000,deduction=5000,bonus=120000` or in the
+/// positional format `monthly_salary,monthly_tax_deduction,year_bonus`.
+pub fn parse_record(arg: &str) -> Result<Record> {
+    if !arg.contains('=') {
+        let tokens: Vec<_> = arg
+            .split(',')
+            .map(|s| s.trim().parse::<f64>())
+            .try_collect()
+            .with_context(|| format!("invalid record `{arg}`"))?;
+        anyhow::ensure!(
+            tokens.len() == 3,
+            "expected 3 comma delimited values (monthly_salary,monthly_tax_deduction,year_bonus), \
+             got {}",
+            tokens.len()
+        );
+        return Ok(Record::new(tokens[0], tokens[1], tokens[2]));
+    }
+    Record::from_pairs(arg.split(',').map(|kv| {
+        kv.split_once('=')
+            .map(|(k, v)| (k.trim(), v.trim()))
+            .ok_or_else(|| anyhow!("expected key=value, got `{kv}`"))
+    }))
+}
+
+/// Parse the records of a batch file in CSV format, one record per line. The name of a record is
+/// taken from its `name` column and defaults to the line number.
+///
+/// If the header line names the columns with record keys (e.g. `name,salary,deduction,bonus`), the
+/// columns are matched by name. Otherwise every line is `[name,]monthly_salary,
+/// monthly_tax_deduction,year_bonus` and a leading header line is skipped. Blank lines and lines
+/// starting with `#` are skipped as well.
+pub fn parse_records(csv: &str) -> Result<Vec<(String, Record)>> {
+    let mut ret = Vec::new();
+    let mut header: Option<Vec<String>> = None;
+    for (i, line) in csv.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<_> = line.split(',').map(str::trim).collect();
+        if let Some(keys) = &header {
+            anyhow::ensure!(
+                keys.len() == fields.len(),
+                "line {}: expected {} fields, got {}",
+                i + 1,
+                keys.len(),
+                fields.len()
+            );
+            let name = keys
+                .iter()
+                .position(|k| k == "name")
+                .map_or_else(|| (i + 1).to_string(), |p| fields[p].to_string());
+            let pairs = keys
+                .iter()
+                .zip(&fields)
+                .filter(|(k, _)| *k != "name")
+                .map(|(k, v)| Ok((k.as_str(), *v)));
+            let r = Record::from_pairs(pairs).with_context(|| format!("line {}", i + 1))?;
+            ret.push((name, r));
+            continue;
+        }
+        if ret.is_empty() && fields.iter().all(|f| *f == "name" || Record::is_key(f)) {
+            header = Some(fields.iter().map(|f| f.to_string()).collect());
+            continue;
+        }
+        let (name, rest) = match fields.len() {
+            3 => ((i + 1).to_string(), fields.join(",")),
+            4 => (fields[0].to_string(), fields[1..].join(",")),
+            n => bail!("line {}: expected 3 or 4 fields, got {n}", i + 1),
+        };
+        match parse_record(&rest) {
+            Ok(r) => ret.push((name, r)),
+            Err(_) if ret.is_empty() && i == 0 => continue,
+            Err(e) => return Err(e.context(format!("line {}", i + 1))),
+        }
+    }
+    Ok(ret)
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.salaries {
+            Some(salaries) => {
+                let salaries: Vec<_> = salaries.iter().map(f64::to_string).collect();
+                write!(f, "salaries={}", salaries.join(";"))?;
+            }
+            None => write!(f, "salary={}", self.monthly_salary)?,
+        }
+        write!(f, ",deduction={}", self.monthly_tax_deduction)?;
+        if self.other_bonuses.is_empty() {
+            write!(f, ",bonus={}", self.year_bonus)?;
+        } else {
+            let bonuses: Vec<_> = std::iter::once(&self.year_bonus)
+                .chain(&self.other_bonuses)
+                .map(f64::to_string)
+                .collect();
+            write!(f, ",bonuses={}", bonuses.join(";"))?;
+        }
+        if let Some(base) = self.social_base {
+            write!(f, ",social_base={base}")?;
+        }
+        if let Some(wages) = self.prior_wages {
+            write!(f, ",prior_wages={wages}")?;
+        }
+        if self.severance != 0.0 {
+            write!(f, ",severance={}", self.severance)?;
+        }
+        for (key, value) in [
+            ("private_pension", self.private_pension),
+            ("annuity", self.annuity),
+            ("employer_annuity", self.employer_annuity),
+            ("supplementary_housing", self.supplementary_housing),
+            ("donations", self.donations),
+            ("qualified_donations", self.qualified_donations),
+            ("health_insurance", self.health_insurance),
+            ("business_expenses", self.business_expenses),
+            ("residence_years", self.residence_years.into()),
+        ] {
+            if value != 0.0 {
+                write!(f, ",{key}={value}")?;
+            }
+        }
+        if !self.equity.is_empty() {
+            let equity: Vec<_> = self.equity.iter().map(Vest::to_string).collect();
+            write!(f, ",equity={}", equity.join(";"))?;
+        }
+        if !self.espp.is_empty() {
+            let espp: Vec<_> = self.espp.iter().map(Purchase::to_string).collect();
+            write!(f, ",espp={}", espp.join(";"))?;
+        }
+        if !self.allowances.is_empty() {
+            let allowances: Vec<_> = self.allowances.iter().map(Allowance::to_string).collect();
+            write!(f, ",allowances={}", allowances.join(";"))?;
+        }
+        if !self.foreign_income.is_empty() {
+            let foreign: Vec<_> = self.foreign_income.iter().map(|s| s.to_string()).collect();
+            write!(f, ",foreign_income={}", foreign.join(";"))?;
+        }
+        for (key, payments) in [
+            ("labor_income", &self.labor_income),
+            ("author_income", &self.author_income),
+            ("royalty_income", &self.royalty_income),
+            ("rental_income", &self.rental_income),
+            ("invoices", &self.invoices),
+        ] {
+            if !payments.is_empty() {
+                let payments: Vec<_> = payments.iter().map(f64::to_string).collect();
+                write!(f, ",{key}={}", payments.join(";"))?;
+            }
+        }
+        for (key, value) in self.special.items() {
+            write!(f, ",{key}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::str::FromStr for Record {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        parse_record(s)
+    }
+}
+
+impl Record {
+    pub fn new(monthly_salary: f64, monthly_tax_deduction: f64, year_bonus: f64) -> Self {
+        Self {
+            monthly_salary,
+            monthly_tax_deduction,
+            year_bonus,
+            movement: 0.0,
+            special: Default::default(),
+            social_base: None,
+            salaries: None,
+            other_bonuses: Vec::new(),
+            allowances: Vec::new(),
+            severance: 0.0,
+            equity: Vec::new(),
+            espp: Vec::new(),
+            labor_income: Vec::new(),
+            author_income: Vec::new(),
+            royalty_income: Vec::new(),
+            rental_income: Vec::new(),
+            invoices: Vec::new(),
+            business_expenses: 0.0,
+            private_pension: 0.0,
+            annuity: 0.0,
+            employer_annuity: 0.0,
+            supplementary_housing: 0.0,
+            donations: 0.0,
+            qualified_donations: 0.0,
+            health_insurance: 0.0,
+            foreign_income: Vec::new(),
+            residence_years: 0,
+            prior_wages: None,
+        }
+    }
+
+    /// Build a record from `(key, value)` pairs. See [`Record::set`] for the accepted keys.
+    /// Instead of `salary` or `salaries`, the pay may be given by the hour with `hourly_rate`,
+    /// `hours`, the regular hours of every month or of each month delimited by `;`, and
+    /// `overtime`, every kind of overtime of a month as `hours:multiple` delimited by `;`, e.g.
+    /// `hourly_rate=150,hours=168,overtime=20:1.5;8:2`.
+    pub fn from_pairs<'a>(
+        pairs: impl IntoIterator<Item = Result<(&'a str, &'a str)>>,
+    ) -> Result<Self> {
+        let mut ret = Self::new(0.0, 0.0, 0.0);
+        let mut hourly = Hourly::default();
+        let mut seen = Vec::new();
+        for pair in pairs {
+            let (key, value) = pair?;
+            match HOURLY_KEYS.contains(&key) {
+                true => hourly.set(key, value)?,
+                false => ret.set(key, value)?,
+            }
+            seen.push(Self::canonical_key(key).unwrap_or(key));
+        }
+        anyhow::ensure!(
+            !(seen.contains(&"salary") && seen.contains(&"salaries")),
+            "salary and salaries cannot be given together"
+        );
+        if HOURLY_KEYS.iter().any(|k| seen.contains(k)) {
+            anyhow::ensure!(
+                !(seen.contains(&"salary") || seen.contains(&"salaries")),
+                "the hourly pay cannot be given together with salary or salaries"
+            );
+            let salaries = hourly.salaries()?;
+            ret.monthly_salary = salaries.iter().sum::<f64>() / 12.0;
+            ret.salaries = Some(salaries);
+            seen.push("salaries");
+        }
+        anyhow::ensure!(
+            !(seen.contains(&"bonus") && seen.contains(&"bonuses")),
+            "bonus and bonuses cannot be given together"
+        );
+        for key in REQUIRED_KEYS {
+            let alias = match key {
+                "salary" => "salaries",
+                _ => "bonuses",
+            };
+            anyhow::ensure!(
+                seen.contains(&key) || seen.contains(&alias),
+                "missing key `{key}`"
+            );
+        }
+        Ok(ret)
+    }
+
+    /// Set a field by its key: `salary`, `deduction`, `bonus`, `social_base`, `salaries`,
+    /// `bonuses`, `allowances`, `severance`, `equity`, `espp`, `labor_income`, `author_income`,
+    /// `royalty_income`, `rental_income`, `invoices`, `business_expenses`, `private_pension`,
+    /// `annuity`, `employer_annuity`, `supplementary_housing`, `donations`, `qualified_donations`,
+    /// `health_insurance`, `foreign_income`, `residence_years`, `prior_wages` or one of the special
+    /// additional deductions, e.g. `children_education`. `salaries` takes the 12 monthly salaries
+    /// delimited by `;`, `bonuses` every bonus of the year, the first of which is taxed separately,
+    /// `allowances` every monthly allowance as `name:amount`, `equity` every exercise or vesting as
+    /// `amount` or `amount@YYYY-MM-DD`, `espp` every purchase as
+    /// `shares:market_price:purchase_price` or `shares:market_price:purchase_price@YYYY-MM-DD`, the
+    /// remuneration keys every payment, `rental_income` the rent of every month, `invoices` the
+    /// revenue invoiced in every month and `foreign_income` every country as `income` or
+    /// `country:income[:tax_paid[:carryforward]]`. The field names of [`Record`] are accepted as
+    /// aliases.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<()> {
+        let canonical = Self::canonical_key(key).ok_or_else(|| anyhow!("unknown key `{key}`"))?;
+        let list = || -> Result<Vec<f64>> {
+            value
+                .split(';')
+                .map(|s| s.trim().parse::<f64>())
+                .try_collect()
+                .with_context(|| format!("invalid value `{value}` for key `{key}`"))
+        };
+        if canonical == "bonuses" {
+            let mut bonuses = list()?;
+            self.year_bonus = bonuses.remove(0);
+            self.other_bonuses = bonuses;
+            return Ok(());
+        }
+        if canonical == "equity" {
+            self.equity = value.split(';').map(Vest::parse).try_collect()?;
+            return Ok(());
+        }
+        if canonical == "espp" {
+            self.espp = value.split(';').map(Purchase::parse).try_collect()?;
+            return Ok(());
+        }
+        let payments = match canonical {
+            "labor_income" => Some(&mut self.labor_income),
+            "author_income" => Some(&mut self.author_income),
+            "royalty_income" => Some(&mut self.royalty_income),
+            "rental_income" => Some(&mut self.rental_income),
+            "invoices" => Some(&mut self.invoices),
+            _ => None,
+        };
+        if let Some(payments) = payments {
+            *payments = list()?;
+            return Ok(());
+        }
+        if canonical == "salaries" {
+            let salaries = list()?;
+            anyhow::ensure!(
+                salaries.len() == 12,
+                "expected 12 monthly salaries, got {}",
+                salaries.len()
+            );
+            self.monthly_salary = salaries.iter().sum::<f64>() / 12.0;
+            self.salaries = Some(salaries);
+            return Ok(());
+        }
+        if canonical == "allowances" {
+            self.allowances = value.split(';').map(Allowance::parse).try_collect()?;
+            return Ok(());
+        }
+        if canonical == "foreign_income" {
+            self.foreign_income = value.split(';').map(ForeignSource::parse).try_collect()?;
+            return Ok(());
+        }
+        if canonical == "residence_years" {
+            self.residence_years = value
+                .parse()
+                .with_context(|| format!("invalid value `{value}` for key `{key}`"))?;
+            return Ok(());
+        }
+        let value = value
+            .parse::<f64>()
+            .with_context(|| format!("invalid value `{value}` for key `{key}`"))?;
+        match canonical {
+            "salary" => {
+                self.monthly_salary = value;
+                self.salaries = None;
+            }
+            "deduction" => self.monthly_tax_deduction = value,
+            "bonus" => {
+                self.year_bonus = value;
+                self.other_bonuses.clear();
+            }
+            "social_base" => self.social_base = Some(value),
+            "prior_wages" => self.prior_wages = Some(value),
+            "severance" => self.severance = value,
+            "private_pension" => self.private_pension = value,
+            "annuity" => self.annuity = value,
+            "employer_annuity" => self.employer_annuity = value,
+            "supplementary_housing" => self.supplementary_housing = value,
+            "donations" => self.donations = value,
+            "qualified_donations" => self.qualified_donations = value,
+            "health_insurance" => self.health_insurance = value,
+            "business_expenses" => self.business_expenses = value,
+            key => *self.special.get_mut(key).unwrap() = value,
+        }
+        Ok(())
+    }
+
+    /// Whether `key` is accepted by [`Record::from_pairs`].
+    pub fn is_key(key: &str) -> bool {
+        Self::canonical_key(key).is_some() || HOURLY_KEYS.contains(&key)
+    }
+
+    fn canonical_key(key: &str) -> Option<&'static str> {
+        Some(match key {
+            "salary" | "monthly_salary" => "salary",
+            "deduction" | "monthly_tax_deduction" => "deduction",
+            "bonus" | "year_bonus" => "bonus",
+            "social_base" => "social_base",
+            "prior_wages" => "prior_wages",
+            "salaries" => "salaries",
+            "bonuses" => "bonuses",
+            "allowances" => "allowances",
+            "severance" => "severance",
+            "equity" => "equity",
+            "espp" => "espp",
+            "labor_income" => "labor_income",
+            "author_income" => "author_income",
+            "royalty_income" => "royalty_income",
+            "rental_income" => "rental_income",
+            "invoices" => "invoices",
+            "private_pension" => "private_pension",
+            "annuity" => "annuity",
+            "employer_annuity" => "employer_annuity",
+            "supplementary_housing" => "supplementary_housing",
+            "donations" => "donations",
+            "qualified_donations" => "qualified_donations",
+            "health_insurance" => "health_insurance",
+            "business_expenses" => "business_expenses",
+            "foreign_income" => "foreign_income",
+            "residence_years" => "residence_years",
+            key => return deduction::KEYS.into_iter().find(|k| *k == key),
+        })
+    }
+
+    /// The salary of `month`, 1 to 12, excluding the movement.
+    pub fn salary_of(&self, month: u32) -> f64 {
+        self.salaries
+            .as_ref()
+            .map_or(self.monthly_salary, |s| s[month as usize - 1])
+    }
+
+    /// The total income from outside China.
+    pub fn foreign_income(&self) -> f64 {
+        self.foreign_income.iter().fold(0.0, |a, s| a + s.income)
+    }
+
+    /// The sum of the bonuses that are taxed together with salary.
+    pub fn other_bonus(&self) -> f64 {
+        self.other_bonuses.iter().fold(0.0, |a, b| a + b)
+    }
+
+    /// The total equity income of the year, the ESPP discounts included.
+    pub fn equity_income(&self) -> f64 {
+        self.equity_payments()
+            .fold(0.0, |a, (_, amount)| a + amount)
+    }
+
+    /// Every payment of equity income, the ESPP discounts included, with its month if dated.
+    pub fn equity_payments(&self) -> impl Iterator<Item = (Option<u32>, f64)> + '_ {
+        self.equity
+            .iter()
+            .map(|v| (v.month(), v.amount))
+            .chain(self.espp.iter().map(|p| (p.month(), p.discount())))
+    }
+
+    /// The payments of each kind of remuneration.
+    pub fn remuneration(&self) -> impl Iterator<Item = (Remuneration, &[f64])> {
+        [
+            (Remuneration::Labor, self.labor_income.as_slice()),
+            (Remuneration::Author, self.author_income.as_slice()),
+            (Remuneration::Royalty, self.royalty_income.as_slice()),
+        ]
+        .into_iter()
+    }
+
+    /// A copy of the record that taxes its `i`th other bonus separately instead of the year bonus.
+    pub fn with_separate_bonus(&self, i: usize) -> Self {
+        let mut r = self.clone();
+        std::mem::swap(&mut r.year_bonus, &mut r.other_bonuses[i]);
+        r
+    }
+
+    /// The monthly social insurance contribution base.
+    pub fn social_base(&self) -> f64 {
+        self.social_base.unwrap_or(self.monthly_salary)
+    }
+
+    /// The record of the previous year, with the same deductions but paid `prior_wages` as salary,
+    /// or the record itself if they are not given.
+    pub fn prior_year(&self) -> Self {
+        let mut r = self.clone();
+        if let Some(wages) = self.prior_wages {
+            r.monthly_salary = wages / 12.0;
+            r.salaries = None;
+            r.year_bonus = 0.0;
+            r.other_bonuses.clear();
+            r.movement = 0.0;
+        }
+        r
+    }
+
+    /// A copy of the record with `movement` moved from its year bonus into salary, see
+    /// [`Self::adjust`].
+    pub fn with_movement(&self, config: &TaxConfig, movement: f64) -> Result<Self> {
+        let mut r = self.clone();
+        if movement != 0.0 {
+            r.adjust(config, movement)?;
+        }
+        Ok(r)
+    }
+
+    /// Move at most `budget` from the year bonus into salary. A negative budget moves salary into
+    /// the year bonus instead, at most the whole salary paid in the months employed under
+    /// `config`.
+    pub fn adjust(&mut self, config: &TaxConfig, budget: f64) -> Result<()> {
+        let budget = if budget < 0.0 {
+            budget.max(-(config.annual_salary(self) + self.movement))
+        } else {
+            self.year_bonus.min(budget)
+        };
+        anyhow::ensure!(budget != 0.0, "budget is invalid");
+        self.year_bonus -= budget;
+        self.movement += budget;
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::{header, StatusCode};
+use serde::Serialize;
+
+use crate::config::{parse_config, ConfigFormat};
+use crate::TaxConfig;
+
+/// Where the text of a config fetched by [`read_remote_config`] came from.
+#[derive(Debug)]
+pub enum Fetched {
+    /// Downloaded, as it was new or changed.
+    Downloaded,
+    /// The cached copy, which the server said is still current.
+    NotModified,
+    /// The cached copy, as the server could not be reached for the reason given.
+    Offline(anyhow::Error),
+}
+
+/// The directory fetched configs are cached in: pto in the user cache directory of XDG, which
+/// defaults to ~/.cache.
+fn cache_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        // Relative paths are invalid in XDG_CACHE_HOME and are ignored.
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".cache")))
+        .map(|dir| dir.join("pto"))
+}
+
+/// The file the config at `url` is cached in, named after the URL. Its ETag is kept next to it
+/// with the extension `.etag`.
+fn cache_file(url: &str) -> Option<PathBuf> {
+    let name: String = url
+        .split_once("://")
+        .map_or(url, |(_, rest)| rest)
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    cache_dir().map(|dir| dir.join(name))
+}
+
+fn etag_file(cache: &Path) -> PathBuf {
+    let mut ret = cache.as_os_str().to_owned();
+    ret.push(".etag");
+    ret.into()
+}
+
+/// Fetch the text of the config at `url`, asking the server only for a newer one than the
+/// cached copy and falling back to that copy when offline.
+async fn fetch(url: &str) -> Result<(String, Fetched)> {
+    let cache = cache_file(url);
+    let cached = match &cache {
+        Some(cache) => tokio::fs::read_to_string(cache).await.ok(),
+        None => None,
+    };
+    let etag = match (&cache, &cached) {
+        (Some(cache), Some(_)) => tokio::fs::read_to_string(etag_file(cache)).await.ok(),
+        _ => None,
+    };
+    let client = reqwest::Client::builder()
+        .timeout(Duration::from_secs(10))
+        .build()?;
+    let mut request = client.get(url);
+    if let Some(etag) = &etag {
+        request = request.header(header::IF_NONE_MATCH, etag.trim());
+    }
+    let response = match request.send().await {
+        Ok(response) => response,
+        Err(e) => match cached {
+            Some(text) => return Ok((text, Fetched::Offline(e.into()))),
+            None => return Err(e).with_context(|| format!("cannot fetch {url}")),
+        },
+    };
+    match (response.status(), cached) {
+        (StatusCode::NOT_MODIFIED, Some(text)) => return Ok((text, Fetched::NotModified)),
+        (status, _) if !status.is_success() => bail!("cannot fetch {url}: {status}"),
+        _ => {}
+    }
+    let etag = response
+        .headers()
+        .get(header::ETAG)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string);
+    let text = response
+        .text()
+        .await
+        .with_context(|| format!("cannot fetch {url}"))?;
+    // The config works without the cache, so failing to write it is no error.
+    if let Some(cache) = cache {
+        if let Some(dir) = cache.parent() {
+            let _ = tokio::fs::create_dir_all(dir).await;
+        }
+        let _ = tokio::fs::write(&cache, &text).await;
+        let _ = match etag {
+            Some(etag) => tokio::fs::write(etag_file(&cache), etag).await,
+            None => tokio::fs::remove_file(etag_file(&cache)).await,
+        };
+    }
+    Ok((text, Fetched::Downloaded))
+}
+
+/// Parse an ed25519 public key trusted to sign configs, encoded in base64.
+pub fn parse_trusted_key(s: &str) -> Result<VerifyingKey> {
+    let bytes = BASE64
+        .decode(s.trim())
+        .map_err(|e| anyhow!("invalid trusted key: {e}"))?;
+    let bytes = <[u8; 32]>::try_from(bytes)
+        .map_err(|_| anyhow!("invalid trusted key: not 32 bytes of an ed25519 key"))?;
+    VerifyingKey::from_bytes(&bytes).map_err(|e| anyhow!("invalid trusted key: {e}"))
+}
+
+/// Check that `text`, fetched from `url`, is signed by one of `keys` with the base64 ed25519
+/// signature in `signature`.
+fn verify(url: &str, text: &str, signature: &str, keys: &[VerifyingKey]) -> Result<()> {
+    let signature = BASE64
+        .decode(signature.trim())
+        .ok()
+        .and_then(|bytes| <[u8; 64]>::try_from(bytes).ok())
+        .ok_or_else(|| anyhow!("{url}.sig is not a base64 ed25519 signature"))?;
+    let signature = Signature::from_bytes(&signature);
+    if !keys
+        .iter()
+        .any(|key| key.verify_strict(text.as_bytes(), &signature).is_ok())
+    {
+        bail!("{url} is not signed by a trusted key, it may have been tampered with");
+    }
+    Ok(())
+}
+
+/// [`fetch`] the text at `url`, which must be signed by one of `trusted_keys` if any are given.
+async fn fetch_verified(url: &str, trusted_keys: &[VerifyingKey]) -> Result<(String, Fetched)> {
+    let (text, fetched) = fetch(url).await?;
+    if !trusted_keys.is_empty() {
+        let (signature, _) = fetch(&format!("{url}.sig"))
+            .await
+            .with_context(|| format!("{url} is not signed"))?;
+        verify(url, &text, &signature, trusted_keys)?;
+    }
+    Ok((text, fetched))
+}
+
+/// Read the config at an HTTPS `url`, e.g. tables maintained centrally by a team, in `format` or
+/// the one of its extension. The config is cached under the user cache directory and only
+/// downloaded again when its ETag changes; the cached copy is used when offline. A remote config
+/// cannot include others.
+///
+/// If `trusted_keys` are given, the config must be signed by one of them, with its signature at
+/// the same URL plus `.sig`; unsigned or tampered configs are refused, cached ones included.
+pub async fn read_remote_config(
+    url: &str,
+    format: Option<ConfigFormat>,
+    trusted_keys: &[VerifyingKey],
+) -> Result<(toml::Table, Fetched)> {
+    let (text, fetched) = fetch_verified(url, trusted_keys).await?;
+    let format = format.unwrap_or_else(|| ConfigFormat::from_path(Path::new(url)));
+    let tbl = parse_config(&text, format).with_context(|| format!("in {url}"))?;
+    if tbl.contains_key("include") {
+        bail!("{url}: a remote config cannot include others");
+    }
+    Ok((tbl, fetched))
+}
+
+/// The directory `pto update-tables` downloads tables to: pto/tables in the user config directory
+/// of XDG, which defaults to ~/.config. The tables there are regions like the built-in ones, and
+/// replace a built-in region of the same name.
+///
+/// The tables are checked when downloaded and trusted from then on, so on Unix the directory and
+/// its tables must only be writable by their owner, as [`update_tables`] leaves them; any other
+/// may have been tampered with and is refused.
+pub fn tables_dir() -> Option<PathBuf> {
+    std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        // Relative paths are invalid in XDG_CONFIG_HOME and are ignored.
+        .filter(|p| p.is_absolute())
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+        .map(|dir| dir.join("pto").join("tables"))
+}
+
+/// Unix permissions that only let the owner write, of [`tables_dir`] and the tables in it.
+#[cfg(unix)]
+const WRITABLE_BY_OTHERS: u32 = 0o022;
+
+/// Check that `path`, [`tables_dir`] or a table in it, can only be written by its owner.
+fn check_trusted(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(path)
+            .with_context(|| format!("cannot read {}", path.display()))?
+            .permissions()
+            .mode();
+        anyhow::ensure!(
+            mode & WRITABLE_BY_OTHERS == 0,
+            "{} is writable by others than its owner and may have been tampered with, run \
+             `chmod go-w` on it if it is not",
+            path.display()
+        );
+    }
+    Ok(())
+}
+
+/// Let only the owner write `path`, see [`tables_dir`].
+async fn restrict(path: &Path) -> Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut permissions = tokio::fs::metadata(path).await?.permissions();
+        permissions.set_mode(permissions.mode() & !WRITABLE_BY_OTHERS);
+        tokio::fs::set_permissions(path, permissions)
+            .await
+            .with_context(|| format!("cannot restrict {}", path.display()))?;
+    }
+    Ok(())
+}
+
+/// The tables of the region `name` downloaded by `pto update-tables`, if any, once checked to be
+/// trusted, see [`tables_dir`].
+pub(crate) fn downloaded_region(name: &str) -> Result<Option<String>> {
+    let Some(dir) = tables_dir() else {
+        return Ok(None);
+    };
+    if !name
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+    {
+        return Ok(None);
+    }
+    let path = dir.join(format!("{name}.toml"));
+    if !path.exists() {
+        return Ok(None);
+    }
+    check_trusted(&dir)?;
+    check_trusted(&path)?;
+    let preset = std::fs::read_to_string(&path)
+        .with_context(|| format!("cannot read {}", path.display()))?;
+    Ok(Some(preset))
+}
+
+/// A region of the index of `pto update-tables`.
+#[derive(Debug, Serialize)]
+pub struct TableUpdate {
+    pub region: String,
+    /// The version downloaded before, if any.
+    pub old: Option<String>,
+    pub new: String,
+}
+
+impl TableUpdate {
+    pub fn changed(&self) -> bool {
+        self.old.as_ref() != Some(&self.new)
+    }
+}
+
+/// The versions of the regions in an index, e.g.
+///
+/// ```toml
+/// [tables.cn-2026]
+/// version = "2026.1"
+/// url = "cn-2026.toml"    # relative to the index
+/// ```
+fn parse_index(text: &str) -> Result<BTreeMap<String, (String, String)>> {
+    let tbl: toml::Table = toml::from_str(text)?;
+    let mut ret = BTreeMap::new();
+    for (key, value) in tbl {
+        let "tables" = key.as_str() else {
+            bail!("unknown index key `{key}`");
+        };
+        let tables = value
+            .as_table()
+            .ok_or_else(|| anyhow!("tables is not a table"))?;
+        for (region, table) in tables {
+            anyhow::ensure!(
+                !region.is_empty()
+                    && region
+                        .chars()
+                        .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_'),
+                "invalid region name `{region}`"
+            );
+            let get = |key: &str| -> Result<String> {
+                table
+                    .get(key)
+                    .and_then(|v| v.as_str())
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow!("tables.{region}.{key} is not a string"))
+            };
+            ret.insert(region.clone(), (get("version")?, get("url")?));
+        }
+    }
+    Ok(ret)
+}
+
+/// Check the index of tax tables at `index_url` and download the regions that are new or have a
+/// new version into [`tables_dir`], each checked to be a valid config and, if `trusted_keys` are
+/// given, signed like [`read_remote_config`] requires. Returns every region of the index, with
+/// its version before and after.
+pub async fn update_tables(
+    index_url: &str,
+    trusted_keys: &[VerifyingKey],
+) -> Result<Vec<TableUpdate>> {
+    let dir = tables_dir().ok_or_else(|| anyhow!("no config directory, HOME is not set"))?;
+    let (index, fetched) = fetch_verified(index_url, trusted_keys).await?;
+    if let Fetched::Offline(e) = fetched {
+        return Err(e).with_context(|| format!("cannot fetch {index_url}"));
+    }
+    let index = parse_index(&index).with_context(|| format!("in {index_url}"))?;
+    let local = dir.join("index.toml");
+    let mut installed = match tokio::fs::read_to_string(&local).await {
+        Ok(text) => parse_index(&text).with_context(|| format!("in {}", local.display()))?,
+        Err(_) => BTreeMap::new(),
+    };
+    let base = reqwest::Url::parse(index_url)?;
+    tokio::fs::create_dir_all(&dir)
+        .await
+        .with_context(|| format!("cannot create {}", dir.display()))?;
+    restrict(&dir).await?;
+    let mut ret = Vec::new();
+    for (region, (version, url)) in index {
+        let update = TableUpdate {
+            region: region.clone(),
+            old: installed.get(&region).map(|(version, _)| version.clone()),
+            new: version.clone(),
+        };
+        if update.changed() {
+            let url = base.join(&url)?;
+            anyhow::ensure!(url.scheme() == "https", "{url} is not an https:// URL");
+            let (text, _) = fetch_verified(url.as_str(), trusted_keys).await?;
+            TaxConfig::with_overrides(parse_config(&text, ConfigFormat::Toml)?)
+                .with_context(|| format!("in {url}"))?;
+            let path = dir.join(format!("{region}.toml"));
+            tokio::fs::write(&path, text)
+                .await
+                .with_context(|| format!("cannot write {}", path.display()))?;
+            restrict(&path).await?;
+            installed.insert(region, (version, url.to_string()));
+            let mut tables = toml::Table::new();
+            for (region, (version, url)) in &installed {
+                let mut table = toml::Table::new();
+                table.insert("version".to_string(), version.clone().into());
+                table.insert("url".to_string(), url.clone().into());
+                tables.insert(region.clone(), table.into());
+            }
+            let mut index = toml::Table::new();
+            index.insert("tables".to_string(), tables.into());
+            tokio::fs::write(&local, toml::to_string(&index)?)
+                .await
+                .with_context(|| format!("cannot write {}", local.display()))?;
+        }
+        ret.push(update);
+    }
+    Ok(ret)
+}
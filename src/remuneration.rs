@@ -0,0 +1,91 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::Table;
+
+/// The kinds of remuneration that are withheld payment by payment, then merged into the
+/// comprehensive income at the annual reconciliation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Remuneration {
+    /// Labor remuneration (劳务报酬), e.g. freelance or consulting fees.
+    Labor,
+    /// Author remuneration (稿酬) for published works.
+    Author,
+    /// Royalties (特许权使用费), e.g. for patents or copyrights.
+    Royalty,
+}
+
+/// Rules of the [`Remuneration`] kinds.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RemunerationRules {
+    /// Payments up to this amount deduct `fixed_expense`, larger ones `expense_ratio` of it.
+    pub expense_threshold: f64,
+    pub fixed_expense: f64,
+    pub expense_ratio: f64,
+    /// The part of author remuneration that is taxed after the expense.
+    pub author_inclusion: f64,
+    /// The ratio withheld from author remuneration and royalties.
+    pub withholding_ratio: f64,
+    /// The table withholding labor remuneration (预扣率表).
+    pub labor_withholding: Table,
+}
+
+impl TryFrom<&toml::Table> for RemunerationRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "labor_withholding" => {
+                    ret.labor_withholding = Table::parse("remuneration.labor_withholding", value)?;
+                    continue;
+                }
+                "expense_threshold" => &mut ret.expense_threshold,
+                "fixed_expense" => &mut ret.fixed_expense,
+                "expense_ratio" => &mut ret.expense_ratio,
+                "author_inclusion" => &mut ret.author_inclusion,
+                "withholding_ratio" => &mut ret.withholding_ratio,
+                _ => bail!("unknown remuneration key `{key}`"),
+            };
+            *field = number(value).ok_or_else(|| anyhow!("remuneration.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+impl RemunerationRules {
+    fn inclusion(&self, kind: Remuneration) -> f64 {
+        match kind {
+            Remuneration::Author => self.author_inclusion,
+            Remuneration::Labor | Remuneration::Royalty => 1.0,
+        }
+    }
+
+    /// The taxable part of `payment` when it is withheld.
+    pub fn taxable(&self, kind: Remuneration, payment: f64) -> f64 {
+        let income = if payment <= self.expense_threshold {
+            0f64.max(payment - self.fixed_expense)
+        } else {
+            payment * (1.0 - self.expense_ratio)
+        };
+        income * self.inclusion(kind)
+    }
+
+    /// The tax withheld from `payment`.
+    pub fn withholding(&self, kind: Remuneration, payment: f64) -> Result<f64> {
+        let taxable = self.taxable(kind, payment);
+        match kind {
+            Remuneration::Labor => self.labor_withholding.tax(taxable),
+            Remuneration::Author | Remuneration::Royalty => Ok(taxable * self.withholding_ratio),
+        }
+    }
+
+    /// The part of the `payments` merged into the comprehensive income at the reconciliation,
+    /// where the expense is always `expense_ratio`.
+    pub fn annual(&self, kind: Remuneration, payments: &[f64]) -> f64 {
+        payments.iter().fold(0.0, |a, p| a + p) * (1.0 - self.expense_ratio) * self.inclusion(kind)
+    }
+}
@@ -0,0 +1,47 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+
+/// Rules of rental income (财产租赁所得), taxed month by month on its own, apart from the
+/// comprehensive income.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct RentalRules {
+    /// Monthly rents up to this amount deduct `fixed_expense`, larger ones `expense_ratio` of it.
+    pub expense_threshold: f64,
+    pub fixed_expense: f64,
+    pub expense_ratio: f64,
+    /// The ratio applied to the rent after the expense.
+    pub ratio: f64,
+}
+
+impl TryFrom<&toml::Table> for RentalRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "expense_threshold" => &mut ret.expense_threshold,
+                "fixed_expense" => &mut ret.fixed_expense,
+                "expense_ratio" => &mut ret.expense_ratio,
+                "ratio" => &mut ret.ratio,
+                _ => bail!("unknown rental key `{key}`"),
+            };
+            *field = number(value).ok_or_else(|| anyhow!("rental.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+impl RentalRules {
+    /// The tax for the rent of one month.
+    pub fn tax(&self, rent: f64) -> f64 {
+        let taxable = if rent <= self.expense_threshold {
+            0f64.max(rent - self.fixed_expense)
+        } else {
+            rent * (1.0 - self.expense_ratio)
+        };
+        taxable * self.ratio
+    }
+}
@@ -0,0 +1,174 @@
+use std::ops::RangeInclusive;
+
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::{Record, RemunerationRules, Slice, Table, TaxConfig};
+
+/// Whether the taxpayer is a tax resident of the year, i.e. lives in China for at least 183 days.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResidentStatus {
+    /// Taxed on the annual comprehensive income.
+    #[default]
+    Resident,
+    /// Taxed month by month, without the annual deductions.
+    NonResident,
+}
+
+impl std::str::FromStr for ResidentStatus {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "resident" => Self::Resident,
+            "non_resident" | "non-resident" => Self::NonResident,
+            _ => bail!("unknown resident status `{s}`, expected resident or non_resident"),
+        })
+    }
+}
+
+impl std::fmt::Display for ResidentStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Resident => "resident",
+            Self::NonResident => "non_resident",
+        })
+    }
+}
+
+/// Rules for non-residents. Salary is taxed month by month after a fixed deduction, and a bonus
+/// is spread over a number of months in the month it is paid.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct NonResidentRules {
+    /// The deduction from the salary of each month.
+    pub deduction: f64,
+    /// The number of months a bonus is spread over.
+    pub bonus_months: f64,
+    /// The monthly table.
+    pub table: Table,
+}
+
+impl TryFrom<&toml::Table> for NonResidentRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "table" => {
+                    ret.table = Table::parse("non_resident.table", value)?;
+                    continue;
+                }
+                "deduction" => &mut ret.deduction,
+                "bonus_months" => &mut ret.bonus_months,
+                _ => bail!("unknown non_resident key `{key}`"),
+            };
+            *field = number(value).ok_or_else(|| anyhow!("non_resident.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+impl NonResidentRules {
+    /// The taxable salary of `month` of the months employed, with its share of the movement. The
+    /// other bonuses are paid in the last month.
+    pub(crate) fn taxable(&self, r: &Record, month: u32, months: &RangeInclusive<u32>) -> f64 {
+        let other = if month == *months.end() {
+            r.other_bonus()
+        } else {
+            0.0
+        };
+        r.salary_of(month) + r.movement / months.clone().count() as f64 + other - self.deduction
+    }
+
+    /// The movements at which the salary of a month employed or the bonus share crosses a
+    /// bracket bound or zero.
+    pub(crate) fn crossings(&self, r: &Record, months: RangeInclusive<u32>) -> Vec<f64> {
+        let n = months.clone().count() as f64;
+        let mut ret = Vec::new();
+        for b in self.table.bounds().chain([0.0]) {
+            for month in months.clone() {
+                ret.push(r.movement + n * (b - self.taxable(r, month, &months)));
+            }
+            ret.push(r.year_bonus - self.bonus_months * b);
+        }
+        ret
+    }
+
+    /// The annual tax for the salary of the `months` employed and the remuneration, with the
+    /// marginal ratio of the salary in the last month. Each payment of remuneration is taxed on its
+    /// own with the monthly table.
+    pub fn salary_tax(
+        &self,
+        r: &Record,
+        months: RangeInclusive<u32>,
+        remuneration: &RemunerationRules,
+    ) -> Result<(f64, f64)> {
+        let (mut tax, mut ratio) = (0.0, 0.0);
+        for month in months.clone() {
+            let slices = self
+                .table
+                .slices(0f64.max(self.taxable(r, month, &months)))
+                .with_context(|| format!("month {month}"))?;
+            tax += slices.iter().fold(0.0, |a, s| a + s.tax);
+            ratio = slices.last().map_or(0.0, |s| s.ratio);
+        }
+        for (kind, payments) in r.remuneration() {
+            for p in payments {
+                tax += self.table.tax(remuneration.taxable(kind, *p))?;
+            }
+        }
+        Ok((tax, ratio))
+    }
+
+    /// The bracket hit by a bonus of `amount` and its tax, the tax of its monthly share times the
+    /// number of months.
+    pub fn bonus_slice(&self, amount: f64) -> Result<Slice> {
+        let share = amount / self.bonus_months;
+        let slices = self.table.slices(share)?;
+        let last = slices
+            .last()
+            .ok_or_else(|| anyhow!("no bracket for {share}"))?;
+        Ok(Slice {
+            amount,
+            tax: slices.iter().fold(0.0, |a, s| a + s.tax) * self.bonus_months,
+            ..last.clone()
+        })
+    }
+}
+
+/// Whether foreign income is taxed in China this year under the six-year rule.
+#[derive(Clone, Debug, Serialize)]
+pub struct ForeignIncome {
+    pub amount: f64,
+    /// The part taxed in China.
+    pub taxable: f64,
+    /// The exempt years left after this one, while the residence stays unbroken.
+    pub exempt_years_left: Option<u32>,
+}
+
+impl std::fmt::Display for ForeignIncome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:.2}, ", self.amount)?;
+        match self.exempt_years_left {
+            None if self.taxable == 0.0 => write!(f, "not taxed for non-residents"),
+            None => write!(f, "taxable after the exempt years of the six-year rule"),
+            Some(0) => write!(f, "exempt, taxable from next year"),
+            Some(n) => write!(f, "exempt, taxable in {} years", n + 1),
+        }
+    }
+}
+
+/// Apply the six-year rule to the foreign income of `record`.
+pub fn foreign_income(config: &TaxConfig, record: &Record) -> ForeignIncome {
+    let taxable = config.taxable_foreign_income(record);
+    let resident = config.resident_status == ResidentStatus::Resident;
+    ForeignIncome {
+        amount: record.foreign_income(),
+        taxable,
+        exempt_years_left: (resident && record.residence_years < config.foreign_exemption_years)
+            .then(|| config.foreign_exemption_years - record.residence_years - 1),
+    }
+}
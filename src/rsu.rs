@@ -0,0 +1,94 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{Record, Tax, TaxConfig, Vest};
+
+/// A vesting of RSUs (限制性股票单位) on a schedule.
+#[derive(Clone, Debug, Serialize)]
+pub struct RsuVest {
+    pub shares: f64,
+    /// The date of the vesting, `YYYY-MM-DD`.
+    pub date: String,
+}
+
+/// Parse a vesting as `shares@YYYY-MM-DD`.
+pub fn parse_rsu_vest(s: &str) -> Result<RsuVest> {
+    let vest = Vest::parse(s)?;
+    anyhow::ensure!(vest.amount > 0.0, "the shares vested must be positive");
+    Ok(RsuVest {
+        shares: vest.amount,
+        date: vest
+            .date
+            .ok_or_else(|| anyhow::anyhow!("expected shares@YYYY-MM-DD, got `{s}`"))?,
+    })
+}
+
+/// The tax withheld from a vesting and the shares sold to cover it.
+#[derive(Clone, Debug, Serialize)]
+pub struct RsuVestEvent {
+    pub date: String,
+    pub shares: f64,
+    /// The taxable income, the market value of the shares.
+    pub income: f64,
+    /// The tax withheld, cumulative over the vestings of the year like salary.
+    pub tax: f64,
+    /// The whole shares sold to cover the tax.
+    pub shares_sold: f64,
+    pub shares_kept: f64,
+    /// What the shares sold bring above the tax, paid out in cash.
+    pub cash: f64,
+}
+
+/// The result of [`rsu`].
+#[derive(Clone, Debug, Serialize)]
+pub struct RsuPlan {
+    /// Every vesting, by date.
+    pub events: Vec<RsuVestEvent>,
+    /// The record with the vestings as its equity income.
+    pub record: Record,
+    /// The tax of the year with the equity income.
+    pub tax: Tax,
+}
+
+/// Compute the tax withheld from every vesting of `vests` at the share `price` and the shares
+/// sold to cover it, and the tax of the year of `record` with the vestings as its equity income,
+/// which replace the equity of the record.
+pub fn rsu(config: &TaxConfig, record: &Record, vests: &[RsuVest], price: f64) -> Result<RsuPlan> {
+    anyhow::ensure!(price > 0.0, "the share price must be positive");
+    let mut vests = vests.to_vec();
+    vests.sort_by(|a, b| a.date.cmp(&b.date));
+    let (mut income, mut withheld) = (0.0, 0.0);
+    let events = vests
+        .iter()
+        .map(|v| -> Result<RsuVestEvent> {
+            let amount = v.shares * price;
+            income += amount;
+            let tax = 0f64.max(config.salary.tax(income)? - withheld);
+            withheld += tax;
+            let shares_sold = v.shares.min((tax / price).ceil());
+            Ok(RsuVestEvent {
+                date: v.date.clone(),
+                shares: v.shares,
+                income: amount,
+                tax,
+                shares_sold,
+                shares_kept: v.shares - shares_sold,
+                cash: shares_sold * price - tax,
+            })
+        })
+        .try_collect::<Vec<_>>()?;
+    let mut record = record.clone();
+    record.equity = vests
+        .iter()
+        .map(|v| Vest {
+            amount: v.shares * price,
+            date: Some(v.date.clone()),
+        })
+        .collect();
+    let tax = config.calc(&record)?;
+    Ok(RsuPlan {
+        events,
+        record,
+        tax,
+    })
+}
@@ -0,0 +1,266 @@
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{OptimizeOptions, Record, ResidentStatus, TaxConfig};
+
+/// One month of the cumulative withholding (累计预扣法) schedule.
+#[derive(Clone, Debug, Serialize)]
+pub struct Month {
+    /// The month, 1 to 12.
+    pub month: u32,
+    /// The salary paid in the month, including its share of the movement.
+    pub salary: f64,
+    pub social_insurance: f64,
+    /// The taxable income from January to this month.
+    pub cumulative_taxable: f64,
+    /// The tax withheld from the salary of the month.
+    pub withheld: f64,
+    /// The bonuses paid in the month. Only the year bonus is taxed separately.
+    pub bonus: f64,
+    pub bonus_tax: f64,
+    /// The equity income of the month.
+    pub equity: f64,
+    /// The tax withheld from the equity income, cumulative over the year like salary.
+    pub equity_tax: f64,
+    /// What is paid out in the month after tax and social insurance.
+    pub net: f64,
+}
+
+/// Compute the monthly withholding of the record, month by month if its salaries vary, for the
+/// months employed under `config`. The movement is spread evenly over them and every bonus is paid
+/// in `bonus_month`. Each month withholds the tax of the cumulative taxable income, after the
+/// deductions of the months employed so far, minus what the previous months withheld, never less
+/// than zero. Equity income is withheld the same way, apart from salary, in the month of its date,
+/// or the nearest month employed, or in `bonus_month` without one.
+pub fn schedule(config: &TaxConfig, record: &Record, bonus_month: u32) -> Result<Vec<Month>> {
+    let mut allocation = [0.0; 12];
+    for month in config.months() {
+        allocation[month as usize - 1] = record.movement / config.months_employed();
+    }
+    schedule_allocated(config, record, bonus_month, &allocation)
+}
+
+/// Check that the withholding schedule applies to `config`, and return the monthly deduction of
+/// `record` with its social insurance.
+pub(crate) fn monthly_deduction(
+    config: &TaxConfig,
+    record: &Record,
+    bonus_month: u32,
+) -> Result<(f64, f64)> {
+    anyhow::ensure!(
+        (1..=12).contains(&bonus_month),
+        "bonus month must be between 1 and 12"
+    );
+    anyhow::ensure!(
+        config.months().contains(&bonus_month),
+        "bonus month {bonus_month} is not a month employed, {} to {}",
+        config.start_month,
+        config.end_month
+    );
+    anyhow::ensure!(
+        config.resident_status == ResidentStatus::Resident,
+        "non-residents are withheld month by month without accumulation"
+    );
+    anyhow::ensure!(
+        config.separate_bonus,
+        "the withholding schedule needs the separate year bonus of China"
+    );
+    let months = config.months_employed();
+    let social_insurance = config.social_insurance(record) / months;
+    let standard_deduction =
+        config.standard_deduction(config.income_before_standard_deduction(record)) / months;
+    let deduction = record.monthly_tax_deduction
+        + standard_deduction
+        + social_insurance
+        + config.special_deduction.monthly(&record.special)
+        + (config.health_insurance_deduction(record)
+            + config.annuity_deduction(record)
+            + config.private_pension.deduction(record.private_pension)
+            + config.employment_deduction(record))
+            / months;
+    Ok((deduction, social_insurance))
+}
+
+/// [`schedule`] with the movement of the record paid as `allocation`, the part of each month.
+pub fn schedule_allocated(
+    config: &TaxConfig,
+    record: &Record,
+    bonus_month: u32,
+    allocation: &[f64; 12],
+) -> Result<Vec<Month>> {
+    let (deduction, social_insurance) = monthly_deduction(config, record, bonus_month)?;
+    anyhow::ensure!(
+        (1..=12).all(|m| config.months().contains(&m) || allocation[m as usize - 1] == 0.0),
+        "the movement is allocated to a month not employed"
+    );
+    let mut ret = Vec::with_capacity(12);
+    let (mut income, mut withheld) = (0.0, 0.0);
+    let (mut equity_income, mut equity_withheld) = (0.0, 0.0);
+    let allowances = config.allowances(record) / config.months_employed();
+    let taxable_allowances = config.taxable_allowances(record) / config.months_employed();
+    for month in config.months() {
+        let salary = record.salary_of(month) + allocation[month as usize - 1];
+        income += salary + taxable_allowances;
+        if month == bonus_month {
+            income += record.other_bonus();
+        }
+        let employed = (month + 1 - config.start_month) as f64;
+        let cumulative_taxable = 0f64.max(income - deduction * employed);
+        let tax = config
+            .salary
+            .tax(cumulative_taxable)
+            .with_context(|| format!("month {month}"))?;
+        let this = 0f64.max(tax - withheld);
+        withheld += this;
+        let (bonus, bonus_tax) = if month == bonus_month {
+            let tax = config.year_bonus.bonus_tax(record.year_bonus)?;
+            (record.year_bonus + record.other_bonus(), tax)
+        } else {
+            (0.0, 0.0)
+        };
+        let equity = record
+            .equity_payments()
+            .filter(|(paid, _)| {
+                let paid = paid.unwrap_or(bonus_month);
+                paid.clamp(config.start_month, config.end_month) == month
+            })
+            .fold(0.0, |a, (_, amount)| a + amount);
+        equity_income += equity;
+        let equity_tax = 0f64.max(config.salary.tax(equity_income)? - equity_withheld);
+        equity_withheld += equity_tax;
+        ret.push(Month {
+            month,
+            salary,
+            social_insurance,
+            cumulative_taxable,
+            withheld: this,
+            bonus,
+            bonus_tax,
+            equity,
+            equity_tax,
+            net: salary + allowances - social_insurance - this + bonus - bonus_tax + equity
+                - equity_tax,
+        });
+    }
+    Ok(ret)
+}
+
+/// The movement of a record allocated to the months of the year by [`allocate`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Allocation {
+    /// The part of the movement paid with the salary of each month.
+    pub months: Vec<f64>,
+    pub schedule: Vec<Month>,
+    /// The tax withheld ahead of time: the sum over the months of the tax withheld up to them.
+    /// An even spread of the movement is compared with it.
+    pub advance: f64,
+    pub even_advance: f64,
+}
+
+/// The tax withheld ahead of time under `schedule`, see [`Allocation::advance`].
+pub(crate) fn advance(schedule: &[Month]) -> f64 {
+    schedule
+        .iter()
+        .scan(0.0, |withheld, m| {
+            *withheld += m.withheld;
+            Some(*withheld)
+        })
+        .sum()
+}
+
+/// The most grid points the movement is split into by [`allocate`].
+const ALLOCATION_UNITS: usize = 2000;
+
+/// Allocate the movement of `record` to the months of the year so that the cumulative withholding
+/// takes as little tax as possible ahead of time, i.e. the sum over the months of the tax withheld
+/// up to them. The annual tax is the same for every allocation, but the months that receive the
+/// movement decide when it is withheld, and low months may withhold more than the final tax.
+///
+/// A dynamic program over the part of the movement paid up to each month, in multiples of the
+/// granularity of `options` or in at most 2000 steps, with each month's salary, including its
+/// part, at most `max_monthly_salary`.
+pub fn allocate(
+    config: &TaxConfig,
+    record: &Record,
+    bonus_month: u32,
+    options: &OptimizeOptions,
+) -> Result<Allocation> {
+    let movement = record.movement;
+    anyhow::ensure!(
+        movement >= 0.0,
+        "only a movement of year bonus into salary can be allocated"
+    );
+    let (deduction, _) = monthly_deduction(config, record, bonus_month)?;
+    let even = schedule(config, record, bonus_month)?;
+    let unit = match options.granularity {
+        Some(g) => g.max(movement / ALLOCATION_UNITS as f64),
+        None => options.precision.max(movement / ALLOCATION_UNITS as f64),
+    };
+    let n = (movement / unit).round() as usize;
+    let unit = if n == 0 { 0.0 } else { movement / n as f64 };
+    let employed: Vec<u32> = config.months().collect();
+    // The most units each month employed can take.
+    let caps: Vec<usize> = employed
+        .iter()
+        .map(|&month| match options.max_monthly_salary {
+            Some(cap) if unit > 0.0 => {
+                (0f64.max(cap - record.salary_of(month)) / unit + 1e-9).floor() as usize
+            }
+            _ => n,
+        })
+        .collect();
+    anyhow::ensure!(
+        caps.iter().sum::<usize>() >= n,
+        "max_monthly_salary leaves no room for the movement {movement}"
+    );
+    // The tax due on the cumulative income of `month` with `s` units paid up to it.
+    let mut base = 0.0;
+    let mut due = Vec::with_capacity(employed.len());
+    let taxable_allowances = config.taxable_allowances(record) / config.months_employed();
+    for (i, &month) in employed.iter().enumerate() {
+        base += record.salary_of(month) + taxable_allowances;
+        if month == bonus_month {
+            base += record.other_bonus();
+        }
+        let row: Vec<f64> = (0..=n)
+            .map(|s| {
+                let taxable = 0f64.max(base + s as f64 * unit - deduction * (i + 1) as f64);
+                config.salary.tax(taxable)
+            })
+            .try_collect()?;
+        due.push(row);
+    }
+    // best[m][s]: the least advance of the months up to m with s units paid up to it, and the
+    // units paid in month m on that path.
+    let mut best = vec![vec![(f64::INFINITY, 0usize); n + 1]; employed.len()];
+    for s in 0..=n.min(caps[0]) {
+        best[0][s] = (due[0][s], s);
+    }
+    for m in 1..employed.len() {
+        for s in 0..=n {
+            let mut min = (f64::INFINITY, 0);
+            for a in 0..=s.min(caps[m]) {
+                let v = best[m - 1][s - a].0;
+                if v < min.0 {
+                    min = (v, a);
+                }
+            }
+            best[m][s] = (min.0 + due[m][s], min.1);
+        }
+    }
+    let mut months = vec![0.0; 12];
+    let mut s = n;
+    for m in (0..employed.len()).rev() {
+        let a = best[m][s].1;
+        months[employed[m] as usize - 1] = a as f64 * unit;
+        s -= a;
+    }
+    let allocation: [f64; 12] = months.clone().try_into().unwrap();
+    let schedule = schedule_allocated(config, record, bonus_month, &allocation)?;
+    Ok(Allocation {
+        months,
+        advance: advance(&schedule),
+        even_advance: advance(&even),
+        schedule,
+    })
+}
@@ -0,0 +1,212 @@
+use serde_json::{json, Value};
+
+use crate::deduction;
+use crate::REGIONS;
+
+/// A JSON Schema of the config file, in any of its formats, for editors and tools that write
+/// configs. Every key is optional, since a config only replaces tables of its region.
+pub fn config_schema() -> Value {
+    let regions: Vec<_> = REGIONS.iter().map(|(region, _)| *region).collect();
+    let numbers = |keys: &[&str]| -> Value {
+        keys.iter()
+            .map(|k| (k.to_string(), json!({ "$ref": "#/$defs/number" })))
+            .collect::<serde_json::Map<_, _>>()
+            .into()
+    };
+    let strict = |properties: Value| {
+        json!({
+            "type": "object",
+            "properties": properties,
+            "additionalProperties": false,
+        })
+    };
+    let named = |def: &str| {
+        json!({
+            "type": "object",
+            "additionalProperties": { "$ref": format!("#/$defs/{def}") },
+        })
+    };
+    let mut properties = json!({
+        "schema_version": {
+            "const": crate::SCHEMA_VERSION,
+            "description": "The version of the config format, the current one if not given.",
+        },
+        "region": { "enum": regions, "description": "The built-in tables to start from." },
+        "filing_status": { "type": "string" },
+        "filing_statuses": {
+            "type": "object",
+            "description": "The tables of each filing status, replacing those at the top.",
+            "additionalProperties": { "$ref": "#" },
+        },
+        "include": {
+            "description": "Config files merged under this one, relative to it.",
+            "oneOf": [
+                { "type": "string" },
+                { "type": "array", "items": { "type": "string" } },
+            ],
+        },
+        "profile": {
+            "type": "object",
+            "description": "Tables selected with --profile NAME, replacing those at the top.",
+            "additionalProperties": { "$ref": "#" },
+        },
+        "resident_status": { "enum": ["resident", "non_resident"] },
+        "start_month": { "type": "integer", "minimum": 1, "maximum": 12 },
+        "end_month": { "type": "integer", "minimum": 1, "maximum": 12 },
+        "separate_bonus": { "type": "boolean" },
+        "splitting": { "type": "boolean" },
+        "severance_exemption": { "$ref": "#/$defs/number" },
+        "health_insurance_cap": { "$ref": "#/$defs/number" },
+        "foreign_exemption_years": { "type": "integer", "minimum": 0 },
+        "standard_deduction": { "$ref": "#/$defs/number" },
+        "personal_amount": { "$ref": "#/$defs/number" },
+        "salary": { "$ref": "#/$defs/table" },
+        "year_bonus": { "$ref": "#/$defs/table" },
+        "standard_rate": { "$ref": "#/$defs/table" },
+        "employment_deduction": { "$ref": "#/$defs/table" },
+    });
+    // Split, as one `json!` of all of them exceeds the recursion limit of macros.
+    let tables = json!({
+        "standard_deduction_taper": strict(numbers(&["threshold", "ratio"])),
+        "special_deduction": strict(numbers(
+            &[&deduction::KEYS[..], &["serious_medical_threshold"]].concat(),
+        )),
+        "social_insurance": strict(json!({
+            "floor": { "$ref": "#/$defs/number" },
+            "cap": { "$ref": "#/$defs/number" },
+            "annual_floor": { "$ref": "#/$defs/number" },
+            "annual_cap": { "$ref": "#/$defs/number" },
+            "item": { "type": "array", "items": strict(json!({
+                "name": { "type": "string" },
+                "employee": { "$ref": "#/$defs/number" },
+                "employer": { "$ref": "#/$defs/number" },
+                "floor": { "$ref": "#/$defs/number" },
+                "cap": { "$ref": "#/$defs/number" },
+                "annual_floor": { "$ref": "#/$defs/number" },
+                "annual_cap": { "$ref": "#/$defs/number" },
+                "bonus": { "type": "boolean" },
+            })) },
+        })),
+        "payroll_tax": strict(json!({
+            "item": { "type": "array", "items": strict(json!({
+                "name": { "type": "string" },
+                "ratio": { "$ref": "#/$defs/number" },
+                "threshold": { "$ref": "#/$defs/number" },
+                "cap": { "$ref": "#/$defs/number" },
+            })) },
+        })),
+        "state": strict(json!({
+            "name": { "type": "string" },
+            "standard_deduction": { "$ref": "#/$defs/number" },
+            "personal_amount": { "$ref": "#/$defs/number" },
+            "per_capita": { "$ref": "#/$defs/number" },
+            "prior_year": { "type": "boolean" },
+            "table": { "$ref": "#/$defs/table" },
+        })),
+        "rebate": strict(json!({
+            "threshold": { "$ref": "#/$defs/number" },
+            "cap": { "$ref": "#/$defs/number" },
+            "marginal_relief": { "type": "boolean" },
+            "phase_out": { "$ref": "#/$defs/table" },
+        })),
+        "surtax": named("surtax"),
+        "credit": named("credit"),
+        "remuneration": strict(json!({
+            "expense_threshold": { "$ref": "#/$defs/number" },
+            "fixed_expense": { "$ref": "#/$defs/number" },
+            "expense_ratio": { "$ref": "#/$defs/number" },
+            "author_inclusion": { "$ref": "#/$defs/number" },
+            "withholding_ratio": { "$ref": "#/$defs/number" },
+            "labor_withholding": { "$ref": "#/$defs/table" },
+        })),
+        "rental": strict(numbers(
+            &["expense_threshold", "fixed_expense", "expense_ratio", "ratio"],
+        )),
+        "business": strict(json!({
+            "relief": { "$ref": "#/$defs/number" },
+            "relief_cap": { "$ref": "#/$defs/number" },
+            "table": { "$ref": "#/$defs/table" },
+        })),
+        "exempt_allowance": named("exempt_allowance"),
+        "vat": strict(numbers(&["rate", "exemption", "surcharge", "surcharge_relief"])),
+        "private_pension": strict(json!({
+            "cap": { "$ref": "#/$defs/number" },
+            "withdrawal_ratio": { "$ref": "#/$defs/number" },
+            "salary_sacrifice": { "type": "boolean" },
+        })),
+        "supplementary_housing_fund": strict(numbers(&["max_ratio", "base_cap"])),
+        "enterprise_annuity": strict(numbers(
+            &["employee_cap", "employer_cap", "base_cap", "withdrawal_ratio"],
+        )),
+        "donation": strict(numbers(&["cap_ratio"])),
+        "non_resident": strict(json!({
+            "deduction": { "$ref": "#/$defs/number" },
+            "bonus_months": { "$ref": "#/$defs/number" },
+            "table": { "$ref": "#/$defs/table" },
+        })),
+    });
+    if let (Value::Object(properties), Value::Object(tables)) = (&mut properties, tables) {
+        properties.extend(tables);
+    }
+    json!({
+        "$schema": "https://json-schema.org/draft/2020-12/schema",
+        "title": "pto config",
+        "type": "object",
+        "properties": properties,
+        "$defs": {
+            "number": { "type": "number" },
+            "amount": {
+                "description": "A number, or a string with a k or m suffix such as \"36k\".",
+                "oneOf": [
+                    { "type": "number" },
+                    { "type": "string", "pattern": "^\\s*-?[0-9.]+\\s*[kKmM]?\\s*$" },
+                ],
+            },
+            "ratio": {
+                "description": "A float such as 0.1, or a percentage such as 10 or \"10%\".",
+                "oneOf": [
+                    { "type": "number", "minimum": 0, "maximum": 100 },
+                    { "type": "string", "pattern": "^\\s*[0-9.]+\\s*%\\s*$" },
+                ],
+            },
+            "table": strict(json!({
+                "mode": { "enum": ["marginal", "quick_deduction", "formula"] },
+                "rule": { "type": "array", "minItems": 1, "items": { "$ref": "#/$defs/rule" } },
+            })),
+            "rule": {
+                "type": "object",
+                "required": ["bound"],
+                "properties": {
+                    "bound": {
+                        "description": "The upper bound of the bracket, \"inf\" for the last one.",
+                        "oneOf": [{ "$ref": "#/$defs/amount" }, { "const": "inf" }],
+                    },
+                    "ratio": { "$ref": "#/$defs/ratio" },
+                    "deduction": { "$ref": "#/$defs/amount" },
+                    "coefficients": { "type": "array", "items": { "type": "number" } },
+                    "offset": { "$ref": "#/$defs/amount" },
+                    "divisor": { "$ref": "#/$defs/amount" },
+                },
+                "additionalProperties": false,
+            },
+            "surtax": {
+                "type": "object",
+                "required": ["base"],
+                "properties": {
+                    "base": { "enum": ["tax", "salary", "total"] },
+                    "ratio": { "$ref": "#/$defs/number" },
+                    "threshold": { "$ref": "#/$defs/number" },
+                    "phase_in": { "$ref": "#/$defs/number" },
+                },
+                "additionalProperties": false,
+            },
+            "exempt_allowance": strict(numbers(&["ratio", "cap"])),
+            "credit": strict(json!({
+                "amount": { "$ref": "#/$defs/number" },
+                "per": { "enum": deduction::KEYS },
+                "refundable": { "type": "boolean" },
+                "phase_out": strict(numbers(&["threshold", "ratio"])),
+            })),
+        },
+    })
+}
@@ -0,0 +1,101 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::optimize::range;
+use crate::{optimize, thresholds, Optimization, OptimizeOptions, Record, TaxConfig, Threshold};
+
+/// How much the result of [`optimize`] depends on the movement and the salary, see
+/// [`sensitivity`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Sensitivity {
+    /// The movement tried on either side of the best one.
+    pub step: f64,
+    /// The total tax at the best movement less the step, if the constraints allow it.
+    pub below: Option<f64>,
+    /// The total tax at the best movement plus the step, if the constraints allow it.
+    pub above: Option<f64>,
+    /// The best movement with the salary 1% higher and 1% lower.
+    pub salary: Vec<SalaryChange>,
+    /// The bracket bound closest to the best movement, if one lies in the range of movements.
+    pub nearest: Option<Threshold>,
+    /// Whether the best movement is within one step of the precision of the nearest bound, so
+    /// that missing it by a little changes the tax.
+    pub on_edge: bool,
+}
+
+/// The best movement under a change of the salary, see [`Sensitivity::salary`].
+#[derive(Clone, Debug, Serialize)]
+pub struct SalaryChange {
+    /// The change of every month's salary, e.g. 0.01 for 1% more.
+    pub change: f64,
+    pub movement: f64,
+    pub tax: f64,
+    /// The total tax under the changed salary keeping the movement found before.
+    pub kept_tax: f64,
+}
+
+/// How sensitive `best`, the result of [`optimize`] of `record` under `options`, is: the tax with
+/// the movement `step` less and more, the best movement if the salary changes by 1%, and how close
+/// the movement is to a bracket bound.
+pub fn sensitivity(
+    config: &TaxConfig,
+    record: &Record,
+    best: &Optimization,
+    options: &OptimizeOptions,
+    step: f64,
+) -> Result<Sensitivity> {
+    anyhow::ensure!(step > 0.0, "step must be positive");
+    // The record whose year bonus the best movement is of, as another bonus may be taxed
+    // separately instead.
+    let base = best.record.with_movement(config, -best.movement)?;
+    let (min, max) = range(config, &base, options);
+    let tax_at = |movement: f64| -> Result<Option<f64>> {
+        if !(min..=max).contains(&movement) {
+            return Ok(None);
+        }
+        Ok(Some(
+            config.calc(&base.with_movement(config, movement)?)?.total(),
+        ))
+    };
+    let mut salary = Vec::new();
+    for change in [0.01, -0.01] {
+        let mut r = record.clone();
+        r.monthly_salary *= 1.0 + change;
+        if let Some(salaries) = &mut r.salaries {
+            for s in salaries {
+                *s *= 1.0 + change;
+            }
+        }
+        let found = optimize(config, &r, options)?;
+        let mut kept = base.clone();
+        kept.monthly_salary = r.monthly_salary;
+        kept.salaries = r.salaries.clone();
+        salary.push(SalaryChange {
+            change,
+            movement: found.movement,
+            tax: found.after.total(),
+            kept_tax: config
+                .calc(&kept.with_movement(config, best.movement)?)?
+                .total(),
+        });
+    }
+    let nearest = thresholds(config, &base, options)
+        .into_iter()
+        .min_by(|a, b| {
+            (a.movement - best.movement)
+                .abs()
+                .total_cmp(&(b.movement - best.movement).abs())
+        });
+    let precision = options.granularity.unwrap_or(options.precision);
+    let on_edge = nearest
+        .as_ref()
+        .is_some_and(|t| (t.movement - best.movement).abs() <= precision + 1e-9);
+    Ok(Sensitivity {
+        step,
+        below: tax_at(best.movement - step)?,
+        above: tax_at(best.movement + step)?,
+        salary,
+        nearest,
+        on_edge,
+    })
+}
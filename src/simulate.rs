@@ -0,0 +1,190 @@
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Serialize;
+
+use crate::optimize::range;
+use crate::{optimize, OptimizeOptions, Record, TaxConfig};
+
+/// The distribution of an uncertain year bonus, see [`parse_distribution`].
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub enum BonusDistribution {
+    /// A normal distribution, cut off at zero.
+    Normal { mean: f64, sd: f64 },
+    /// Outcomes and their probabilities, which sum to one.
+    Discrete(Vec<(f64, f64)>),
+}
+
+/// Parse the distribution of a year bonus, either `normal:MEAN,SD`, e.g. `normal:150000,30000`,
+/// or `discrete:` and outcomes as `AMOUNT@PROBABILITY` delimited by `;`, e.g.
+/// `discrete:100000@0.3;150000@0.5;200000@0.2`.
+pub fn parse_distribution(arg: &str) -> Result<BonusDistribution> {
+    let number = |s: &str| -> Result<f64> {
+        s.trim()
+            .parse()
+            .with_context(|| format!("invalid number `{s}` in `{arg}`"))
+    };
+    let (kind, spec) = arg
+        .split_once(':')
+        .ok_or_else(|| anyhow!("expected normal:MEAN,SD or discrete:AMOUNT@P;..., got `{arg}`"))?;
+    match kind.trim() {
+        "normal" => {
+            let (mean, sd) = spec
+                .split_once(',')
+                .ok_or_else(|| anyhow!("expected normal:MEAN,SD, got `{arg}`"))?;
+            let (mean, sd) = (number(mean)?, number(sd)?);
+            anyhow::ensure!(sd >= 0.0, "the standard deviation of `{arg}` is negative");
+            Ok(BonusDistribution::Normal { mean, sd })
+        }
+        "discrete" => {
+            let outcomes: Vec<(f64, f64)> = spec
+                .split(';')
+                .map(|o| {
+                    let (amount, p) = o
+                        .split_once('@')
+                        .ok_or_else(|| anyhow!("expected AMOUNT@PROBABILITY, got `{o}`"))?;
+                    let (amount, p) = (number(amount)?, number(p)?);
+                    anyhow::ensure!(amount >= 0.0, "the bonus {amount} is negative");
+                    anyhow::ensure!(p >= 0.0, "the probability {p} is negative");
+                    Ok((amount, p))
+                })
+                .try_collect()?;
+            let total: f64 = outcomes.iter().map(|(_, p)| p).sum();
+            anyhow::ensure!(
+                (total - 1.0).abs() < 1e-6,
+                "the probabilities of `{arg}` sum to {total}, not 1"
+            );
+            Ok(BonusDistribution::Discrete(outcomes))
+        }
+        k => bail!("unknown distribution `{k}`, expected normal or discrete"),
+    }
+}
+
+/// The SplitMix64 generator, small and good enough to draw bonuses, seeded for reproducible
+/// simulations.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// A uniform draw in (0, 1].
+    fn uniform(&mut self) -> f64 {
+        ((self.next() >> 11) + 1) as f64 / (1u64 << 53) as f64
+    }
+}
+
+impl BonusDistribution {
+    fn draw(&self, rng: &mut SplitMix64) -> f64 {
+        match self {
+            Self::Normal { mean, sd } => {
+                // Box-Muller.
+                let (u, v) = (rng.uniform(), rng.uniform());
+                let z = (-2.0 * u.ln()).sqrt() * (std::f64::consts::TAU * v).cos();
+                (mean + sd * z).max(0.0)
+            }
+            Self::Discrete(outcomes) => {
+                let mut u = rng.uniform();
+                for (amount, p) in outcomes {
+                    if u <= *p {
+                        return *amount;
+                    }
+                    u -= p;
+                }
+                outcomes.last().map_or(0.0, |(amount, _)| *amount)
+            }
+        }
+    }
+}
+
+/// A movement fixed before the year bonus is known, see [`Simulation::robust`].
+#[derive(Clone, Debug, Serialize)]
+pub struct FixedMovement {
+    /// The movement agreed, at most the whole bonus of a draw.
+    pub movement: f64,
+    /// The mean total tax over the draws.
+    pub expected_tax: f64,
+    /// The most tax of a draw above the tax of its best movement.
+    pub max_regret: f64,
+}
+
+/// The result of [`simulate`].
+#[derive(Clone, Debug, Serialize)]
+pub struct Simulation {
+    pub draws: usize,
+    /// The mean year bonus drawn.
+    pub mean_bonus: f64,
+    /// The mean total tax with the best movement of each draw, as if the bonus were known.
+    pub expected_tax: f64,
+    /// The best movements of the draws at the 0th, 10th, 50th, 90th and 100th percentiles.
+    pub movement_percentiles: [f64; 5],
+    /// Moving nothing.
+    pub no_movement: FixedMovement,
+    /// The movement of the least expected tax, of those best for a draw.
+    pub robust: FixedMovement,
+}
+
+/// Simulate `draws` year bonuses of `record` from `distribution`, seeded with `seed`, to see how
+/// the best movement under `options` varies, and find the movement to agree before the bonus is
+/// known: the one with the least expected tax of those best for some draw. A fixed movement moves
+/// at most what the constraints of `options` allow with the bonus drawn.
+pub fn simulate(
+    config: &TaxConfig,
+    record: &Record,
+    distribution: &BonusDistribution,
+    draws: usize,
+    seed: u64,
+    options: &OptimizeOptions,
+) -> Result<Simulation> {
+    anyhow::ensure!(draws > 0, "the number of draws must be positive");
+    let mut rng = SplitMix64(seed);
+    let mut outcomes = Vec::with_capacity(draws);
+    for _ in 0..draws {
+        let mut r = record.clone();
+        // Paid in whole cents.
+        r.year_bonus = (distribution.draw(&mut rng) * 100.0).round() / 100.0;
+        let best = optimize(config, &r, options)?;
+        outcomes.push((r, best.movement, best.after.total()));
+    }
+    let mut movements: Vec<f64> = outcomes.iter().map(|(_, m, _)| *m).collect();
+    movements.sort_by(|a, b| a.total_cmp(b));
+    let percentile = |p: usize| movements[(movements.len() - 1) * p / 100];
+    let fixed = |movement: f64| -> Result<FixedMovement> {
+        let (mut sum, mut max_regret) = (0.0, 0.0f64);
+        for (r, _, best) in &outcomes {
+            let (min, max) = range(config, r, options);
+            let tax = config
+                .calc(&r.with_movement(config, movement.clamp(min, max))?)?
+                .total();
+            sum += tax;
+            max_regret = max_regret.max(tax - best);
+        }
+        Ok(FixedMovement {
+            movement,
+            expected_tax: sum / draws as f64,
+            max_regret,
+        })
+    };
+    // Those at every percentile, as trying the movement of every draw against every draw is slow.
+    let mut candidates: Vec<f64> = (0..=100).map(percentile).collect();
+    candidates.dedup();
+    let mut robust = fixed(0.0)?;
+    let no_movement = robust.clone();
+    for m in candidates {
+        let f = fixed(m)?;
+        if f.expected_tax < robust.expected_tax - 0.005 {
+            robust = f;
+        }
+    }
+    Ok(Simulation {
+        draws,
+        mean_bonus: outcomes.iter().map(|(r, _, _)| r.year_bonus).sum::<f64>() / draws as f64,
+        expected_tax: outcomes.iter().map(|(_, _, t)| t).sum::<f64>() / draws as f64,
+        movement_percentiles: [0, 10, 50, 90, 100].map(percentile),
+        no_movement,
+        robust,
+    })
+}
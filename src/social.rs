@@ -0,0 +1,161 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+
+/// A social insurance or housing fund item, contributed as a ratio of the contribution base.
+#[derive(Clone, Debug, Serialize)]
+pub struct Contribution {
+    pub name: String,
+    /// The ratio paid by the employee, deducted before tax.
+    pub employee: f64,
+    /// The ratio paid by the employer.
+    pub employer: f64,
+    /// The floor of the monthly contribution base, overriding the one of [`SocialInsurance`].
+    pub floor: Option<f64>,
+    /// The cap of the monthly contribution base, overriding the one of [`SocialInsurance`].
+    pub cap: Option<f64>,
+    /// The floor of the annual contribution base, overriding the one of [`SocialInsurance`].
+    pub annual_floor: Option<f64>,
+    /// The cap of the annual contribution base, overriding the one of [`SocialInsurance`].
+    pub annual_cap: Option<f64>,
+    /// Whether the bonuses are part of the annual contribution base too, e.g. the additional
+    /// wages of the CPF in Singapore.
+    pub bonus: bool,
+}
+
+/// Social insurance and housing fund (五险一金) contributions.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct SocialInsurance {
+    /// The floor of the monthly contribution base.
+    pub floor: f64,
+    /// The cap of the monthly contribution base, unlimited if zero.
+    pub cap: f64,
+    /// The floor of the annual contribution base, twelve monthly bases plus the bonuses of the
+    /// items that include them.
+    pub annual_floor: f64,
+    /// The cap of the annual contribution base, unlimited if zero.
+    pub annual_cap: f64,
+    pub items: Vec<Contribution>,
+}
+
+impl TryFrom<&toml::Table> for SocialInsurance {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let get = |t: &toml::Table, key: &str| -> Result<Option<f64>> {
+            t.get(key)
+                .map(|v| {
+                    number(v).ok_or_else(|| anyhow!("social_insurance: {key} is not a number"))
+                })
+                .transpose()
+        };
+        let mut items = Vec::new();
+        for item in tbl
+            .get("item")
+            .map(|i| i.as_array().ok_or_else(|| anyhow!("item is not an array")))
+            .transpose()?
+            .into_iter()
+            .flatten()
+        {
+            let item = item
+                .as_table()
+                .ok_or_else(|| anyhow!("social_insurance.item is not a table"))?;
+            items.push(Contribution {
+                name: item
+                    .get("name")
+                    .and_then(|n| n.as_str())
+                    .ok_or_else(|| anyhow!("social_insurance.item: missing name"))?
+                    .to_string(),
+                employee: get(item, "employee")?.unwrap_or(0.0),
+                employer: get(item, "employer")?.unwrap_or(0.0),
+                floor: get(item, "floor")?,
+                cap: get(item, "cap")?,
+                annual_floor: get(item, "annual_floor")?,
+                annual_cap: get(item, "annual_cap")?,
+                bonus: match item.get("bonus") {
+                    Some(toml::Value::Boolean(b)) => *b,
+                    Some(_) => bail!("social_insurance.item: bonus is not a boolean"),
+                    None => false,
+                },
+            });
+        }
+        Ok(Self {
+            floor: get(tbl, "floor")?.unwrap_or(0.0),
+            cap: get(tbl, "cap")?.unwrap_or(0.0),
+            annual_floor: get(tbl, "annual_floor")?.unwrap_or(0.0),
+            annual_cap: get(tbl, "annual_cap")?.unwrap_or(0.0),
+            items,
+        })
+    }
+}
+
+impl SocialInsurance {
+    /// The contribution base of `item` for the monthly `base`.
+    fn base(&self, item: &Contribution, base: f64) -> f64 {
+        if base <= 0.0 {
+            return 0.0;
+        }
+        let cap = item.cap.unwrap_or(self.cap);
+        let base = base.max(item.floor.unwrap_or(self.floor));
+        if cap > 0.0 {
+            base.min(cap)
+        } else {
+            base
+        }
+    }
+
+    /// The annual contribution base of `item` for the monthly `base` paid `months` months and the
+    /// annual `bonus`.
+    fn annual_base(&self, item: &Contribution, base: f64, months: f64, bonus: f64) -> f64 {
+        let mut annual = self.base(item, base) * months;
+        if item.bonus {
+            annual += 0f64.max(bonus);
+        }
+        if annual <= 0.0 {
+            return 0.0;
+        }
+        let cap = item.annual_cap.unwrap_or(self.annual_cap);
+        let annual = annual.max(item.annual_floor.unwrap_or(self.annual_floor));
+        if cap > 0.0 {
+            annual.min(cap)
+        } else {
+            annual
+        }
+    }
+
+    /// The annual contribution of the employee for the monthly `base` paid `months` months and
+    /// the annual `bonus`.
+    pub fn annual_employee(&self, base: f64, months: f64, bonus: f64) -> f64 {
+        self.items
+            .iter()
+            .map(|i| i.employee * self.annual_base(i, base, months, bonus))
+            .fold(0.0, |a, b| a + b)
+    }
+
+    /// The annual contribution of the employer for the monthly `base` paid `months` months and
+    /// the annual `bonus`.
+    pub fn annual_employer(&self, base: f64, months: f64, bonus: f64) -> f64 {
+        self.items
+            .iter()
+            .map(|i| i.employer * self.annual_base(i, base, months, bonus))
+            .fold(0.0, |a, b| a + b)
+    }
+
+    /// The monthly contribution of the employee for the monthly `base`.
+    pub fn employee(&self, base: f64) -> f64 {
+        self.items
+            .iter()
+            .map(|i| i.employee * self.base(i, base))
+            // Unlike `sum`, which gives -0.0 for no items.
+            .fold(0.0, |a, b| a + b)
+    }
+
+    /// The monthly contribution of the employer for the monthly `base`.
+    pub fn employer(&self, base: f64) -> f64 {
+        self.items
+            .iter()
+            .map(|i| i.employer * self.base(i, base))
+            .fold(0.0, |a, b| a + b)
+    }
+}
@@ -0,0 +1,140 @@
+use anyhow::{bail, Result};
+use serde::Serialize;
+
+use crate::{optimize, Optimization, OptimizeOptions, Record, Tax, TaxConfig};
+
+/// The gross package that leaves a net income, see [`solve_gross`].
+#[derive(Clone, Debug, Serialize)]
+pub struct GrossPackage {
+    /// The annual salary and bonuses.
+    pub package: f64,
+    /// The record scaled to the package, before the movement.
+    pub record: Record,
+    /// The best movement of the record.
+    pub best: Optimization,
+}
+
+/// The gross pay of a net-pay agreement, see [`gross_up`].
+#[derive(Clone, Debug, Serialize)]
+pub struct GrossUp {
+    /// The record with the gross monthly salary and year bonus.
+    pub record: Record,
+    pub tax: Tax,
+    /// The rounds of the fixed point iteration, of the salary and the bonus together.
+    pub iterations: usize,
+}
+
+/// The most rounds of [`gross_up`] for each of the salary and the bonus.
+const MAX_ITERATIONS: usize = 100;
+
+/// The gross amount whose net, by `net`, is `target`, by the fixed point iteration of adding what
+/// the net falls short to the gross. Each round leaves the shortfall times the marginal rate, so
+/// it converges unless the tax jumps across the target. Returns the amount and the rounds.
+fn fixed_point(target: f64, mut net: impl FnMut(f64) -> Result<f64>) -> Result<(f64, usize)> {
+    let mut gross = target;
+    for i in 1..=MAX_ITERATIONS {
+        let short = target - net(gross)?;
+        if short.abs() < 1e-4 {
+            return Ok((gross, i));
+        }
+        gross += short;
+    }
+    bail!("no gross amount leaves a net of exactly {target}, the tax may jump across it")
+}
+
+/// A copy of `record` with its salary and bonuses scaled by `factor`.
+pub(crate) fn scaled(record: &Record, factor: f64) -> Record {
+    let mut r = record.clone();
+    r.monthly_salary *= factor;
+    if let Some(salaries) = &mut r.salaries {
+        for s in salaries {
+            *s *= factor;
+        }
+    }
+    r.year_bonus *= factor;
+    for b in &mut r.other_bonuses {
+        *b *= factor;
+    }
+    r
+}
+
+/// The least gross package, the annual salary and bonuses of `record` scaled alike, whose net
+/// income under the best movement of `options` is at least `target_net`, found to the cent by
+/// bisection as the net income rises with the package. The deductions and other income of the
+/// record stay as given.
+pub fn solve_gross(
+    config: &TaxConfig,
+    record: &Record,
+    target_net: f64,
+    options: &OptimizeOptions,
+) -> Result<GrossPackage> {
+    let shape = config.annual_salary(record) + record.year_bonus + record.other_bonus();
+    anyhow::ensure!(shape > 0.0, "the record has no salary or bonus to scale");
+    let net = |package: f64| -> Result<(Record, Optimization)> {
+        let r = scaled(record, package / shape);
+        let best = optimize(config, &r, options)?;
+        Ok((r, best))
+    };
+    let (mut lo, mut hi) = (0.0, target_net.max(1.0));
+    // Doubled until it is enough, at most to amounts no payroll pays.
+    while net(hi)?.1.after.net < target_net {
+        if hi > 1e12 {
+            bail!("no gross package leaves a net income of {target_net}");
+        }
+        (lo, hi) = (hi, hi * 2.0);
+    }
+    while hi - lo > 0.01 {
+        let mid = (lo + hi) / 2.0;
+        if net(mid)?.1.after.net >= target_net {
+            hi = mid;
+        } else {
+            lo = mid;
+        }
+    }
+    let package = (hi * 100.0).ceil() / 100.0;
+    let (record, best) = net(package)?;
+    Ok(GrossPackage {
+        package,
+        record,
+        best,
+    })
+}
+
+/// Gross up a net-pay agreement, in which the employer bears the tax and social insurance: the
+/// gross monthly salary and year bonus of `record` that leave exactly `net_salary` a month on
+/// average and `net_bonus`, its other income and deductions as given. The salary is grossed up
+/// without the bonus first, then the bonus for what it adds to the annual net income, so that it
+/// also works where the bonus is taxed with the salary.
+pub fn gross_up(
+    config: &TaxConfig,
+    record: &Record,
+    net_salary: f64,
+    net_bonus: f64,
+) -> Result<GrossUp> {
+    anyhow::ensure!(
+        net_salary >= 0.0 && net_bonus >= 0.0,
+        "the net salary and bonus must not be negative"
+    );
+    let mut r = record.clone();
+    r.salaries = None;
+    r.year_bonus = 0.0;
+    r.other_bonuses.clear();
+    r.movement = 0.0;
+    let (salary, salary_rounds) = fixed_point(net_salary, |gross| {
+        r.monthly_salary = gross;
+        Ok(config.calc(&r)?.monthly_net)
+    })?;
+    r.monthly_salary = salary;
+    let without = config.calc(&r)?.net;
+    let (bonus, bonus_rounds) = fixed_point(net_bonus, |gross| {
+        let mut with = r.clone();
+        with.year_bonus = gross;
+        Ok(config.calc(&with)?.net - without)
+    })?;
+    r.year_bonus = bonus;
+    Ok(GrossUp {
+        tax: config.calc(&r)?,
+        record: r,
+        iterations: salary_rounds + bonus_rounds,
+    })
+}
@@ -0,0 +1,80 @@
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{Record, TaxConfig};
+
+/// A split of a compensation budget between base salary and year bonus.
+#[derive(Clone, Debug, Serialize)]
+pub struct Split {
+    pub monthly_salary: f64,
+    pub year_bonus: f64,
+    /// The total tax.
+    pub tax: f64,
+    /// The annual net income.
+    pub net: f64,
+}
+
+/// The result of [`split`].
+#[derive(Clone, Debug, Serialize)]
+pub struct SplitAnalysis {
+    /// The split with the most net income, of the least salary if several tie.
+    pub best: Split,
+    /// The splits, from the least salary, at which paying more as base salary stops raising the
+    /// net income, or starts to raise it again.
+    pub breakeven: Vec<Split>,
+}
+
+/// The most steps [`split`] tries.
+const MAX_STEPS: f64 = 100_000.0;
+
+/// Split an annual compensation `budget` between the base salary and the year bonus of `record`
+/// to leave the most net income, trying monthly salaries in steps of `step`. Unlike a movement,
+/// the base salary is also the base of the social insurance, unless the record gives its own.
+/// The deductions and other income of the record stay as given.
+pub fn split(config: &TaxConfig, record: &Record, budget: f64, step: f64) -> Result<SplitAnalysis> {
+    anyhow::ensure!(budget > 0.0, "the budget must be positive");
+    anyhow::ensure!(step > 0.0, "the step must be positive");
+    let months = config.months_employed();
+    let steps = (budget / months / step + 1e-9).floor();
+    anyhow::ensure!(
+        steps <= MAX_STEPS,
+        "the budget has more than {MAX_STEPS} steps of {step}, give a larger step"
+    );
+    let at = |monthly_salary: f64| -> Result<Split> {
+        let mut r = record.clone();
+        r.monthly_salary = monthly_salary;
+        r.salaries = None;
+        r.year_bonus = budget - monthly_salary * months;
+        r.other_bonuses.clear();
+        r.movement = 0.0;
+        let tax = config.calc(&r)?;
+        Ok(Split {
+            monthly_salary,
+            year_bonus: r.year_bonus,
+            tax: tax.total(),
+            net: tax.net,
+        })
+    };
+    let splits: Vec<Split> = (0..=steps as usize)
+        .map(|i| at(i as f64 * step))
+        .try_collect()?;
+    let best = splits
+        .iter()
+        .reduce(|best, s| if s.net > best.net + 0.005 { s } else { best })
+        .unwrap()
+        .clone();
+    let mut breakeven = Vec::new();
+    // Whether the last step that changed the net income raised it.
+    let mut rising = None;
+    for pair in splits.windows(2) {
+        let change = pair[1].net - pair[0].net;
+        if change.abs() < 0.005 {
+            continue;
+        }
+        if rising.is_some_and(|r| r != (change > 0.0)) {
+            breakeven.push(pair[0].clone());
+        }
+        rising = Some(change > 0.0);
+    }
+    Ok(SplitAnalysis { best, breakeven })
+}
@@ -0,0 +1,79 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+use crate::Table;
+
+/// A second income tax on the same income, e.g. of a US state, a Canadian province or a Japanese
+/// municipality.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct StateTax {
+    pub name: String,
+    /// The annual standard deduction of the state, replacing the one of the config.
+    pub standard_deduction: f64,
+    /// The basic personal amount of the state, credited at the lowest ratio of its table.
+    pub personal_amount: f64,
+    /// A flat amount charged on any taxable income, e.g. the per capita levy of the inhabitant
+    /// tax of Japan.
+    pub per_capita: f64,
+    /// Whether the tax paid this year is assessed on the income of the previous year.
+    pub prior_year: bool,
+    pub table: Table,
+}
+
+impl TryFrom<&toml::Table> for StateTax {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            match key.as_str() {
+                "name" => {
+                    ret.name = value
+                        .as_str()
+                        .ok_or_else(|| anyhow!("state.name is not a string"))?
+                        .to_string()
+                }
+                "standard_deduction" => {
+                    ret.standard_deduction = number(value)
+                        .ok_or_else(|| anyhow!("state.standard_deduction is not a number"))?
+                }
+                "personal_amount" => {
+                    ret.personal_amount = number(value)
+                        .ok_or_else(|| anyhow!("state.personal_amount is not a number"))?
+                }
+                "per_capita" => {
+                    ret.per_capita =
+                        number(value).ok_or_else(|| anyhow!("state.per_capita is not a number"))?
+                }
+                "prior_year" => {
+                    ret.prior_year = value
+                        .as_bool()
+                        .ok_or_else(|| anyhow!("state.prior_year is not a boolean"))?
+                }
+                "table" => ret.table = Table::parse("state.table", value)?,
+                _ => bail!("unknown state key `{key}`"),
+            }
+        }
+        Ok(ret)
+    }
+}
+
+impl StateTax {
+    /// The taxable incomes at which the tax of the state changes slope or jumps: the bounds of
+    /// its table, zero, above which the per capita levy is charged, and the personal amount, up to
+    /// which its credit takes the whole tax.
+    pub fn bounds(&self) -> impl Iterator<Item = f64> + '_ {
+        self.table.bounds().chain([0.0, self.personal_amount])
+    }
+
+    /// The tax of the state for the salary `income` before any standard deduction.
+    pub fn tax(&self, income: f64) -> Result<f64> {
+        let taxable = income - self.standard_deduction;
+        if taxable <= 0.0 {
+            return Ok(0.0);
+        }
+        let tax = self.table.tax(taxable)?;
+        Ok(0f64.max(tax - self.personal_amount * self.table.lowest_ratio()) + self.per_capita)
+    }
+}
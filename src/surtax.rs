@@ -0,0 +1,80 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+
+/// What a [`Surtax`] is charged on.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SurtaxBase {
+    /// The income tax, after credits and rebates.
+    #[default]
+    Tax,
+    /// The taxable salary income.
+    Salary,
+    /// The gross income.
+    Total,
+}
+
+/// A tax charged on top of the income tax as a ratio of a [`SurtaxBase`], e.g. the solidarity
+/// surcharge and church tax of Germany, the cess of India or the Medicare levy of Australia.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct Surtax {
+    pub base: SurtaxBase,
+    /// The ratio of the base charged.
+    pub ratio: f64,
+    /// The base up to which nothing is charged.
+    pub threshold: f64,
+    /// The ratio of the base above the threshold that the surtax is limited to, so it phases in
+    /// instead of jumping at the threshold. No limit if zero.
+    pub phase_in: f64,
+}
+
+impl Surtax {
+    /// Parse the surtax `name` of a config.
+    pub(crate) fn parse(name: &str, tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        let mut base = None;
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "base" => {
+                    base = Some(match value.as_str() {
+                        Some("tax") => SurtaxBase::Tax,
+                        Some("salary") => SurtaxBase::Salary,
+                        Some("total") => SurtaxBase::Total,
+                        _ => bail!("surtax.{name}.base is not one of tax, salary or total"),
+                    });
+                    continue;
+                }
+                "ratio" => &mut ret.ratio,
+                "threshold" => &mut ret.threshold,
+                "phase_in" => &mut ret.phase_in,
+                _ => bail!("unknown surtax.{name} key `{key}`"),
+            };
+            *field = number(value).ok_or_else(|| anyhow!("surtax.{name}.{key} is not a number"))?;
+        }
+        ret.base = base.ok_or_else(|| anyhow!("surtax.{name}: missing base"))?;
+        Ok(ret)
+    }
+
+    /// The surtax on `base`.
+    pub fn tax(&self, base: f64) -> f64 {
+        if base <= self.threshold {
+            return 0.0;
+        }
+        let full = base * self.ratio;
+        match self.phase_in {
+            0.0 => full,
+            phase_in => full.min((base - self.threshold) * phase_in),
+        }
+    }
+
+    /// The bases at which the surtax starts and at which its phase-in reaches the full ratio.
+    pub(crate) fn bounds(&self) -> Vec<f64> {
+        let mut ret = vec![self.threshold];
+        if self.phase_in > self.ratio {
+            ret.push(self.threshold * self.phase_in / (self.phase_in - self.ratio));
+        }
+        ret
+    }
+}
@@ -0,0 +1,158 @@
+use serde::Serialize;
+
+/// The tax payment of a record.
+#[derive(Clone, Debug, Serialize)]
+pub struct Tax {
+    pub salary: f64,
+    pub year_bonus: f64,
+    pub severance: f64,
+    pub equity: f64,
+    pub rental: f64,
+    /// The VAT and the surcharges on the revenue invoiced.
+    pub vat: f64,
+    /// The tax for the business income.
+    pub business: f64,
+    /// Payroll taxes on the wages, e.g. FICA.
+    pub payroll: f64,
+    /// The income tax of the state, if the config has one.
+    pub state: f64,
+    /// The taxes charged on top of the income tax, e.g. the solidarity surcharge of Germany.
+    pub surtax: f64,
+    /// The credits against the income tax, including the refunded part of the refundable ones.
+    pub credits: f64,
+    /// The credit for the tax paid abroad, which reduces the tax on the comprehensive income.
+    pub foreign_credit: f64,
+    /// The ratio applied to the last unit of salary income.
+    pub marginal_rate: f64,
+    /// The ratio of the bracket the year bonus falls in.
+    pub bonus_rate: f64,
+    /// The gross annual income, salary plus bonuses, severance, equity, remuneration, rental
+    /// income and the revenue invoiced.
+    pub income: f64,
+    /// The part of the gross income that is neither salary nor bonus, e.g. severance.
+    pub other_income: f64,
+    /// The total tax divided by the gross income.
+    pub effective_rate: f64,
+    /// The annual social insurance and housing fund contributions of the employee.
+    pub social_insurance: f64,
+    /// The annual income after tax, including the tax paid abroad, social insurance and the
+    /// business expenses.
+    pub net: f64,
+    /// The average monthly salary after tax and social insurance, excluding the year bonus.
+    pub monthly_net: f64,
+    /// The months of salary, 12 unless employed for part of the year.
+    pub months: f64,
+}
+
+impl std::fmt::Display for Tax {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:.2} (tax for salary: {:.2}, tax for year bonus: {:.2}",
+            self.total(),
+            self.salary,
+            self.year_bonus
+        )?;
+        if self.severance != 0.0 {
+            write!(f, ", tax for severance: {:.2}", self.severance)?;
+        }
+        if self.equity != 0.0 {
+            write!(f, ", tax for equity: {:.2}", self.equity)?;
+        }
+        if self.rental != 0.0 {
+            write!(f, ", tax for rental: {:.2}", self.rental)?;
+        }
+        if self.vat != 0.0 {
+            write!(f, ", VAT and surcharges: {:.2}", self.vat)?;
+        }
+        if self.business != 0.0 {
+            write!(f, ", tax for business income: {:.2}", self.business)?;
+        }
+        if self.state != 0.0 {
+            write!(f, ", state tax: {:.2}", self.state)?;
+        }
+        if self.payroll != 0.0 {
+            write!(f, ", payroll tax: {:.2}", self.payroll)?;
+        }
+        if self.surtax != 0.0 {
+            write!(f, ", surtax: {:.2}", self.surtax)?;
+        }
+        if self.credits != 0.0 {
+            write!(f, ", credits: {:.2}", self.credits)?;
+        }
+        if self.foreign_credit != 0.0 {
+            write!(f, ", foreign tax credit: {:.2}", self.foreign_credit)?;
+        }
+        f.write_fmt(format_args!(
+            "; marginal rate: {:.2}%, bonus rate: {:.2}%, effective rate: {:.2}%)",
+            self.marginal_rate * 100.0,
+            self.bonus_rate * 100.0,
+            self.effective_rate * 100.0
+        ))?;
+        let rest = if self.other_income != 0.0 {
+            "bonus and others"
+        } else {
+            "year bonus"
+        };
+        // Rounding noise would print as -0.00 when there is nothing but salary.
+        let other = match self.net - self.monthly_net * self.months {
+            x if x.abs() < 0.005 => 0.0,
+            x => x,
+        };
+        write!(
+            f,
+            "\n  net income: {:.2} (monthly salary: {:.2}, {rest}: {other:.2})",
+            self.net, self.monthly_net,
+        )?;
+        if self.social_insurance != 0.0 {
+            write!(f, "\n  social insurance: {:.2}", self.social_insurance)?;
+        }
+        Ok(())
+    }
+}
+
+impl Tax {
+    pub fn total(&self) -> f64 {
+        self.salary
+            + self.year_bonus
+            + self.severance
+            + self.equity
+            + self.rental
+            + self.vat
+            + self.business
+            + self.state
+            + self.payroll
+            + self.surtax
+            - self.credits
+            - self.foreign_credit
+    }
+
+    /// The tax of a year in which the tables change, from the tax of the same income under each
+    /// version of the tables weighted by the share of the year it is in force.
+    pub fn prorate(parts: &[(f64, Tax)]) -> Self {
+        let sum = |field: fn(&Tax) -> f64| parts.iter().map(|(w, t)| w * field(t)).sum();
+        Self {
+            salary: sum(|t| t.salary),
+            year_bonus: sum(|t| t.year_bonus),
+            severance: sum(|t| t.severance),
+            equity: sum(|t| t.equity),
+            rental: sum(|t| t.rental),
+            vat: sum(|t| t.vat),
+            business: sum(|t| t.business),
+            payroll: sum(|t| t.payroll),
+            state: sum(|t| t.state),
+            surtax: sum(|t| t.surtax),
+            credits: sum(|t| t.credits),
+            foreign_credit: sum(|t| t.foreign_credit),
+            marginal_rate: sum(|t| t.marginal_rate),
+            bonus_rate: sum(|t| t.bonus_rate),
+            income: sum(|t| t.income),
+            other_income: sum(|t| t.other_income),
+            effective_rate: sum(|t| t.effective_rate),
+            social_insurance: sum(|t| t.social_insurance),
+            net: sum(|t| t.net),
+            monthly_net: sum(|t| t.monthly_net),
+            months: sum(|t| t.months),
+        }
+    }
+}
@@ -0,0 +1,64 @@
+use anyhow::{anyhow, bail, Result};
+use serde::Serialize;
+
+use crate::config::number;
+
+/// Rules of the VAT (增值税) a small-scale taxpayer pays on the revenue it invoices, e.g. a sole
+/// proprietorship (个体工商户) billing freelance work, and the surcharges on it.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct VatRules {
+    /// The ratio of the revenue before VAT.
+    pub rate: f64,
+    /// Months whose revenue before VAT is at most this amount are exempt.
+    pub exemption: f64,
+    /// The ratio of the VAT charged as surcharges, e.g. the urban maintenance and construction tax
+    /// and the education surcharges.
+    pub surcharge: f64,
+    /// The part of the surcharges waived for small-scale taxpayers.
+    pub surcharge_relief: f64,
+}
+
+impl TryFrom<&toml::Table> for VatRules {
+    type Error = anyhow::Error;
+
+    fn try_from(tbl: &toml::Table) -> Result<Self> {
+        let mut ret = Self::default();
+        for (key, value) in tbl {
+            let field = match key.as_str() {
+                "rate" => &mut ret.rate,
+                "exemption" => &mut ret.exemption,
+                "surcharge" => &mut ret.surcharge,
+                "surcharge_relief" => &mut ret.surcharge_relief,
+                _ => bail!("unknown vat key `{key}`"),
+            };
+            *field = number(value).ok_or_else(|| anyhow!("vat.{key} is not a number"))?;
+        }
+        Ok(ret)
+    }
+}
+
+impl VatRules {
+    /// The revenue of an invoice of `amount`, VAT included, before VAT.
+    pub fn revenue(&self, amount: f64) -> f64 {
+        amount / (1.0 + self.rate)
+    }
+
+    /// The VAT for the revenue invoiced in one month, VAT included.
+    pub fn vat(&self, amount: f64) -> f64 {
+        let revenue = self.revenue(amount);
+        match revenue <= self.exemption {
+            true => 0.0,
+            false => revenue * self.rate,
+        }
+    }
+
+    /// The surcharges on the VAT of one month, less the relief.
+    pub fn surcharge(&self, amount: f64) -> f64 {
+        self.vat(amount) * self.surcharge * (1.0 - self.surcharge_relief)
+    }
+
+    /// The VAT and the surcharges for the revenue invoiced in one month, VAT included.
+    pub fn tax(&self, amount: f64) -> f64 {
+        self.vat(amount) + self.surcharge(amount)
+    }
+}
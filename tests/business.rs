@@ -0,0 +1,26 @@
+use pto::TaxConfig;
+
+#[test]
+fn business_relief_only_on_the_part_up_to_the_cap() -> anyhow::Result<()> {
+    let config = TaxConfig::default();
+    let rules = config
+        .business
+        .as_ref()
+        .expect("China taxes business income");
+    let cap = rules.relief_cap;
+    let full = rules.table.tax(cap + 200_000.0)?;
+    let relieved = full - rules.relief * full * cap / (cap + 200_000.0);
+    assert!((rules.tax(cap + 200_000.0)? - relieved).abs() < 0.01);
+    // No cliff at the cap: every 10000 more income is taxed at most at the top rate.
+    let mut last = rules.tax(cap - 200_000.0)?;
+    for income in (1..=40).map(|i| cap - 200_000.0 + i as f64 * 10_000.0) {
+        let tax = rules.tax(income)?;
+        assert!(
+            tax >= last && tax - last <= 3500.01,
+            "the tax jumps at {income}"
+        );
+        last = tax;
+    }
+    assert_eq!(rules.tax(0.0)?, 0.0);
+    Ok(())
+}
@@ -0,0 +1,79 @@
+use pto::TaxConfig;
+
+#[test]
+fn missing_year_bonus_table_is_an_error() {
+    let tbl: toml::Table = toml::from_str(
+        r#"
+        separate_bonus = true
+
+        [salary]
+        mode = "quick_deduction"
+
+        [[salary.rule]]
+        bound = "inf"
+        ratio = 0.03
+        deduction = 0
+        "#,
+    )
+    .unwrap();
+    let Err(err) = TaxConfig::try_from(tbl) else {
+        panic!("a config without a year bonus table");
+    };
+    assert!(
+        err.to_string().contains("missing table year_bonus"),
+        "{err}"
+    );
+}
+
+#[test]
+fn quick_deduction_matches_marginal() {
+    let quick = TaxConfig::default();
+    let rules: String = [
+        (36000, 0.03),
+        (144000, 0.1),
+        (300000, 0.2),
+        (420000, 0.25),
+        (660000, 0.3),
+        (960000, 0.35),
+    ]
+    .iter()
+    .map(|(bound, ratio)| format!("[[salary.rule]]\nbound = {bound}\nratio = {ratio}\n"))
+    .collect();
+    let tbl: toml::Table = toml::from_str(&format!(
+        "separate_bonus = false\n[salary]\nmode = \"marginal\"\n{rules}\
+         [[salary.rule]]\nbound = \"inf\"\nratio = 0.45\n"
+    ))
+    .unwrap();
+    let Ok(marginal) = TaxConfig::try_from(tbl) else {
+        panic!("a marginal salary table");
+    };
+    for bound in quick.salary.bounds().chain([0.0, 1e6, 2e6]) {
+        for amount in [bound - 1.0, bound, bound + 1.0].map(|a| a.max(0.0)) {
+            let (q, m) = (
+                quick.salary.tax(amount).unwrap(),
+                marginal.salary.tax(amount).unwrap(),
+            );
+            assert!((q - m).abs() < 1e-6, "{amount}: {q} != {m}");
+        }
+    }
+}
+
+#[test]
+fn german_formula_matches_the_published_tax() {
+    let Ok(config) = TaxConfig::try_from(pto::region_preset("de-2024").unwrap()) else {
+        panic!("the German tables");
+    };
+    // The tax of § 32a EStG 2024, rounded down to the euro, at the edges of its zones and inside.
+    for (income, tax) in [
+        (11604.0, 0.0),
+        (17005.0, 1025.0),
+        (20000.0, 1759.0),
+        (50000.0, 10906.0),
+        (66760.0, 17437.0),
+        (277825.0, 106084.0),
+        (300000.0, 116063.0),
+    ] {
+        let computed = config.salary.tax(income).unwrap().floor();
+        assert_eq!(computed, tax, "{income}");
+    }
+}
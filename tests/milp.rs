@@ -0,0 +1,30 @@
+#![cfg(feature = "milp")]
+
+use pto::{optimize, parse_record, solve_milp, OptimizeOptions, TaxConfig};
+
+#[test]
+fn milp_matches_optimize() -> anyhow::Result<()> {
+    let config = TaxConfig::default();
+    let options = OptimizeOptions {
+        granularity: Some(100.0),
+        ..Default::default()
+    };
+    for record in [
+        "salary=10000,bonus=37000",
+        "salary=30000,bonus=150000",
+        "salary=20000,deduction=5000,bonus=80000",
+    ] {
+        let record = parse_record(record)?;
+        let (milp, _) = solve_milp(&config, &record, 12, &options)?;
+        let best = optimize(&config, &record, &options)?;
+        assert!(
+            (milp.after.total() - best.after.total()).abs() < 0.01,
+            "{record}: {} at {} != {} at {}",
+            milp.after.total(),
+            milp.movement,
+            best.after.total(),
+            best.movement
+        );
+    }
+    Ok(())
+}
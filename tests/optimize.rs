@@ -0,0 +1,81 @@
+use pto::{optimize, parse_record, region_preset, OptimizeOptions, TaxConfig, DEFAULT_CONFIG};
+
+/// Check that the exact search finds a movement as good as a sweep in steps of `step`.
+fn assert_exact(config: &TaxConfig, record: &str, step: f64) -> anyhow::Result<()> {
+    let record = parse_record(record)?;
+    let exact = optimize(config, &record, &Default::default())?;
+    let options = OptimizeOptions {
+        step: Some(step),
+        ..Default::default()
+    };
+    let sweep = optimize(config, &record, &options)?;
+    assert!(
+        exact.after.total() <= sweep.after.total() + 0.01,
+        "{record}: {} at {} > {} at {}",
+        exact.after.total(),
+        exact.movement,
+        sweep.after.total(),
+        sweep.movement
+    );
+    Ok(())
+}
+
+#[test]
+fn state_tax_bounds_are_candidates() -> anyhow::Result<()> {
+    let mut tbl: toml::Table = toml::from_str(DEFAULT_CONFIG)?;
+    let state: toml::Table = toml::from_str(
+        r#"
+        name = "city"
+        standard_deduction = 70000
+        per_capita = 3000
+
+        [table]
+        mode = "marginal"
+
+        [[table.rule]]
+        bound = 50000
+        ratio = 0.02
+
+        [[table.rule]]
+        bound = "inf"
+        ratio = 0.06
+        "#,
+    )?;
+    tbl.insert("state".to_string(), state.into());
+    let config = TaxConfig::try_from(tbl)?;
+    for record in ["salary=5000,bonus=100000", "salary=20000,bonus=200000"] {
+        assert_exact(&config, record, 1.0)?;
+    }
+    Ok(())
+}
+
+#[test]
+fn employment_deduction_bounds_are_candidates() -> anyhow::Result<()> {
+    let config = TaxConfig::try_from(region_preset("jp-2024")?)?;
+    assert_exact(&config, "salary=100000,bonus=200000", 1.0)?;
+    // With the year bonus taxed separately, the movement shifts the wages the deduction applies
+    // to, and the taxable income only moves with it above the wages it deducts in whole.
+    let mut tbl = region_preset("jp-2024")?;
+    let cn: toml::Table = toml::from_str(DEFAULT_CONFIG)?;
+    tbl.insert("separate_bonus".to_string(), true.into());
+    tbl.insert("year_bonus".to_string(), cn["year_bonus"].clone());
+    let config = TaxConfig::try_from(tbl)?;
+    assert_exact(&config, "salary=40000,bonus=600000", 1.0)
+}
+
+#[test]
+fn exact_search_matches_a_sweep() -> anyhow::Result<()> {
+    let config = TaxConfig::default();
+    // The bonuses of the first two fall in trap intervals, above 36000 and 144000.
+    assert!(config.bonus_trap(37000.0).is_some());
+    assert!(config.bonus_trap(150000.0).is_some());
+    for record in [
+        "salary=10000,bonus=37000",
+        "salary=30000,bonus=150000",
+        "salary=20000,deduction=5000,bonus=80000",
+        "salary=60000,bonus=120000",
+    ] {
+        assert_exact(&config, record, 1.0)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use pto::{annuity, parse_record, TaxConfig};
+
+#[test]
+fn annuity_of_a_mid_year_joiner_counts_the_months_employed() -> anyhow::Result<()> {
+    let config = TaxConfig {
+        start_month: 7,
+        ..Default::default()
+    };
+    let record = parse_record("salary=30000,bonus=0,annuity=0.04,employer_annuity=0.08")?;
+    let base = config.annuity_base(&record);
+    let result = annuity(&config, &record)?;
+    assert!((result.employee - base * 0.04 * 6.0).abs() < 0.01);
+    assert!((result.employer - base * 0.08 * 6.0).abs() < 0.01);
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use pto::{parse_record, reconcile, schedule, TaxConfig};
+
+/// The tax withheld by the schedule of `record`, salary and bonus.
+fn withheld(config: &TaxConfig, record: &pto::Record) -> anyhow::Result<f64> {
+    Ok(schedule(config, record, config.end_month)?
+        .iter()
+        .map(|m| m.withheld + m.bonus_tax)
+        .sum())
+}
+
+#[test]
+fn annuity_and_pension_are_deducted_when_withheld() -> anyhow::Result<()> {
+    let config = TaxConfig::default();
+    for record in [
+        "salary=30000,bonus=0,annuity=0.04",
+        "salary=30000,bonus=0,private_pension=12000",
+        "salary=30000,bonus=50000,annuity=0.04,private_pension=12000",
+    ] {
+        let record = parse_record(record)?;
+        let result = reconcile(&config, &record, withheld(&config, &record)?)?;
+        assert!(result.balance.abs() < 0.01, "{record}: {}", result.balance);
+    }
+    Ok(())
+}
+
+#[test]
+fn health_insurance_is_deducted_in_the_months_employed() -> anyhow::Result<()> {
+    let config = TaxConfig {
+        start_month: 5,
+        ..Default::default()
+    };
+    let record = parse_record("salary=30000,bonus=0,health_insurance=2400")?;
+    let result = reconcile(&config, &record, withheld(&config, &record)?)?;
+    assert!(result.balance.abs() < 0.01, "{}", result.balance);
+    Ok(())
+}
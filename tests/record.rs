@@ -0,0 +1,14 @@
+use pto::{parse_record, TaxConfig};
+
+#[test]
+fn reverse_movement_is_at_most_the_salary_paid() -> anyhow::Result<()> {
+    let config = TaxConfig {
+        start_month: 7,
+        ..Default::default()
+    };
+    let record = parse_record("salary=10000,bonus=0")?;
+    let moved = record.with_movement(&config, -1e6)?;
+    assert_eq!(moved.movement, -60000.0);
+    assert_eq!(moved.year_bonus, 60000.0);
+    Ok(())
+}
@@ -0,0 +1,66 @@
+use pto::{allocate, evaluate, parse_record, schedule, OptimizeOptions, TaxConfig};
+
+#[test]
+fn allocation_with_taxable_allowances_beats_an_even_spread() -> anyhow::Result<()> {
+    let config = TaxConfig::default();
+    let options = OptimizeOptions {
+        granularity: Some(500.0),
+        ..Default::default()
+    };
+    for (salaries, allowance, movement) in [
+        (
+            "3000;3000;3000;3000;3000;3000;6000;6000;6000;6000;6000;6000",
+            20000,
+            10000.0,
+        ),
+        (
+            "8000;8000;8000;8000;8000;8000;16000;16000;16000;16000;16000;16000",
+            3000,
+            30000.0,
+        ),
+        (
+            "20000;20000;20000;20000;20000;20000;20000;20000;20000;20000;20000;20000",
+            6000,
+            60000.0,
+        ),
+    ] {
+        let record = parse_record(&format!(
+            "salaries={salaries},bonus=200000,allowances=car:{allowance}"
+        ))?
+        .with_movement(&config, movement)?;
+        let allocation = allocate(&config, &record, 12, &options)?;
+        assert!(
+            allocation.advance <= allocation.even_advance + 1e-6,
+            "{record}: {} > {}",
+            allocation.advance,
+            allocation.even_advance
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn withholding_adds_up_to_the_salary_tax() -> anyhow::Result<()> {
+    for start_month in [1, 5] {
+        let config = TaxConfig {
+            start_month,
+            ..Default::default()
+        };
+        for record in [
+            "salary=30000,deduction=2000,bonus=50000",
+            "salary=12000,bonus=0",
+        ] {
+            let record = parse_record(record)?;
+            let withheld: f64 = schedule(&config, &record, 12)?
+                .iter()
+                .map(|m| m.withheld)
+                .sum();
+            let tax = evaluate(&config, &record, 0.0)?.after.salary;
+            assert!(
+                (withheld - tax).abs() < 0.01,
+                "{record} from month {start_month}: {withheld} != {tax}"
+            );
+        }
+    }
+    Ok(())
+}